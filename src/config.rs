@@ -1,19 +1,202 @@
+use crate::logging::warn;
+use crate::patterns::{Pattern, CATEGORY_PREFIX};
+use crate::size::ByteSize;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Where a pattern's sentinel file must be found relative to the matched directory.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SentinelLocation {
+    /// Next to the matched directory (e.g. `package.json` beside `node_modules`).
+    #[default]
+    Parent,
+    /// Within the matched directory itself (e.g. `pyvenv.cfg` in `.venv`).
+    Inside,
+}
+
+impl SentinelLocation {
+    fn is_parent(&self) -> bool {
+        *self == SentinelLocation::Parent
+    }
+}
+
+/// How many patterns are checked for a directory that several could claim.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchPolicy {
+    /// Check every candidate and record all that match (e.g. `composer` and
+    /// `bundler` for one `vendor` directory).
+    #[default]
+    All,
+    /// Stop at the most specific match, skipping the other sentinel checks.
+    First,
+}
+
+impl MatchPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchPolicy::All => "all",
+            MatchPolicy::First => "first",
+        }
+    }
+}
+
+/// What kind of filesystem entry a sentinel must be.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SentinelType {
+    /// A file (e.g. `package.json`).
+    File,
+    /// A directory (e.g. a `.git` directory or an `*.xcodeproj` bundle).
+    Dir,
+    /// Either.
+    #[default]
+    Any,
+}
+
+impl SentinelType {
+    fn is_any(&self) -> bool {
+        *self == SentinelType::Any
+    }
+
+    /// Whether an entry with this metadata qualifies.
+    pub fn accepts(self, meta: &std::fs::Metadata) -> bool {
+        match self {
+            SentinelType::File => meta.is_file(),
+            SentinelType::Dir => meta.is_dir(),
+            SentinelType::Any => true,
+        }
+    }
+}
+
+/// What kind of filesystem entry a pattern matches.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternKind {
+    /// A directory named `directory` (e.g. `node_modules`).
+    #[default]
+    Directory,
+    /// A single file whose name matches the `directory` glob (e.g. `*.qcow2`).
+    File,
+    /// A symlink whose name matches the `directory` glob, pointing at build
+    /// output kept elsewhere (e.g. Bazel's `bazel-out`). Built-in only.
+    #[serde(skip)]
+    Link,
+}
+
+impl PatternKind {
+    fn is_directory(&self) -> bool {
+        *self == PatternKind::Directory
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CustomPattern {
     pub name: String,
+    /// Directory name, or a file name glob for `kind = "file"`.
     pub directory: String,
+
+    /// Required for directory patterns, unless `match_anywhere` is set; optional
+    /// for file patterns.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub sentinel: String,
+
+    /// Match the directory name wherever it appears, with no sentinel (e.g. a
+    /// company-wide `.cache` folder that is always disposable).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub match_anywhere: bool,
+
+    #[serde(default, skip_serializing_if = "SentinelType::is_any")]
+    pub sentinel_type: SentinelType,
+
+    /// Further entries that must all exist next to the sentinel. A trailing `/`
+    /// requires a directory (e.g. "Assets/").
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub all_sentinels: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "PatternKind::is_directory")]
+    pub kind: PatternKind,
+
+    #[serde(default, skip_serializing_if = "SentinelLocation::is_parent")]
+    pub sentinel_location: SentinelLocation,
+
+    /// Also look for the sentinel this many directory levels up, counting the
+    /// usual location as 1 (e.g. 3 for workspace packages two levels below the root).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentinel_depth: Option<usize>,
+
+    /// Only exclude matches at least this large (e.g. "200MB"). Required for file
+    /// patterns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<ByteSize>,
+
+    /// Only exclude matches untouched for at least this many days
+    /// (overrides the global `older_than_days`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than_days: Option<u64>,
+
+    /// Keep scanning inside matches, so projects nested in them (e.g. vendored
+    /// checkouts in a `build` directory) are matched too.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub descend: bool,
+
+    /// Typical size of a match (e.g. "50MB–1GB"), shown by `patterns info` and
+    /// `run --dry-run --verbose`. Informational only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_size_hint: Option<String>,
+
+    /// Labels that `disable_tags` and `run --include-tag`/`--exclude-tag` go by.
+    /// The first is the pattern's category.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// What the pattern matches, shown by `patterns list` and `patterns info`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl CustomPattern {
+    /// A directory pattern with its sentinel in the project directory, the
+    /// shape `patterns add` creates.
+    pub fn new(name: &str, directory: &str, sentinel: &str) -> Self {
+        CustomPattern {
+            name: name.to_string(),
+            directory: directory.to_string(),
+            sentinel: sentinel.to_string(),
+            match_anywhere: false,
+            sentinel_type: SentinelType::Any,
+            all_sentinels: Vec::new(),
+            kind: PatternKind::Directory,
+            sentinel_location: SentinelLocation::Parent,
+            sentinel_depth: None,
+            min_size: None,
+            older_than_days: None,
+            descend: false,
+            expected_size_hint: None,
+            tags: Vec::new(),
+            description: None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
+    /// Further config files layered onto this one (see `IncludedConfig`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
     #[serde(default = "default_scan_roots")]
     pub scan_roots: Vec<String>,
 
+    /// Walk `scan_roots` for pattern matches. When false, runs only apply the
+    /// exclude paths, as with `run --exclude-paths-only`.
+    #[serde(default = "default_scan_enabled")]
+    pub scan_enabled: bool,
+
     /// Additional paths to exclude from backups (on top of built-ins).
     #[serde(default)]
     pub extra_exclude_paths: Vec<String>,
@@ -25,14 +208,168 @@ pub struct Config {
     #[serde(default)]
     pub disable_patterns: Vec<String>,
 
+    /// Off-by-default built-in patterns to turn on, by name or group.
+    #[serde(default)]
+    pub enable_patterns: Vec<String>,
+
+    /// Turn off every pattern, built-in or custom, carrying one of these tags
+    /// (e.g. "javascript").
+    #[serde(default)]
+    pub disable_tags: Vec<String>,
+
+    #[serde(default)]
+    pub custom_patterns: Vec<CustomPattern>,
+
+    /// Number of paths to check and exclude in parallel during `run`.
+    #[serde(default = "default_exclusion_parallelism")]
+    pub exclusion_parallelism: usize,
+
+    /// Only exclude pattern matches untouched for at least this many days.
+    /// Off by default; exclude paths are never age-filtered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than_days: Option<u64>,
+
+    /// Only exclude pattern matches at least this large, unless the pattern sets
+    /// its own `min_size`. Exclude paths are never size-filtered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<ByteSize>,
+
+    /// Per-pattern `min_size` by pattern name, for built-in and custom patterns
+    /// alike. Takes precedence over the pattern's own threshold.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pattern_min_size: BTreeMap<String, ByteSize>,
+
+    /// Follow symlinks while scanning. Each physical directory is still walked once.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+
+    /// Don't descend into hidden directories, except those named by an active
+    /// pattern (e.g. `.venv`).
+    #[serde(default)]
+    pub skip_hidden: bool,
+
+    /// Also exclude the directories a git repository's `.gitignore` lists at its
+    /// top level, when no pattern matches them.
+    #[serde(default)]
+    pub use_gitignore: bool,
+
+    /// Whether a directory records every pattern that matches it, or only the
+    /// most specific one.
+    #[serde(default)]
+    pub match_policy: MatchPolicy,
+
+    /// Stop scanning after this many seconds and apply what was found so far.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_time_budget: Option<u64>,
+
+    /// Show a macOS notification summarizing each (non-dry) run.
     #[serde(default)]
+    pub notify_on_run: bool,
+
+    /// Check and change exclusions with `tmutil` rather than through their
+    /// attribute directly. Only read from the main config file.
+    #[serde(default)]
+    pub use_tmutil: bool,
+
+    /// Keep the entries of earlier runs in the state file, so `list` shows
+    /// everything tmignore has ever excluded rather than just the last run.
+    #[serde(default)]
+    pub accumulate_state: bool,
+
+    /// Write run metrics here after each run (Prometheus text, or JSON for `.json`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_path: Option<String>,
+
+    /// Named overlays activated with `tmignore run --profile=<name>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ConfigProfile>,
+
+    /// Pattern changes for everything under a path, by path (usually a scan root).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub root_patterns: BTreeMap<String, RootPatterns>,
+}
+
+/// Patterns that differ under one path (`[root_patterns."/Volumes/Archive"]`),
+/// e.g. an external drive laid out by another machine. They apply on top of the
+/// patterns used everywhere else.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RootPatterns {
+    /// Patterns not used under the path, by name, group or category.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disable_patterns: Vec<String>,
+
+    /// Patterns only used under the path.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_patterns: Vec<CustomPattern>,
+}
+
+/// A named set of overrides layered onto the base config (`[profiles.<name>]`).
+/// List fields append to the base, except `scan_roots` which replaces it when set.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ConfigProfile {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scan_roots: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_exclude_paths: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disable_exclude_paths: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disable_patterns: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub custom_patterns: Vec<CustomPattern>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclusion_parallelism: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than_days: Option<u64>,
+}
+
+/// A config file pulled in with `include`, e.g. a machine-local overlay on a
+/// shared base config. Its lists are appended to the including config, and
+/// `scan_roots` replaces it when set, so the last include that sets it wins.
+/// Included files may include further files.
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct IncludedConfig {
+    #[serde(default)]
+    include: Vec<String>,
+
+    #[serde(default)]
+    scan_roots: Vec<String>,
+
+    #[serde(default)]
+    extra_exclude_paths: Vec<String>,
+
+    #[serde(default)]
+    disable_exclude_paths: Vec<String>,
+
+    #[serde(default)]
+    disable_patterns: Vec<String>,
+
+    #[serde(default)]
+    custom_patterns: Vec<CustomPattern>,
 }
 
+/// Upper bound for `exclusion_parallelism` and `run --concurrency`.
+pub const MAX_PARALLELISM: usize = 64;
+
 fn default_scan_roots() -> Vec<String> {
     vec!["~".to_string()]
 }
 
+fn default_scan_enabled() -> bool {
+    true
+}
+
+fn default_exclusion_parallelism() -> usize {
+    1
+}
+
 /// System directories the scanner should never walk into.
 /// These are not excluded from backups, just skipped for scanning.
 const SYSTEM_SKIP_PATHS: &[&str] = &[
@@ -72,6 +409,15 @@ pub fn builtin_exclude_paths() -> Vec<&'static str> {
         "~/.npm-global",
         "~/.bundle/cache",
         "~/.cache",
+        // Bazel repository and disk caches (inside ~/.cache, listed so they stay
+        // excluded if that is disabled)
+        "~/.cache/bazel",
+        "~/.cache/bazelisk",
+        // Model, dataset and package caches for data science / ML
+        "~/.cache/huggingface",
+        "~/.cache/torch",
+        "~/.cache/pip",
+        "~/.conda/pkgs",
         // Homebrew
         "/opt/homebrew",
         // Nix / Devbox
@@ -87,22 +433,261 @@ pub fn builtin_exclude_paths() -> Vec<&'static str> {
         "~/Library/Developer/Xcode/watchOS DeviceSupport",
         "~/Library/Developer/Xcode/tvOS DeviceSupport",
         "~/Library/Developer/CoreSimulator/Devices",
+        // Android SDK, emulator images and build cache, all re-downloadable
+        "~/Library/Android/sdk",
+        "~/.android/avd",
+        "~/.android/cache",
     ]
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            include: Vec::new(),
             scan_roots: default_scan_roots(),
+            scan_enabled: true,
             extra_exclude_paths: Vec::new(),
             disable_exclude_paths: Vec::new(),
             disable_patterns: Vec::new(),
+            enable_patterns: Vec::new(),
+            disable_tags: Vec::new(),
             custom_patterns: Vec::new(),
+            exclusion_parallelism: default_exclusion_parallelism(),
+            older_than_days: None,
+            min_size: None,
+            pattern_min_size: BTreeMap::new(),
+            follow_symlinks: false,
+            skip_hidden: false,
+            use_gitignore: false,
+            match_policy: MatchPolicy::All,
+            scan_time_budget: None,
+            notify_on_run: false,
+            use_tmutil: false,
+            accumulate_state: false,
+            metrics_path: None,
+            profiles: BTreeMap::new(),
+            root_patterns: BTreeMap::new(),
+        }
+    }
+}
+
+/// How serious a `ConfigIssue` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found by `Config::issues`.
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub message: String,
+    /// Text to look for in the config source to point at the offending line.
+    pub needle: String,
+}
+
+impl ConfigIssue {
+    fn error(message: String, needle: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            needle,
+        }
+    }
+
+    fn warning(message: String, needle: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            needle,
+        }
+    }
+}
+
+/// The 1-based number of the first line in `contents` that isn't a comment and
+/// contains `needle`.
+pub fn find_line(contents: &str, needle: &str) -> Option<usize> {
+    contents
+        .lines()
+        .position(|line| !line.trim_start().starts_with('#') && line.contains(needle))
+        .map(|i| i + 1)
+}
+
+/// The 1-based line number of a byte offset in `contents`.
+pub fn line_at(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())].matches('\n').count() + 1
+}
+
+/// A single semantic difference between two configs, as reported by `Config::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigChange {
+    ChangedScanRoot(Vec<String>),
+    AddedExcludePath(String),
+    RemovedExcludePath(String),
+    DisabledExcludePath(String),
+    EnabledExcludePath(String),
+    AddedPattern(String),
+    RemovedPattern(String),
+    DisabledPattern(String),
+    EnabledPattern(String),
+    ChangedSetting {
+        key: &'static str,
+        old: String,
+        new: String,
+    },
+}
+
+impl std::fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigChange::ChangedScanRoot(roots) => write!(f, "~ scan_roots = [{}]", roots.join(", ")),
+            ConfigChange::AddedExcludePath(p) => write!(f, "+ extra_exclude_paths: {p}"),
+            ConfigChange::RemovedExcludePath(p) => write!(f, "- extra_exclude_paths: {p}"),
+            ConfigChange::DisabledExcludePath(p) => write!(f, "+ disable_exclude_paths: {p}"),
+            ConfigChange::EnabledExcludePath(p) => write!(f, "- disable_exclude_paths: {p}"),
+            ConfigChange::AddedPattern(name) => write!(f, "+ custom_patterns: {name}"),
+            ConfigChange::RemovedPattern(name) => write!(f, "- custom_patterns: {name}"),
+            ConfigChange::DisabledPattern(name) => write!(f, "+ disable_patterns: {name}"),
+            ConfigChange::EnabledPattern(name) => write!(f, "- disable_patterns: {name}"),
+            ConfigChange::ChangedSetting { key, old, new } => write!(f, "~ {key}: {old} -> {new}"),
         }
     }
 }
 
+/// Items in `new` but not `old`, and items in `old` but not `new`, in list order.
+fn list_diff<'a, T: PartialEq>(old: &'a [T], new: &'a [T]) -> (Vec<&'a T>, Vec<&'a T>) {
+    let added = new.iter().filter(|x| !old.contains(x)).collect();
+    let removed = old.iter().filter(|x| !new.contains(x)).collect();
+    (added, removed)
+}
+
 impl Config {
+    /// Compare two configs and describe what changed from `old` to `new`.
+    pub fn diff(old: &Config, new: &Config) -> Vec<ConfigChange> {
+        let mut changes = Vec::new();
+
+        if old.scan_roots != new.scan_roots {
+            changes.push(ConfigChange::ChangedScanRoot(new.scan_roots.clone()));
+        }
+
+        let (added, removed) = list_diff(&old.extra_exclude_paths, &new.extra_exclude_paths);
+        changes.extend(added.into_iter().cloned().map(ConfigChange::AddedExcludePath));
+        changes.extend(removed.into_iter().cloned().map(ConfigChange::RemovedExcludePath));
+
+        let (added, removed) = list_diff(&old.disable_exclude_paths, &new.disable_exclude_paths);
+        changes.extend(added.into_iter().cloned().map(ConfigChange::DisabledExcludePath));
+        changes.extend(removed.into_iter().cloned().map(ConfigChange::EnabledExcludePath));
+
+        let old_names: Vec<&String> = old.custom_patterns.iter().map(|p| &p.name).collect();
+        let new_names: Vec<&String> = new.custom_patterns.iter().map(|p| &p.name).collect();
+        let (added, removed) = list_diff(&old_names, &new_names);
+        changes.extend(added.into_iter().map(|n| ConfigChange::AddedPattern(n.to_string())));
+        changes.extend(removed.into_iter().map(|n| ConfigChange::RemovedPattern(n.to_string())));
+
+        let (added, removed) = list_diff(&old.disable_patterns, &new.disable_patterns);
+        changes.extend(added.into_iter().cloned().map(ConfigChange::DisabledPattern));
+        changes.extend(removed.into_iter().cloned().map(ConfigChange::EnabledPattern));
+
+        let mut setting = |key: &'static str, old: String, new: String| {
+            if old != new {
+                changes.push(ConfigChange::ChangedSetting { key, old, new });
+            }
+        };
+        setting(
+            "enable_patterns",
+            format!("[{}]", old.enable_patterns.join(", ")),
+            format!("[{}]", new.enable_patterns.join(", ")),
+        );
+        setting(
+            "disable_tags",
+            format!("[{}]", old.disable_tags.join(", ")),
+            format!("[{}]", new.disable_tags.join(", ")),
+        );
+        setting(
+            "include",
+            format!("[{}]", old.include.join(", ")),
+            format!("[{}]", new.include.join(", ")),
+        );
+        let opt = |v: Option<u64>| v.map_or("unset".to_string(), |n| n.to_string());
+        setting(
+            "exclusion_parallelism",
+            old.exclusion_parallelism.to_string(),
+            new.exclusion_parallelism.to_string(),
+        );
+        setting("older_than_days", opt(old.older_than_days), opt(new.older_than_days));
+        let size = |v: Option<ByteSize>| v.map_or("unset".to_string(), |s| crate::size::format_size(s.0));
+        setting("min_size", size(old.min_size), size(new.min_size));
+        let sizes = |m: &BTreeMap<String, ByteSize>| {
+            let items: Vec<String> = m
+                .iter()
+                .map(|(name, s)| format!("{} = {}", name, crate::size::format_size(s.0)))
+                .collect();
+            format!("{{{}}}", items.join(", "))
+        };
+        setting("pattern_min_size", sizes(&old.pattern_min_size), sizes(&new.pattern_min_size));
+        setting("scan_enabled", old.scan_enabled.to_string(), new.scan_enabled.to_string());
+        setting("follow_symlinks", old.follow_symlinks.to_string(), new.follow_symlinks.to_string());
+        setting("skip_hidden", old.skip_hidden.to_string(), new.skip_hidden.to_string());
+        setting("use_gitignore", old.use_gitignore.to_string(), new.use_gitignore.to_string());
+        setting("match_policy", old.match_policy.as_str().into(), new.match_policy.as_str().into());
+        setting("scan_time_budget", opt(old.scan_time_budget), opt(new.scan_time_budget));
+        setting("notify_on_run", old.notify_on_run.to_string(), new.notify_on_run.to_string());
+        setting("use_tmutil", old.use_tmutil.to_string(), new.use_tmutil.to_string());
+        setting(
+            "accumulate_state",
+            old.accumulate_state.to_string(),
+            new.accumulate_state.to_string(),
+        );
+        setting(
+            "metrics_path",
+            old.metrics_path.clone().unwrap_or_else(|| "unset".to_string()),
+            new.metrics_path.clone().unwrap_or_else(|| "unset".to_string()),
+        );
+
+        changes
+    }
+
+    /// Merge the named profile into this config.
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            let available: Vec<&str> = self.profiles.keys().map(|k| k.as_str()).collect();
+            if available.is_empty() {
+                anyhow::bail!("Unknown profile {:?}: no profiles are defined in the config", name);
+            }
+            anyhow::bail!(
+                "Unknown profile {:?} (available: {})",
+                name,
+                available.join(", ")
+            );
+        };
+
+        if !profile.scan_roots.is_empty() {
+            self.scan_roots = profile.scan_roots;
+        }
+        self.extra_exclude_paths.extend(profile.extra_exclude_paths);
+        self.disable_exclude_paths.extend(profile.disable_exclude_paths);
+        self.disable_patterns.extend(profile.disable_patterns);
+        self.custom_patterns.extend(profile.custom_patterns);
+        if let Some(n) = profile.exclusion_parallelism {
+            self.exclusion_parallelism = n;
+        }
+        if profile.older_than_days.is_some() {
+            self.older_than_days = profile.older_than_days;
+        }
+
+        Ok(())
+    }
+
     /// Resolve effective exclude paths: built-ins minus disabled, plus extras.
     pub fn resolved_exclude_paths(&self) -> Vec<String> {
         let mut paths: Vec<String> = builtin_exclude_paths()
@@ -120,6 +705,26 @@ impl Config {
         paths
     }
 
+    /// Entries of `extra_exclude_paths` that are globs matching `path` (e.g.
+    /// `~/Projects/*/node_modules`). `*` doesn't reach across directories.
+    pub fn exclude_globs_matching(&self, path: &Path) -> Vec<&str> {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+        self.extra_exclude_paths
+            .iter()
+            .filter(|entry| entry.contains(['*', '?', '[']))
+            .filter(|entry| {
+                expand_tilde(entry)
+                    .ok()
+                    .and_then(|p| glob::Pattern::new(&p.to_string_lossy()).ok())
+                    .is_some_and(|glob| glob.matches_path_with(path, options))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
     /// Resolve paths the scanner should skip: system paths + all resolved exclude paths.
     pub fn resolved_skip_paths(&self) -> Vec<String> {
         let mut paths: Vec<String> = SYSTEM_SKIP_PATHS
@@ -136,15 +741,230 @@ impl Config {
         paths
     }
 
+    /// Check settings that parse fine but can't work.
+    pub fn validate(&self) -> Result<()> {
+        match self.issues().into_iter().find(|i| i.severity == Severity::Error) {
+            Some(issue) => anyhow::bail!(issue.message),
+            None => Ok(()),
+        }
+    }
+
+    /// Append a custom pattern, refusing names a built-in or another custom
+    /// pattern already uses.
+    pub fn add_custom_pattern(&mut self, pattern: CustomPattern) -> Result<()> {
+        if self.custom_patterns.iter().any(|p| p.name == pattern.name) {
+            anyhow::bail!("A custom pattern named {:?} already exists", pattern.name);
+        }
+        if crate::patterns::builtin_patterns().iter().any(|p| p.selected_by(&pattern.name)) {
+            anyhow::bail!(
+                "{:?} is already a built-in pattern name; choose another",
+                pattern.name
+            );
+        }
+        self.custom_patterns.push(pattern);
+        Ok(())
+    }
+
+    /// Stop matching the named pattern: a custom pattern is deleted, a built-in
+    /// one (or group) is added to `disable_patterns`. Returns false if it was
+    /// already off.
+    pub fn remove_pattern(&mut self, name: &str) -> Result<bool> {
+        let before = self.custom_patterns.len();
+        self.custom_patterns.retain(|p| p.name != name);
+        if self.custom_patterns.len() < before {
+            return Ok(true);
+        }
+
+        let builtins: Vec<Pattern> = crate::patterns::builtin_patterns()
+            .into_iter()
+            .filter(|p| p.selected_by(name))
+            .collect();
+        if builtins.is_empty() {
+            anyhow::bail!("Unknown pattern {:?}", name);
+        }
+        let enabled_before = self.enable_patterns.len();
+        self.enable_patterns.retain(|n| n != name);
+        let mut changed = self.enable_patterns.len() < enabled_before;
+        // Off-by-default ones are already off once they aren't enabled
+        if builtins.iter().any(|p| p.enabled_by_default)
+            && !self.disable_patterns.iter().any(|n| n == name)
+        {
+            self.disable_patterns.push(name.to_string());
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    /// Problems with settings that parse fine: errors can't work, warnings
+    /// probably don't do what was meant. Only looks at the config itself, not
+    /// the filesystem, so results are the same on every machine.
+    pub fn issues(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        let quoted = |s: &str| toml::Value::String(s.to_string()).to_string();
+
+        let profile_patterns = self.profiles.values().flat_map(|p| &p.custom_patterns);
+        let root_patterns = self.root_patterns.values().flat_map(|r| &r.custom_patterns);
+        for p in self.custom_patterns.iter().chain(profile_patterns).chain(root_patterns) {
+            let message = match p.kind {
+                _ if p.name.is_empty() => "a custom pattern needs a name".to_string(),
+                _ if p.directory.trim_matches('/').is_empty() => {
+                    format!("custom pattern {:?} needs a directory", p.name)
+                }
+                _ if p.match_anywhere && !p.sentinel.is_empty() => {
+                    format!("custom pattern {:?} sets both a sentinel and match_anywhere", p.name)
+                }
+                PatternKind::Directory if p.sentinel.is_empty() && !p.match_anywhere => format!(
+                    "custom pattern {:?} needs a sentinel (or match_anywhere = true)",
+                    p.name
+                ),
+                _ if p.sentinel.is_empty() && !p.all_sentinels.is_empty() => {
+                    format!("custom pattern {:?} sets all_sentinels without a sentinel", p.name)
+                }
+                PatternKind::File if p.min_size.is_none() => {
+                    format!("custom pattern {:?} matches files, so it needs a min_size", p.name)
+                }
+                PatternKind::File if glob::Pattern::new(&p.directory).is_err() => format!(
+                    "custom pattern {:?} has an invalid file glob {:?}",
+                    p.name, p.directory
+                ),
+                _ => continue,
+            };
+            issues.push(ConfigIssue::error(message, quoted(&p.name)));
+        }
+
+        if !(1..=MAX_PARALLELISM).contains(&self.exclusion_parallelism) {
+            issues.push(ConfigIssue::error(
+                format!(
+                    "exclusion_parallelism must be between 1 and {}, got {}",
+                    MAX_PARALLELISM, self.exclusion_parallelism
+                ),
+                "exclusion_parallelism".to_string(),
+            ));
+        }
+
+        // Profile patterns count too, since they apply once the profile is active
+        let custom: Vec<CustomPattern> = self
+            .custom_patterns
+            .iter()
+            .chain(self.profiles.values().flat_map(|p| &p.custom_patterns))
+            .cloned()
+            .collect();
+        // Off-by-default built-ins can be named too, and so can patterns only
+        // used under one path
+        let patterns: Vec<Pattern> = crate::patterns::builtin_patterns()
+            .into_iter()
+            .chain(custom.iter().map(Pattern::from))
+            .chain(self.root_patterns.values().flat_map(|r| &r.custom_patterns).map(Pattern::from))
+            .collect();
+        for (name, size) in &self.pattern_min_size {
+            match patterns.iter().find(|p| &p.name == name) {
+                None => issues.push(ConfigIssue::error(
+                    format!("pattern_min_size names an unknown pattern {:?}", name),
+                    name.clone(),
+                )),
+                Some(p) if p.kind == PatternKind::File && size.0 == 0 => {
+                    issues.push(ConfigIssue::error(
+                        format!("pattern {:?} matches files, so its min_size can't be 0", name),
+                        name.clone(),
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+
+        if !self.scan_enabled && !self.custom_patterns.is_empty() {
+            issues.push(ConfigIssue::warning(
+                "custom_patterns have no effect while scan_enabled is false".to_string(),
+                "scan_enabled".to_string(),
+            ));
+        }
+
+        let unknown = |key: &str, name: &str| match name.strip_prefix(CATEGORY_PREFIX) {
+            Some(category) => format!("{} names an unknown category {:?}", key, category),
+            None => format!("{} names an unknown pattern {:?}", key, name),
+        };
+        let profile_disabled = self.profiles.values().flat_map(|p| &p.disable_patterns);
+        for name in self.disable_patterns.iter().chain(profile_disabled) {
+            if !patterns.iter().any(|p| p.selected_by(name)) {
+                issues.push(ConfigIssue::warning(unknown("disable_patterns", name), quoted(name)));
+            }
+        }
+
+        for name in &self.enable_patterns {
+            if !patterns.iter().any(|p| p.selected_by(name)) {
+                issues.push(ConfigIssue::warning(unknown("enable_patterns", name), quoted(name)));
+            }
+        }
+
+        for (root, scoped) in &self.root_patterns {
+            let key = format!("root_patterns.{}.disable_patterns", quoted(root));
+            for name in &scoped.disable_patterns {
+                if !patterns.iter().any(|p| p.selected_by(name)) {
+                    issues.push(ConfigIssue::warning(unknown(&key, name), quoted(name)));
+                }
+            }
+        }
+
+        for tag in &self.disable_tags {
+            if !patterns.iter().any(|p| p.has_tag(tag)) {
+                issues.push(ConfigIssue::warning(
+                    format!("disable_tags names a tag no pattern carries {:?}", tag),
+                    quoted(tag),
+                ));
+            }
+        }
+
+        let mut names = HashSet::new();
+        for p in &custom {
+            if !names.insert(&p.name) {
+                issues.push(ConfigIssue::warning(
+                    format!("custom pattern {:?} is defined more than once", p.name),
+                    quoted(&p.name),
+                ));
+            }
+        }
+
+        let builtin = builtin_exclude_paths();
+        let profile_enabled = self.profiles.values().flat_map(|p| &p.disable_exclude_paths);
+        for path in self.disable_exclude_paths.iter().chain(profile_enabled) {
+            if !builtin.contains(&path.as_str()) {
+                issues.push(ConfigIssue::warning(
+                    format!("disable_exclude_paths entry {:?} is not a built-in exclude path", path),
+                    quoted(path),
+                ));
+            }
+        }
+        for path in &self.extra_exclude_paths {
+            if builtin.contains(&path.as_str()) && !self.disable_exclude_paths.contains(path) {
+                issues.push(ConfigIssue::warning(
+                    format!("extra_exclude_paths entry {:?} is already a built-in exclude path", path),
+                    quoted(path),
+                ));
+            }
+        }
+
+        issues
+    }
+
     pub fn default_toml() -> &'static str {
-        r#"# Directories to scan for dependency patterns (default: home dir)
+        r#"# Layer more config files on top of this one, e.g. a machine-local overlay.
+# Their lists are appended and their scan_roots, if set, replace these.
+# include = ["~/.config/tmignore/local.toml"]
+
+# Directories to scan for dependency patterns (default: home dir). Relative
+# paths are resolved from this file's directory, e.g. "." for it alone.
 scan_roots = ["~"]
 
+# Set to false to skip the scan and only apply exclude paths, making runs
+# nearly instant. Patterns, including custom_patterns, are then unused.
+# scan_enabled = true
+
 # tmignore excludes these paths from backups by default:
 # version managers (~/.rbenv, ~/.pyenv, ~/.nvm, ~/.asdf, ~/.local/share/mise),
 # language toolchain caches (~/.cargo, ~/.rustup, ~/.gradle, ~/.m2, ~/.npm, etc.),
-# Homebrew (/opt/homebrew), Nix/Devbox (/nix), Docker, and
-# Xcode (DerivedData, DeviceSupport, CoreSimulator).
+# Homebrew (/opt/homebrew), Nix/Devbox (/nix), Docker,
+# Xcode (DerivedData, DeviceSupport, CoreSimulator) and Android
+# (~/Library/Android/sdk, ~/.android/avd, ~/.android/cache).
 #
 # Run `tmignore run --verbose` to see the full list.
 
@@ -156,10 +976,6 @@ extra_exclude_paths = [
     # "~/Virtual Machines.localized",
     # "~/.vagrant.d/boxes",
 
-    # Android
-    # "~/Library/Android/sdk",
-    # "~/.android/avd",
-
     # Large media (user preference)
     # "~/Movies",
     # "~/Downloads",
@@ -168,16 +984,116 @@ extra_exclude_paths = [
 # Stop excluding a built-in path (it will be backed up normally).
 # disable_exclude_paths = ["~/.cargo"]
 
+# Number of paths to check and exclude in parallel (1-64).
+# Override per run with `tmignore run --concurrency=N`.
+# exclusion_parallelism = 1
+
+# Only exclude dependency directories untouched for this many days, so active
+# projects keep their build artifacts in backups. Off by default.
+# older_than_days = 30
+
+# Only exclude dependency directories at least this large (default: off).
+# Override per run with `tmignore run --min-size`. pattern_min_size sets the
+# threshold for individual built-in or custom patterns by name, e.g. to always
+# exclude node_modules but only Rust target directories over 500MB.
+# min_size = "100MB"
+# pattern_min_size = { cargo = "500MB", node = "0" }
+
+# Follow symlinks while scanning (e.g. projects linked in from an external drive).
+# Matches are excluded at their physical location; cycles are handled.
+# follow_symlinks = false
+
+# Skip hidden directories (.git, app caches, ...) while scanning, which can make
+# scans much faster. Hidden directories used by a pattern (.venv, .gradle, ...)
+# are still visited.
+# skip_hidden = false
+
+# In the top directory of a git repository, also exclude the directories its
+# .gitignore lists (build/, out/, ...) that no pattern matches. Only simple
+# entries count (a later !entry keeps a directory), so this can be aggressive.
+# use_gitignore = false
+
+# Check every pattern that could claim a directory and record all that match
+# ("all"), or stop at the most specific one ("first"), which is a little faster.
+# match_policy = "all"
+
+# Stop scanning after this many seconds and exclude what was found so far
+# (useful on battery). Override per run with `tmignore run --max-duration`.
+# scan_time_budget = 60
+
+# Show a macOS notification when a run finishes (handy for the LaunchAgent).
+# notify_on_run = false
+
+# Exclusions are read and written straight from each directory's extended
+# attribute, the same one `tmutil addexclusion` sets. Set this to run tmutil
+# for each path instead (slower). `--use-tmutil` does the same for one command.
+# use_tmutil = false
+
+# Keep what earlier runs excluded in the state file, so `tmignore list` shows
+# everything tmignore has ever excluded instead of only the last run's finds.
+# accumulate_state = false
+
+# Write run metrics after each run for monitoring, e.g. for node_exporter's
+# textfile collector. Prometheus text format, or JSON if the path ends in .json.
+# metrics_path = "~/.local/state/tmignore/metrics.prom"
+
 # tmignore scans for dependency directories (node_modules, target, vendor, etc.)
-# by matching a directory name + a sentinel file in its parent (e.g. package.json).
-# 40 patterns are built-in. You can disable any by name or add your own.
+# by matching a directory name + a sentinel file in its parent (e.g. package.json),
+# and for large files such as VM disk images. 88 patterns are built-in. You can disable any by name or add your own.
+# `tmignore patterns list` shows them by category; "category:<name>" disables a
+# whole category.
 #
 # disable_patterns = ["bundler"]
 #
+# A few patterns match names other tools use too, so they are off by default.
+# Turn them on by name or group, e.g. .cache and coverage in JS projects:
+# enable_patterns = ["js-extras"]
+#
+# Built-in patterns are tagged by language or ecosystem (javascript, python,
+# jvm, apple, ...); `tmignore patterns info <name>` shows a pattern's tags.
+# Turn off every pattern with a tag at once:
+# disable_tags = ["dotnet"]
+#
 # [[custom_patterns]]
 # name = "my-build"
-# directory = "dist"
+# directory = "dist"             # the last part may be a glob, e.g. "dist-*"
 # sentinel = "turbo.json"
+# sentinel_location = "parent"   # or "inside" to look within the directory itself
+# sentinel_type = "any"          # or "file"/"dir" to require that kind of entry
+# all_sentinels = ["src/"]       # also require these next to it (trailing / = directory)
+# sentinel_depth = 3             # also look for the sentinel up to 2 levels higher
+# match_anywhere = true          # no sentinel: match the directory name anywhere
+# min_size = "200MB"             # only exclude matches at least this large
+# older_than_days = 14           # only exclude matches untouched this long
+# descend = true                 # keep scanning inside matches for nested projects
+# expected_size_hint = "10MB–1GB" # typical match size, shown by `patterns info`
+# tags = ["javascript"]          # labels for disable_tags and run --include-tag
+# description = "Generated code" # shown by `patterns list`
+#
+# [[custom_patterns]]
+# name = "model-checkpoints"
+# kind = "file"                  # match files instead of directories
+# directory = "*.safetensors"    # file name glob
+# min_size = "1GB"               # required for file patterns; sentinel is optional
+
+# Profiles layer extra settings on top of this file for a single run:
+# `tmignore run --profile=work`. List settings are appended, except
+# scan_roots, which replaces the base value when set.
+#
+# [profiles.work]
+# scan_roots = ["~/Work"]
+# disable_patterns = ["node"]
+
+# Use different patterns under one path, e.g. a drive from another machine.
+# They change the patterns used everywhere else for matches below that path.
+#
+# [root_patterns."/Volumes/Archive"]
+# disable_patterns = ["category:apple"]
+#
+# [[root_patterns."/Volumes/Archive".custom_patterns]]
+# name = "archive-build"
+# directory = "out"
+# sentinel = "build.ninja"
 "#
     }
 }
@@ -187,60 +1103,718 @@ pub fn config_dir() -> PathBuf {
     PathBuf::from(home).join(".config/tmignore")
 }
 
+/// Config file names in order of precedence. The first that exists is used;
+/// `config.toml` is created when there is none.
+const CONFIG_FILE_NAMES: [&str; 3] = ["config.toml", "config.yaml", "config.yml"];
+
 pub fn config_path() -> PathBuf {
-    config_dir().join("config.toml")
+    let dir = config_dir();
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(CONFIG_FILE_NAMES[0]))
 }
 
-pub fn expand_tilde(path: &str) -> PathBuf {
-    if let Some(rest) = path.strip_prefix("~/") {
-        let home = std::env::var("HOME").expect("HOME not set");
-        PathBuf::from(home).join(rest)
-    } else if path == "~" {
-        let home = std::env::var("HOME").expect("HOME not set");
-        PathBuf::from(home)
-    } else {
-        PathBuf::from(path)
-    }
+/// Config files that exist but are ignored because one earlier in
+/// `CONFIG_FILE_NAMES` takes precedence.
+fn shadowed_config_files() -> Vec<PathBuf> {
+    let dir = config_dir();
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.exists())
+        .skip(1)
+        .collect()
 }
 
-pub fn contract_tilde(path: &str) -> String {
-    if let Ok(home) = std::env::var("HOME") {
-        if let Some(rest) = path.strip_prefix(&home) {
-            if rest.is_empty() {
-                return "~".to_string();
-            }
-            if rest.starts_with('/') {
-                return format!("~{rest}");
-            }
+/// The syntax of a config file, from its extension: YAML for `.yaml` and `.yml`,
+/// TOML otherwise. Both describe the same settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
         }
     }
-    path.to_string()
-}
 
-pub fn load_config() -> Result<Config> {
-    let path = config_path();
+    fn parse<T: serde::de::DeserializeOwned>(self, contents: &str) -> Result<T> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+        })
+    }
 
-    if !path.exists() {
-        return Ok(Config::default());
+    pub fn serialize(self, config: &Config) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(config)?,
+        })
     }
 
-    let contents =
-        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    /// Why `contents` isn't a valid config, with the line if known.
+    pub fn syntax_error(self, contents: &str) -> Option<(Option<usize>, String)> {
+        match self {
+            ConfigFormat::Toml => toml::from_str::<Config>(contents).err().map(|e| {
+                let line = e.span().map(|span| line_at(contents, span.start));
+                (line, e.message().trim().replace('\n', ": "))
+            }),
+            ConfigFormat::Yaml => serde_yaml::from_str::<Config>(contents)
+                .err()
+                .map(|e| (e.location().map(|l| l.line()), e.to_string())),
+        }
+    }
+}
 
-    let config: Config =
-        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+/// Expand a leading `~` (current user) or `~name` (another user's home directory).
+/// Fails if `~name` names an unknown user.
+pub fn expand_tilde(path: &str) -> Result<PathBuf> {
+    expand_tilde_with(path, user_home)
+}
+
+/// Expand `~` like `expand_tilde`, then resolve a relative result against
+/// `base_dir`, the directory of the config file that named it. `.` and `..` are
+/// resolved without touching the filesystem.
+pub fn expand_path(path: &str, base_dir: &Path) -> Result<PathBuf> {
+    let expanded = expand_tilde(path)?;
+    if expanded.is_absolute() {
+        return Ok(expanded);
+    }
+    let mut resolved = base_dir.to_path_buf();
+    for component in expanded.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Make relative `scan_roots` absolute, relative to the directory of the config
+/// file that set them. `~` and absolute roots are kept as written.
+fn resolve_scan_roots(roots: &mut [String], config_file: &Path) {
+    let base_dir = config_file.parent().unwrap_or(Path::new("/"));
+    for root in roots.iter_mut().filter(|r| !r.starts_with('~')) {
+        if let Ok(resolved) = expand_path(root, base_dir) {
+            *root = resolved.to_string_lossy().into_owned();
+        }
+    }
+}
+
+/// Look up a user's home directory in the system user database.
+fn user_home(name: &str) -> Option<PathBuf> {
+    nix::unistd::User::from_name(name).ok().flatten().map(|u| u.dir)
+}
+
+fn expand_tilde_with(path: &str, lookup: impl Fn(&str) -> Option<PathBuf>) -> Result<PathBuf> {
+    let Some(after_tilde) = path.strip_prefix('~') else {
+        return Ok(PathBuf::from(path));
+    };
+    let (user, rest) = after_tilde.split_once('/').unwrap_or((after_tilde, ""));
+
+    let home = if user.is_empty() {
+        PathBuf::from(std::env::var("HOME").expect("HOME not set"))
+    } else {
+        lookup(user).with_context(|| format!("Unknown user in path {}: {}", path, user))?
+    };
+
+    Ok(if rest.is_empty() { home } else { home.join(rest) })
+}
+
+pub fn contract_tilde(path: &str) -> String {
+    if let Ok(home) = std::env::var("HOME")
+        && let Some(rest) = path.strip_prefix(&home)
+    {
+        if rest.is_empty() {
+            return "~".to_string();
+        }
+        if rest.starts_with('/') {
+            return format!("~{rest}");
+        }
+    }
+    path.to_string()
+}
+
+/// Parse a newline-separated path list, skipping blank lines and `#` comment lines.
+pub fn parse_path_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse an age like "30d", "2w" or "30" (days) into a number of days.
+pub fn parse_age_days(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let value: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {:?}", input))?;
+
+    let days = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "d" => 1,
+        "w" => 7,
+        _ => anyhow::bail!("Invalid duration unit in {:?} (use d or w)", input),
+    };
+
+    Ok(value * days)
+}
+
+/// Check an ISO 8601 date or date and time, e.g. "2024-06-01" or
+/// "2024-06-01T09:30:00Z", as Spotlight's `$time.iso()` accepts them.
+pub fn parse_timestamp(input: &str) -> Result<String> {
+    let trimmed = input.trim();
+    let number = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_digit());
+    let (date, time) = match trimmed.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (trimmed, None),
+    };
+    let date: Vec<&str> = date.split('-').collect();
+    let date_ok = date.len() == 3 && number(date[0], 4) && number(date[1], 2) && number(date[2], 2);
+    let time_ok = time.is_none_or(|time| {
+        // Drop the UTC offset: Z, +HH:MM or -HH:MM
+        let time = time
+            .strip_suffix('Z')
+            .or_else(|| time.find(['+', '-']).map(|at| &time[..at]))
+            .unwrap_or(time);
+        let parts: Vec<&str> = time.split(':').collect();
+        (2..=3).contains(&parts.len()) && parts.iter().all(|p| number(p, 2))
+    });
+    if !date_ok || !time_ok {
+        anyhow::bail!(
+            "Invalid timestamp {:?} (use ISO 8601, e.g. 2024-06-01 or 2024-06-01T09:30:00Z)",
+            input
+        );
+    }
+    Ok(trimmed.to_string())
+}
+
+/// Load the config file with its includes merged in. This is the config to run
+/// with; edit and save `load_base_config` instead so included settings aren't
+/// copied into the main file.
+pub fn load_config() -> Result<Config> {
+    let path = config_path();
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    for ignored in shadowed_config_files() {
+        warn!(
+            "ignoring {}; {} takes precedence",
+            contract_tilde(&ignored.to_string_lossy()),
+            contract_tilde(&path.to_string_lossy())
+        );
+    }
+
+    load_config_from(&path)
+}
+
+/// Load just the main config file, without its includes.
+pub fn load_base_config() -> Result<Config> {
+    let path = config_path();
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    read_config(&path)
+}
+
+fn parse_config(path: &Path) -> Result<Config> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    ConfigFormat::of(path)
+        .parse(&contents)
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    let config = parse_config(path)?;
+    config
+        .validate()
+        .with_context(|| format!("Invalid config {}", path.display()))?;
 
     Ok(config)
 }
 
+fn load_config_from(path: &Path) -> Result<Config> {
+    let mut config = read_config(path)?;
+    resolve_scan_roots(&mut config.scan_roots, path);
+    for profile in config.profiles.values_mut() {
+        resolve_scan_roots(&mut profile.scan_roots, path);
+    }
+    let includes = config.include.clone();
+    let mut chain = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    merge_includes(&mut config, path, &includes, &mut chain, &mut Vec::new())?;
+    config
+        .validate()
+        .with_context(|| format!("Invalid config {} (with includes)", path.display()))?;
+    Ok(config)
+}
+
+/// Load a config file with its includes merged in, without validating it, for
+/// `config validate` to report every issue. Also returns the included files that
+/// were read, in order.
+pub fn load_unvalidated(path: &Path) -> Result<(Config, Vec<PathBuf>)> {
+    let mut config = parse_config(path)?;
+    resolve_scan_roots(&mut config.scan_roots, path);
+    for profile in config.profiles.values_mut() {
+        resolve_scan_roots(&mut profile.scan_roots, path);
+    }
+    let includes = config.include.clone();
+    let mut chain = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    let mut files = Vec::new();
+    merge_includes(&mut config, path, &includes, &mut chain, &mut files)?;
+    Ok((config, files))
+}
+
+/// Merge each of `includes` (listed in the file `from`) into `config`, depth first.
+/// `chain` holds the files currently being included, to detect cycles, and
+/// `files` collects every file read. Relative
+/// paths are relative to the including file; missing files are skipped, so a
+/// shared config can include an overlay that only some machines have.
+fn merge_includes(
+    config: &mut Config,
+    from: &Path,
+    includes: &[String],
+    chain: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for include in includes {
+        let path = expand_tilde(include)?;
+        let path = match from.parent() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path,
+        };
+        if !path.exists() {
+            continue;
+        }
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
+
+        if chain.contains(&path) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|p| contract_tilde(&p.to_string_lossy()))
+                .collect();
+            anyhow::bail!("Config include cycle: {}", cycle.join(" -> "));
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let included: IncludedConfig = ConfigFormat::of(&path)
+            .parse(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        if !included.scan_roots.is_empty() {
+            config.scan_roots = included.scan_roots;
+            resolve_scan_roots(&mut config.scan_roots, &path);
+        }
+        config.extra_exclude_paths.extend(included.extra_exclude_paths);
+        config.disable_exclude_paths.extend(included.disable_exclude_paths);
+        config.disable_patterns.extend(included.disable_patterns);
+        config.custom_patterns.extend(included.custom_patterns);
+
+        files.push(path.clone());
+        chain.push(path.clone());
+        merge_includes(config, &path, &included.include, chain, files)?;
+        chain.pop();
+    }
+    Ok(())
+}
+
+/// The default config template with `extra_exclude_paths` filled in, keeping all
+/// of the template's comments.
+fn default_toml_with(extra_exclude_paths: &[String]) -> String {
+    let template = Config::default_toml();
+    let start = template
+        .find("\nextra_exclude_paths = [\n")
+        .expect("template has extra_exclude_paths");
+    let end = start
+        + template[start..]
+            .find("\n]\n")
+            .expect("template closes extra_exclude_paths")
+        + 1;
+    let entries: String = extra_exclude_paths
+        .iter()
+        .map(|p| format!("    {},\n", toml::Value::String(p.clone())))
+        .collect();
+    format!("{}{}{}", &template[..end], entries, &template[end..])
+}
+
+/// Write the documented default config, with `extra_exclude_paths` set. Used by
+/// `init` and when `add` or `remove` runs before there is a config file, so the
+/// first edit doesn't produce a bare file without the template's comments.
+pub fn write_default_config(extra_exclude_paths: &[String]) -> Result<PathBuf> {
+    write_config_file(&default_toml_with(extra_exclude_paths))
+}
+
+/// Write the default config template with a preset's settings filled in.
+pub fn write_preset_config(preset: &Preset) -> Result<PathBuf> {
+    write_config_file(&preset_toml(&preset.config()))
+}
+
+fn write_config_file(contents: &str) -> Result<PathBuf> {
+    let path = config_path();
+    // The templates are commented TOML; a YAML config gets the settings alone
+    let contents = match ConfigFormat::of(&path) {
+        ConfigFormat::Toml => contents.to_string(),
+        ConfigFormat::Yaml => ConfigFormat::Yaml.serialize(&toml::from_str(contents)?)?,
+    };
+    std::fs::create_dir_all(config_dir()).context("Failed to create config directory")?;
+    std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// A named starting config for `init --preset`.
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    build: fn() -> Config,
+}
+
+impl Preset {
+    pub fn config(&self) -> Config {
+        (self.build)()
+    }
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "minimal",
+        description: "Dependency directories only; no large-file scan",
+        build: preset_minimal,
+    },
+    Preset {
+        name: "full",
+        description: "Everything built-in, plus virtual machine paths",
+        build: preset_full,
+    },
+    Preset {
+        name: "ios-dev",
+        description: "Extra Xcode and simulator caches",
+        build: preset_ios_dev,
+    },
+    Preset {
+        name: "data-science",
+        description: "Conda package caches and large model checkpoints",
+        build: preset_data_science,
+    },
+    Preset {
+        name: "backend",
+        description: "JVM build caches, Kubernetes and Vagrant images",
+        build: preset_backend,
+    },
+];
+
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+fn preset_minimal() -> Config {
+    Config {
+        disable_patterns: strings(&["vm-qcow2", "vm-vmdk", "vm-vdi"]),
+        ..Config::default()
+    }
+}
+
+fn preset_full() -> Config {
+    Config {
+        extra_exclude_paths: strings(&[
+            "~/Parallels",
+            "~/Virtual Machines.localized",
+            "~/.vagrant.d/boxes",
+        ]),
+        ..Config::default()
+    }
+}
+
+fn preset_ios_dev() -> Config {
+    Config {
+        extra_exclude_paths: strings(&[
+            "~/Library/Caches/com.apple.dt.Xcode",
+            "~/Library/Developer/Xcode/UserData/Previews",
+            "~/Library/Developer/CoreSimulator/Caches",
+        ]),
+        ..Config::default()
+    }
+}
+
+fn preset_data_science() -> Config {
+    let checkpoint = |name: &str, glob: &str| CustomPattern {
+        name: name.to_string(),
+        directory: glob.to_string(),
+        sentinel: String::new(),
+        match_anywhere: false,
+        sentinel_type: SentinelType::Any,
+        all_sentinels: Vec::new(),
+        kind: PatternKind::File,
+        sentinel_location: SentinelLocation::Parent,
+        sentinel_depth: None,
+        min_size: Some(ByteSize(1 << 30)),
+        older_than_days: None,
+        descend: false,
+        expected_size_hint: None,
+        tags: Vec::new(),
+        description: Some("Model checkpoints over 1GB".to_string()),
+    };
+    Config {
+        extra_exclude_paths: strings(&[
+            "~/.conda/pkgs",
+            "~/anaconda3/pkgs",
+            "~/miniconda3/pkgs",
+        ]),
+        custom_patterns: vec![
+            checkpoint("model-safetensors", "*.safetensors"),
+            checkpoint("model-ckpt", "*.ckpt"),
+            checkpoint("model-gguf", "*.gguf"),
+        ],
+        ..Config::default()
+    }
+}
+
+fn preset_backend() -> Config {
+    Config {
+        extra_exclude_paths: strings(&[
+            "~/.ivy2/cache",
+            "~/.sbt/boot",
+            "~/.sdkman/candidates",
+            "~/.minikube/cache",
+            "~/.vagrant.d/boxes",
+        ]),
+        ..Config::default()
+    }
+}
+
+/// The default config template with a preset's exclude paths, disabled patterns
+/// and custom patterns filled in.
+fn preset_toml(preset: &Config) -> String {
+    let mut contents = default_toml_with(&preset.extra_exclude_paths);
+    if !preset.disable_patterns.is_empty() {
+        let example = "# disable_patterns = [\"bundler\"]\n";
+        let at = contents.find(example).expect("template documents disable_patterns") + example.len();
+        let names = toml::Value::Array(
+            preset.disable_patterns.iter().cloned().map(toml::Value::String).collect(),
+        );
+        contents.insert_str(at, &format!("disable_patterns = {}\n", names));
+    }
+    if !preset.custom_patterns.is_empty() {
+        #[derive(Serialize)]
+        struct Patterns<'a> {
+            custom_patterns: &'a [CustomPattern],
+        }
+        let tables = toml::to_string_pretty(&Patterns {
+            custom_patterns: &preset.custom_patterns,
+        })
+        .expect("custom patterns serialize");
+        contents.push('\n');
+        contents.push_str(&tables);
+    }
+    contents
+}
+
+/// Write `config` to the config file. An existing TOML file is updated in place
+/// (see `merge_toml`), so its comments survive `add`, `remove` and friends.
 pub fn save_config(config: &Config) -> Result<()> {
     let path = config_path();
     std::fs::create_dir_all(config_dir()).context("Failed to create config directory")?;
-    let contents = toml::to_string_pretty(config).context("Failed to serialize config")?;
+    let format = ConfigFormat::of(&path);
+    let contents = match (format, std::fs::read_to_string(&path)) {
+        (ConfigFormat::Toml, Ok(original)) => merge_toml(&original, config),
+        _ => format.serialize(config),
+    }
+    .context("Failed to serialize config")?;
     std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
     Ok(())
 }
 
+/// `config` as TOML written over `original`, keeping its comments and layout:
+/// settings that didn't change are left as they were written, list entries are
+/// added and removed in place, and settings left at their defaults aren't
+/// spelled out. Falls back to a plain serialization if the result wouldn't
+/// read back as `config`.
+fn merge_toml(original: &str, config: &Config) -> Result<String> {
+    let plain = toml::to_string_pretty(config)?;
+    let Ok(mut doc) = original.parse::<toml_edit::DocumentMut>() else {
+        return Ok(plain);
+    };
+    let updated: toml_edit::DocumentMut = plain.parse()?;
+    let defaults: toml_edit::DocumentMut = toml::to_string_pretty(&Config::default())?.parse()?;
+    merge_table(doc.as_table_mut(), updated.as_table(), Some(defaults.as_table()));
+
+    let merged = doc.to_string();
+    let round_trip = toml::from_str::<Config>(&merged).ok().map(|c| toml::to_string_pretty(&c));
+    Ok(match round_trip {
+        Some(Ok(text)) if text == plain => merged,
+        _ => plain,
+    })
+}
+
+/// Make `old` hold what `new` does. Keys missing from `old` are only added when
+/// they differ from `defaults`.
+fn merge_table(
+    old: &mut toml_edit::Table,
+    new: &toml_edit::Table,
+    defaults: Option<&toml_edit::Table>,
+) {
+    let stale: Vec<String> = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in stale {
+        old.remove(&key);
+    }
+    for (key, item) in new.iter() {
+        match old.get_mut(key) {
+            Some(existing) => merge_item(existing, item),
+            None if defaults.and_then(|d| d.get(key)).is_some_and(|d| same_item(d, item)) => {}
+            None => {
+                old.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+fn merge_item(old: &mut toml_edit::Item, new: &toml_edit::Item) {
+    use toml_edit::{Item, Value};
+    if same_item(old, new) {
+        return;
+    }
+    match (old, new) {
+        (Item::Value(Value::Array(old)), Item::Value(Value::Array(new))) => merge_array(old, new),
+        (Item::Table(old), Item::Table(new)) => merge_table(old, new, None),
+        (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) => {
+            // Tables that are still there keep their comments
+            let mut merged = toml_edit::ArrayOfTables::new();
+            for table in new.iter() {
+                let kept = old.iter().find(|t| same_table(t, table)).unwrap_or(table);
+                merged.push(kept.clone());
+            }
+            *old = merged;
+        }
+        (Item::Value(old), Item::Value(new)) => {
+            let decor = old.decor().clone();
+            *old = new.clone();
+            *old.decor_mut() = decor;
+        }
+        (old, new) => *old = new.clone(),
+    }
+}
+
+/// Remove the entries of `old` that `new` lacks and append the ones it adds,
+/// formatted like their neighbors. Comments above a removed entry move to the
+/// entry after it.
+fn merge_array(old: &mut toml_edit::Array, new: &toml_edit::Array) {
+    let prefix = |v: &toml_edit::Value| {
+        v.decor().prefix().and_then(|p| p.as_str()).unwrap_or("").to_string()
+    };
+    let mut i = 0;
+    while i < old.len() {
+        if old.get(i).is_some_and(|o| new.iter().any(|n| same_value(n, o))) {
+            i += 1;
+            continue;
+        }
+        let removed = prefix(&old.remove(i));
+        if removed.contains('#') {
+            let comments = removed.trim_end_matches(' ');
+            match old.get_mut(i) {
+                Some(next) => {
+                    let own = prefix(next);
+                    let own = own.trim_start_matches('\n');
+                    next.decor_mut().set_prefix(format!("{comments}{own}"));
+                }
+                None => {
+                    let trailing = old.trailing().as_str().unwrap_or("").to_string();
+                    let trailing = trailing.trim_start_matches('\n');
+                    old.set_trailing(format!("{comments}{trailing}"));
+                }
+            }
+        }
+    }
+
+    for value in new.iter() {
+        if old.iter().any(|o| same_value(o, value)) {
+            continue;
+        }
+        let trailing = old.trailing().as_str().unwrap_or("").to_string();
+        let lead = match old.iter().last().map(prefix) {
+            // Indent like the entry before
+            Some(last) => match last.rfind('\n') {
+                Some(at) => format!("\n{}", &last[at + 1..]),
+                None => " ".to_string(),
+            },
+            // A multi-line empty list: its comments go above the first entry
+            None if trailing.contains('\n') => {
+                old.set_trailing("\n");
+                old.set_trailing_comma(true);
+                format!("{}    ", trailing.trim_end_matches(' '))
+            }
+            None => String::new(),
+        };
+        let mut value = value.clone();
+        value.decor_mut().set_prefix(lead);
+        value.decor_mut().set_suffix("");
+        old.push_formatted(value);
+    }
+
+    // Reordered or repeated entries can't be patched like this
+    let plain = |a: &toml_edit::Array| a.iter().map(plain_value).collect::<Vec<_>>();
+    if plain(old) != plain(new) {
+        let decor = old.decor().clone();
+        *old = new.clone();
+        *old.decor_mut() = decor;
+    }
+}
+
+/// An item as a plain TOML value, for comparing what it says rather than how.
+fn plain_item(item: &toml_edit::Item) -> Option<toml::Value> {
+    let mut doc = toml_edit::DocumentMut::new();
+    doc.insert("item", item.clone());
+    doc.to_string().parse::<toml::Table>().ok()?.remove("item")
+}
+
+fn plain_value(value: &toml_edit::Value) -> Option<toml::Value> {
+    // An array entry's decor may hold comments that only make sense inside the array
+    let mut value = value.clone();
+    value.decor_mut().clear();
+    plain_item(&toml_edit::Item::Value(value))
+}
+
+fn same_item(a: &toml_edit::Item, b: &toml_edit::Item) -> bool {
+    plain_item(a) == plain_item(b)
+}
+
+fn same_value(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    plain_value(a) == plain_value(b)
+}
+
+fn same_table(a: &toml_edit::Table, b: &toml_edit::Table) -> bool {
+    same_item(&toml_edit::Item::Table(a.clone()), &toml_edit::Item::Table(b.clone()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,6 +1827,11 @@ mod tests {
         assert!(config.disable_exclude_paths.is_empty());
         assert!(config.disable_patterns.is_empty());
         assert!(config.custom_patterns.is_empty());
+        assert_eq!(config.exclusion_parallelism, 1);
+        assert_eq!(config.older_than_days, None);
+        assert!(!config.notify_on_run);
+        assert!(!config.follow_symlinks);
+        assert!(!config.skip_hidden);
     }
 
     #[test]
@@ -285,6 +1864,29 @@ mod tests {
         assert!(resolved.contains(&"~/.rbenv".to_string()));
     }
 
+    #[test]
+    fn test_exclude_globs_matching() {
+        let config = Config {
+            extra_exclude_paths: vec![
+                "~/Projects/*/node_modules".to_string(),
+                "~/Projects/foo/node_modules".to_string(),
+                "/Volumes/Data/[ab]*".to_string(),
+            ],
+            ..Config::default()
+        };
+        let home = expand_tilde("~").unwrap();
+        let foo = home.join("Projects/foo/node_modules");
+        assert_eq!(config.exclude_globs_matching(&foo), vec!["~/Projects/*/node_modules"]);
+        // `*` stays within one directory
+        let nested = home.join("Projects/foo/web/node_modules");
+        assert!(config.exclude_globs_matching(&nested).is_empty());
+        assert_eq!(
+            config.exclude_globs_matching(Path::new("/Volumes/Data/builds")),
+            vec!["/Volumes/Data/[ab]*"]
+        );
+        assert!(config.exclude_globs_matching(Path::new("/Volumes/Data/cache")).is_empty());
+    }
+
     #[test]
     fn test_resolved_skip_paths_includes_system_and_excludes() {
         let config = Config::default();
@@ -322,29 +1924,527 @@ disable_exclude_paths = ["~/.cargo"]
 name = "my-build"
 directory = "dist"
 sentinel = "turbo.json"
+
+[[custom_patterns]]
+name = "cmake"
+directory = "build"
+sentinel = "CMakeCache.txt"
+sentinel_location = "inside"
+sentinel_type = "file"
+min_size = "10MB"
+older_than_days = 14
 "#;
         let config: Config = toml::from_str(toml_str).unwrap();
         assert_eq!(config.scan_roots, vec!["~", "/Volumes/Code"]);
         assert_eq!(config.extra_exclude_paths, vec!["~/Movies"]);
         assert_eq!(config.disable_patterns, vec!["node"]);
         assert_eq!(config.disable_exclude_paths, vec!["~/.cargo"]);
-        assert_eq!(config.custom_patterns.len(), 1);
+        assert_eq!(config.custom_patterns.len(), 2);
         assert_eq!(config.custom_patterns[0].name, "my-build");
+        assert_eq!(config.custom_patterns[0].sentinel_location, SentinelLocation::Parent);
+        assert_eq!(config.custom_patterns[1].sentinel_location, SentinelLocation::Inside);
+        assert_eq!(config.custom_patterns[0].sentinel_type, SentinelType::Any);
+        assert_eq!(config.custom_patterns[1].sentinel_type, SentinelType::File);
+        assert_eq!(config.custom_patterns[0].min_size, None);
+        assert_eq!(config.custom_patterns[1].min_size, Some(ByteSize(10 * 1024 * 1024)));
+        assert_eq!(config.custom_patterns[1].older_than_days, Some(14));
+    }
+
+    #[test]
+    fn test_validate_file_patterns() {
+        let parse = |extra: &str| {
+            let toml_str = format!(
+                "[[custom_patterns]]\nname = \"images\"\nkind = \"file\"\ndirectory = \"*.img\"\n{}",
+                extra
+            );
+            toml::from_str::<Config>(&toml_str).unwrap()
+        };
+
+        let config = parse("min_size = \"1GB\"");
+        assert_eq!(config.custom_patterns[0].kind, PatternKind::File);
+        assert!(config.custom_patterns[0].sentinel.is_empty());
+        assert!(config.validate().is_ok());
+
+        let err = parse("").validate().unwrap_err();
+        assert!(err.to_string().contains("min_size"), "{err}");
+
+        let no_sentinel: Config =
+            toml::from_str("[[custom_patterns]]\nname = \"x\"\ndirectory = \"out\"\n").unwrap();
+        let err = no_sentinel.validate().unwrap_err();
+        assert!(err.to_string().contains("match_anywhere"), "{err}");
+
+        let mut anywhere = no_sentinel.clone();
+        anywhere.custom_patterns[0].match_anywhere = true;
+        assert!(anywhere.validate().is_ok());
+        anywhere.custom_patterns[0].sentinel = "out.json".to_string();
+        let err = anywhere.validate().unwrap_err();
+        assert!(err.to_string().contains("both a sentinel and match_anywhere"), "{err}");
+    }
+
+    #[test]
+    fn test_validate_pattern_min_size() {
+        let parse = |toml_str: &str| toml::from_str::<Config>(toml_str).unwrap();
+
+        let config = parse(
+            r#"
+min_size = "100MB"
+pattern_min_size = { cargo = "500MB", node = 0 }
+"#,
+        );
+        assert_eq!(config.min_size, Some(ByteSize(100 << 20)));
+        assert_eq!(config.pattern_min_size["cargo"], ByteSize(500 << 20));
+        assert_eq!(config.pattern_min_size["node"], ByteSize(0));
+        assert!(config.validate().is_ok());
+
+        let err = parse("pattern_min_size = { crago = \"1GB\" }\n").validate().unwrap_err();
+        assert!(err.to_string().contains("crago"), "{err}");
+        let err = parse("pattern_min_size = { vm-vmdk = 0 }\n").validate().unwrap_err();
+        assert!(err.to_string().contains("can't be 0"), "{err}");
+    }
+
+    #[test]
+    fn test_config_issues() {
+        let source = r#"
+scan_roots = ["~"]
+disable_patterns = ["nod"]
+disable_exclude_paths = ["~/.npm", "~/Movies"]
+extra_exclude_paths = ["~/.cargo"]
+exclusion_parallelism = 0
+
+[[custom_patterns]]
+name = "dupe"
+directory = "out"
+sentinel = "out.json"
+
+[[custom_patterns]]
+name = "dupe"
+directory = "gen"
+sentinel = "gen.json"
+"#;
+        let config: Config = toml::from_str(source).unwrap();
+        let issues: Vec<(Severity, usize)> = config
+            .issues()
+            .iter()
+            .map(|i| (i.severity, find_line(source, &i.needle).unwrap()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (Severity::Error, 6),
+                (Severity::Warning, 3),
+                (Severity::Warning, 9),
+                (Severity::Warning, 4),
+                (Severity::Warning, 5),
+            ]
+        );
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("exclusion_parallelism"), "{err}");
+
+        assert!(Config::default().issues().is_empty());
+        let template: Config = toml::from_str(Config::default_toml()).unwrap();
+        assert!(template.issues().is_empty());
+    }
+
+    #[test]
+    fn test_scan_disabled_warns_about_custom_patterns() {
+        let source = r#"
+scan_enabled = false
+
+[[custom_patterns]]
+name = "gen"
+directory = "gen"
+sentinel = "gen.json"
+"#;
+        let config: Config = toml::from_str(source).unwrap();
+        assert!(!config.scan_enabled);
+        let issues = config.issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert_eq!(find_line(source, &issues[0].needle), Some(2));
+        assert!(config.validate().is_ok());
+        assert!(Config::default().scan_enabled);
+    }
+
+    #[test]
+    fn test_add_and_remove_patterns() {
+        let mut config = Config::default();
+        config.add_custom_pattern(CustomPattern::new("gen", "gen", "gen.json")).unwrap();
+        assert_eq!(config.custom_patterns.len(), 1);
+        assert!(config.add_custom_pattern(CustomPattern::new("gen", "out", "x")).is_err());
+        assert!(config.add_custom_pattern(CustomPattern::new("node", "out", "x")).is_err());
+        assert!(config.add_custom_pattern(CustomPattern::new("js-extras", "out", "x")).is_err());
+        let mut invalid = config.clone();
+        invalid.add_custom_pattern(CustomPattern::new("blank", "", "x")).unwrap();
+        assert!(invalid.validate().is_err());
+
+        assert!(config.remove_pattern("gen").unwrap());
+        assert!(config.custom_patterns.is_empty());
+        assert!(config.disable_patterns.is_empty());
+
+        assert!(config.remove_pattern("node").unwrap());
+        assert!(!config.remove_pattern("node").unwrap());
+        assert_eq!(config.disable_patterns, vec!["node"]);
+
+        // Off-by-default built-ins only need to leave enable_patterns
+        assert!(!config.remove_pattern("js-coverage").unwrap());
+        config.enable_patterns.push("js-coverage".to_string());
+        assert!(config.remove_pattern("js-coverage").unwrap());
+        assert!(config.enable_patterns.is_empty());
+        assert_eq!(config.disable_patterns, vec!["node"]);
+
+        assert!(config.remove_pattern("nope").is_err());
+    }
+
+    #[test]
+    fn test_unknown_disable_tag_warns() {
+        let source = "disable_tags = [\"jvm\", \"cobol\"]\n";
+        let config: Config = toml::from_str(source).unwrap();
+        let issues = config.issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert!(issues[0].message.contains("cobol"));
+        assert!(!crate::patterns::config_patterns(&config).iter().any(|p| p.name == "maven"));
+    }
+
+    #[test]
+    fn test_unknown_category_warns() {
+        let source = "disable_patterns = [\"category:jvm\", \"category:cobol\"]\n";
+        let config: Config = toml::from_str(source).unwrap();
+        let issues = config.issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, "disable_patterns names an unknown category \"cobol\"");
+    }
+
+    #[test]
+    fn test_root_patterns() {
+        let source = r#"
+[root_patterns."/Volumes/Archive"]
+disable_patterns = ["node", "nope"]
+
+[[root_patterns."/Volumes/Archive".custom_patterns]]
+name = "archive-build"
+directory = "out"
+sentinel = "build.ninja"
+
+[[root_patterns."/Volumes/Archive".custom_patterns]]
+name = "broken"
+directory = "out"
+"#;
+        let config: Config = toml::from_str(source).unwrap();
+        let archive = &config.root_patterns["/Volumes/Archive"];
+        assert_eq!(archive.disable_patterns, vec!["node", "nope"]);
+        assert_eq!(archive.custom_patterns[0].name, "archive-build");
+
+        let messages: Vec<String> = config.issues().into_iter().map(|i| i.message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "custom pattern \"broken\" needs a sentinel (or match_anywhere = true)",
+                "root_patterns.\"/Volumes/Archive\".disable_patterns names an unknown pattern \"nope\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_line_skips_comments() {
+        let source = "# disable_patterns = [\"node\"]\ndisable_patterns = [\"node\"]\n";
+        assert_eq!(find_line(source, "\"node\""), Some(2));
+        assert_eq!(find_line(source, "cargo"), None);
+        assert_eq!(line_at(source, 0), 1);
+        assert_eq!(line_at(source, source.find("\ndisable").unwrap() + 1), 2);
+    }
+
+    #[test]
+    fn test_load_config_merges_includes() {
+        let dir = std::env::temp_dir().join("tmignore_test_include");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("local")).unwrap();
+        std::fs::write(
+            dir.join("config.toml"),
+            r#"
+include = ["local/machine.toml", "missing.toml"]
+scan_roots = ["~"]
+extra_exclude_paths = ["~/Movies"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("local/machine.toml"),
+            r#"
+include = ["more.toml"]
+scan_roots = ["~/Code"]
+extra_exclude_paths = ["~/VMs"]
+disable_patterns = ["bundler"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("local/more.toml"),
+            r#"
+scan_roots = ["/Volumes/Work"]
+
+[[custom_patterns]]
+name = "my-build"
+directory = "dist"
+sentinel = "turbo.json"
+"#,
+        )
+        .unwrap();
+
+        let config = load_config_from(&dir.join("config.toml")).unwrap();
+        assert_eq!(config.scan_roots, vec!["/Volumes/Work"]);
+        assert_eq!(config.extra_exclude_paths, vec!["~/Movies", "~/VMs"]);
+        assert_eq!(config.disable_patterns, vec!["bundler"]);
+        assert_eq!(config.custom_patterns.len(), 1);
+
+        let base = read_config(&dir.join("config.toml")).unwrap();
+        assert_eq!(base.extra_exclude_paths, vec!["~/Movies"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_yaml_config() {
+        let dir = std::env::temp_dir().join("tmignore_test_yaml");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("config.yaml"),
+            r#"
+include: [local.toml]
+scan_roots: ["~/Code"]
+min_size: 200MB
+custom_patterns:
+  - name: my-build
+    directory: dist
+    sentinel: turbo.json
+"#,
+        )
+        .unwrap();
+        // Includes may use either format
+        std::fs::write(dir.join("local.toml"), "extra_exclude_paths = [\"~/VMs\"]\n").unwrap();
+
+        let config = load_config_from(&dir.join("config.yaml")).unwrap();
+        assert_eq!(config.scan_roots, vec!["~/Code"]);
+        assert_eq!(config.min_size.map(|s| s.0), Some(200 << 20));
+        assert_eq!(config.custom_patterns[0].sentinel, "turbo.json");
+        assert_eq!(config.extra_exclude_paths, vec!["~/VMs"]);
+
+        let yaml = ConfigFormat::Yaml.serialize(&config).unwrap();
+        let reparsed: Config = ConfigFormat::Yaml.parse(&yaml).unwrap();
+        assert!(Config::diff(&config, &reparsed).is_empty());
+
+        let invalid = "scan_roots: [~]\nexclusion_parallelism: many\n";
+        let (line, _) = ConfigFormat::Yaml.syntax_error(invalid).unwrap();
+        assert_eq!(line, Some(2));
+        assert_eq!(ConfigFormat::of(Path::new("config.yml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::of(Path::new("config.toml")), ConfigFormat::Toml);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_config_detects_include_cycles() {
+        let dir = std::env::temp_dir().join("tmignore_test_include_cycle");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), "include = [\"a.toml\"]\n").unwrap();
+        std::fs::write(dir.join("a.toml"), "include = [\"b.toml\"]\n").unwrap();
+        std::fs::write(dir.join("b.toml"), "include = [\"config.toml\"]\n").unwrap();
+
+        let err = load_config_from(&dir.join("config.toml")).unwrap_err();
+        assert!(err.to_string().contains("include cycle"), "{err}");
+
+        // Only settings that can be layered are allowed in included files
+        std::fs::write(dir.join("b.toml"), "follow_symlinks = true\n").unwrap();
+        assert!(load_config_from(&dir.join("config.toml")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let toml_str = r#"
+scan_roots = ["~"]
+disable_patterns = ["bundler"]
+
+[[custom_patterns]]
+name = "base-build"
+directory = "dist"
+sentinel = "turbo.json"
+
+[profiles.work]
+scan_roots = ["~/Work"]
+disable_patterns = ["node"]
+
+[[profiles.work.custom_patterns]]
+name = "work-build"
+directory = "out"
+sentinel = "build.sbt"
+
+[profiles.personal]
+disable_patterns = ["maven"]
+"#;
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.profiles.len(), 2);
+
+        config.apply_profile("work").unwrap();
+        assert_eq!(config.scan_roots, vec!["~/Work"]);
+        assert_eq!(config.disable_patterns, vec!["bundler", "node"]);
+        let names: Vec<&str> = config.custom_patterns.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["base-build", "work-build"]);
+
+        // A profile without scan_roots keeps the base roots
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+        config.apply_profile("personal").unwrap();
+        assert_eq!(config.scan_roots, vec!["~"]);
+        assert_eq!(config.disable_patterns, vec!["bundler", "maven"]);
+
+        let err = config.apply_profile("missing").unwrap_err().to_string();
+        assert!(err.contains("personal, work"), "{err}");
+    }
+
+    #[test]
+    fn test_diff_identical_is_empty() {
+        assert!(Config::diff(&Config::default(), &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_reports_semantic_changes() {
+        let old = Config {
+            extra_exclude_paths: vec!["~/Movies".to_string(), "~/VMs".to_string()],
+            disable_patterns: vec!["bundler".to_string()],
+            ..Config::default()
+        };
+        let new = Config {
+            scan_roots: vec!["~/Code".to_string()],
+            extra_exclude_paths: vec!["~/Movies".to_string(), "~/Downloads".to_string()],
+            disable_patterns: vec!["node".to_string()],
+            custom_patterns: vec![CustomPattern {
+                name: "my-build".to_string(),
+                directory: "dist".to_string(),
+                sentinel: "turbo.json".to_string(),
+                match_anywhere: false,
+                sentinel_location: SentinelLocation::Parent,
+                sentinel_type: SentinelType::Any,
+                all_sentinels: Vec::new(),
+                kind: PatternKind::Directory,
+                sentinel_depth: None,
+                min_size: None,
+                older_than_days: None,
+                descend: false,
+                expected_size_hint: None,
+                tags: Vec::new(),
+                description: None,
+            }],
+            older_than_days: Some(30),
+            ..Config::default()
+        };
+
+        let changes = Config::diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange::ChangedScanRoot(vec!["~/Code".to_string()]),
+                ConfigChange::AddedExcludePath("~/Downloads".to_string()),
+                ConfigChange::RemovedExcludePath("~/VMs".to_string()),
+                ConfigChange::AddedPattern("my-build".to_string()),
+                ConfigChange::DisabledPattern("node".to_string()),
+                ConfigChange::EnabledPattern("bundler".to_string()),
+                ConfigChange::ChangedSetting {
+                    key: "older_than_days",
+                    old: "unset".to_string(),
+                    new: "30".to_string(),
+                },
+            ]
+        );
+        assert_eq!(changes[1].to_string(), "+ extra_exclude_paths: ~/Downloads");
     }
 
     #[test]
     fn test_expand_tilde() {
-        let expanded = expand_tilde("~/Documents");
+        let expanded = expand_tilde("~/Documents").unwrap();
         assert!(!expanded.to_string_lossy().contains('~'));
         assert!(expanded.to_string_lossy().ends_with("/Documents"));
 
-        let expanded_home = expand_tilde("~");
+        let expanded_home = expand_tilde("~").unwrap();
         assert!(!expanded_home.to_string_lossy().contains('~'));
 
-        let absolute = expand_tilde("/usr/local");
+        let absolute = expand_tilde("/usr/local").unwrap();
         assert_eq!(absolute, PathBuf::from("/usr/local"));
     }
 
+    #[test]
+    fn test_expand_path_resolves_relative_to_config_dir() {
+        let base = Path::new("/Users/alice/.config/tmignore");
+        assert_eq!(
+            expand_path("../../projects", base).unwrap(),
+            PathBuf::from("/Users/alice/projects")
+        );
+        assert_eq!(expand_path("../../../../..", base).unwrap(), PathBuf::from("/"));
+        assert_eq!(expand_path(".", base).unwrap(), base);
+        assert_eq!(expand_path("./sub/../code", base).unwrap(), base.join("code"));
+        assert_eq!(expand_path("/Volumes/Code", base).unwrap(), PathBuf::from("/Volumes/Code"));
+        assert_eq!(expand_path("~/Code", base).unwrap(), expand_tilde("~/Code").unwrap());
+    }
+
+    #[test]
+    fn test_relative_scan_roots_follow_their_config_file() {
+        let dir = std::env::temp_dir().join("tmignore_test_relative_roots");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("local")).unwrap();
+        std::fs::write(
+            dir.join("config.toml"),
+            r#"
+scan_roots = [".", "~/Code"]
+
+[profiles.work]
+scan_roots = ["work"]
+"#,
+        )
+        .unwrap();
+        let config = load_config_from(&dir.join("config.toml")).unwrap();
+        assert_eq!(config.scan_roots, vec![dir.to_string_lossy().into_owned(), "~/Code".into()]);
+        assert_eq!(config.profiles["work"].scan_roots, vec![dir.join("work").to_string_lossy()]);
+        // Saving goes through the base config, which keeps roots as written
+        assert_eq!(read_config(&dir.join("config.toml")).unwrap().scan_roots[0], ".");
+
+        std::fs::write(dir.join("config.toml"), "include = [\"local/machine.toml\"]\n").unwrap();
+        std::fs::write(dir.join("local/machine.toml"), "scan_roots = [\"../src\"]\n").unwrap();
+        let config = load_config_from(&dir.join("config.toml")).unwrap();
+        let canonical = dir.canonicalize().unwrap();
+        assert_eq!(config.scan_roots, vec![canonical.join("src").to_string_lossy()]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_expand_tilde_other_user() {
+        let lookup = |name: &str| (name == "alice").then(|| PathBuf::from("/Users/alice"));
+
+        assert_eq!(
+            expand_tilde_with("~alice/Code", lookup).unwrap(),
+            PathBuf::from("/Users/alice/Code")
+        );
+        assert_eq!(expand_tilde_with("~alice", lookup).unwrap(), PathBuf::from("/Users/alice"));
+        assert_eq!(expand_tilde_with("~alice/", lookup).unwrap(), PathBuf::from("/Users/alice"));
+
+        let err = expand_tilde_with("~nobody-here/Code", lookup).unwrap_err();
+        assert!(err.to_string().contains("nobody-here"), "{err}");
+
+        // A tilde that isn't leading is left alone
+        assert_eq!(
+            expand_tilde_with("/tmp/~alice", lookup).unwrap(),
+            PathBuf::from("/tmp/~alice")
+        );
+    }
+
+    #[test]
+    fn test_expand_tilde_system_user() {
+        // root exists in the user database on every Unix system
+        assert!(expand_tilde("~root").unwrap().is_absolute());
+    }
+
     #[test]
     fn test_contract_tilde() {
         let home = std::env::var("HOME").unwrap();
@@ -358,11 +2458,119 @@ sentinel = "turbo.json"
         assert_eq!(absolute, "/usr/local");
     }
 
+    #[test]
+    fn test_parse_path_list() {
+        let contents = "# exported from laptop\n~/Movies\n\n  /Volumes/Scratch  \n# ~/Old\n~/VMs/my #1\n";
+        assert_eq!(
+            parse_path_list(contents),
+            vec!["~/Movies", "/Volumes/Scratch", "~/VMs/my #1"]
+        );
+    }
+
+    #[test]
+    fn test_parse_age_days() {
+        assert_eq!(parse_age_days("30d").unwrap(), 30);
+        assert_eq!(parse_age_days("30").unwrap(), 30);
+        assert_eq!(parse_age_days("2w").unwrap(), 14);
+        assert_eq!(parse_age_days(" 7 D ").unwrap(), 7);
+        assert!(parse_age_days("d").is_err());
+        assert!(parse_age_days("1.5d").is_err());
+        assert!(parse_age_days("3mo").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        for input in ["2024-06-01", "2024-06-01T09:30", "2024-06-01T09:30:00Z", "2024-06-01T09:30:00-07:00"] {
+            assert_eq!(parse_timestamp(input).unwrap(), input);
+        }
+        for input in ["yesterday", "2024-6-1", "2024-06-01T", "2024-06-01T9:30", "2024-06-01 09:30"] {
+            assert!(parse_timestamp(input).is_err(), "{input}");
+        }
+    }
+
     #[test]
     fn test_default_toml_parses() {
         let _config: Config = toml::from_str(Config::default_toml()).unwrap();
     }
 
+    #[test]
+    fn test_default_toml_with_extra_paths() {
+        let paths = vec!["~/Movies".to_string(), "~/My \"VMs\"".to_string()];
+        let contents = default_toml_with(&paths);
+        let config: Config = toml::from_str(&contents).unwrap();
+        assert_eq!(config.extra_exclude_paths, paths);
+        // The template's documentation survives
+        assert!(contents.contains("# Virtual machines"));
+        assert!(contents.contains("# [[custom_patterns]]"));
+
+        let empty: Config = toml::from_str(&default_toml_with(&[])).unwrap();
+        assert!(empty.extra_exclude_paths.is_empty());
+    }
+
+    #[test]
+    fn test_merge_toml_keeps_comments() {
+        let original = Config::default_toml();
+        let mut config: Config = toml::from_str(original).unwrap();
+        // Nothing changed, nothing rewritten
+        assert_eq!(merge_toml(original, &config).unwrap(), original);
+
+        config.extra_exclude_paths.push("~/Movies".to_string());
+        let merged = merge_toml(original, &config).unwrap();
+        assert_eq!(merged, default_toml_with(&config.extra_exclude_paths));
+
+        config.extra_exclude_paths.push("~/VMs".to_string());
+        config.add_custom_pattern(CustomPattern::new("gen", "gen", "gen.json")).unwrap();
+        let merged = merge_toml(&merged, &config).unwrap();
+        assert!(merged.contains("# Virtual machines"));
+        assert!(merged.contains("# [[custom_patterns]]"));
+        let reloaded: Config = toml::from_str(&merged).unwrap();
+        assert_eq!(toml::to_string(&reloaded).unwrap(), toml::to_string(&config).unwrap());
+        // Settings left at their defaults aren't spelled out
+        assert!(!merged.contains("\nmatch_policy"));
+    }
+
+    #[test]
+    fn test_merge_toml_moves_comments_of_removed_entries() {
+        let original = "\
+# Backed up elsewhere
+extra_exclude_paths = [
+    # Media
+    \"~/Movies\",
+    \"~/Music\", # for now
+    # VMs
+    \"~/VMs\",
+]
+";
+        let mut config: Config = toml::from_str(original).unwrap();
+        config.extra_exclude_paths.retain(|p| p != "~/Movies");
+        let merged = merge_toml(original, &config).unwrap();
+        assert_eq!(merged, original.replace("    \"~/Movies\",\n", ""));
+
+        config.extra_exclude_paths.retain(|p| p != "~/VMs");
+        let merged = merge_toml(&merged, &config).unwrap();
+        assert!(merged.starts_with("# Backed up elsewhere\n"));
+        assert!(merged.contains("# VMs"));
+        let reloaded: Config = toml::from_str(&merged).unwrap();
+        assert_eq!(toml::to_string(&reloaded).unwrap(), toml::to_string(&config).unwrap());
+    }
+
+    #[test]
+    fn test_presets_render_valid_configs() {
+        for preset in PRESETS {
+            let expected = preset.config();
+            let contents = preset_toml(&expected);
+            let config: Config = toml::from_str(&contents)
+                .unwrap_or_else(|e| panic!("preset {}: {}", preset.name, e));
+            config.validate().unwrap();
+            assert_eq!(config.extra_exclude_paths, expected.extra_exclude_paths);
+            assert_eq!(config.disable_patterns, expected.disable_patterns);
+            assert_eq!(config.custom_patterns.len(), expected.custom_patterns.len());
+            assert!(contents.contains("# [[custom_patterns]]"));
+        }
+        assert!(find_preset("ios-dev").is_some());
+        assert!(find_preset("nope").is_none());
+    }
+
     #[test]
     fn test_builtin_exclude_count() {
         assert!(builtin_exclude_paths().len() >= 20);
@@ -1,66 +1,550 @@
 use anyhow::{Context, Result};
-use std::path::Path;
+use crate::logging::debug;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use walkdir::WalkDir;
 
-/// Check if a path is already excluded from Time Machine backups.
+/// The extended attribute `tmutil addexclusion` writes for a sticky exclusion.
+const EXCLUDE_XATTR: &str = "com.apple.metadata:com_apple_backup_excludeItem";
+/// What tmutil writes to it: the string "com.apple.backupd" as a binary property
+/// list (header, string object, offset table, then the 32-byte trailer).
+const EXCLUDE_XATTR_VALUE: &[u8] = b"bplist00\x5f\x10\x11com.apple.backupd\x08\
+    \0\0\0\0\0\0\x01\x01\0\0\0\0\0\0\0\x01\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\x1c";
+
+/// Whether exclusions are changed and checked by running tmutil instead of
+/// through the attribute directly. See `use_tmutil`.
+static USE_TMUTIL: AtomicBool = AtomicBool::new(false);
+
+/// How many times adding or removing an exclusion is tried before giving up,
+/// whether through the attribute or `tmutil`. Both occasionally fail
+/// transiently, e.g. while Spotlight reindexes.
+const EXCLUSION_ATTEMPTS: u32 = 3;
+/// Wait before the first retry; doubled for each retry after it.
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// What to do about `AccessDenied` errors, shown once per run rather than per path.
+pub const FULL_DISK_ACCESS_HINT: &str = "Grant Full Disk Access to your terminal (or to the \
+    tmignore binary, for the LaunchAgent) in System Settings > Privacy & Security > Full Disk \
+    Access, then run again.";
+
+/// Why changing or checking a path's exclusion failed, read from tmutil's error
+/// output or the OS error. Shown below the original message in the error chain,
+/// with what to do about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExclusionError {
+    /// Refused by macOS privacy controls. On a fresh machine this almost always
+    /// means the terminal running tmignore (or tmignore itself) lacks Full Disk Access.
+    PermissionDenied,
+    /// The path was deleted or renamed since it was found.
+    PathNotFound,
+    /// The volume can't hold exclusions, e.g. read-only or network volumes.
+    VolumeNotSupported,
+    DiskFull,
+    /// Anything else, with tmutil's output.
+    Unknown(String),
+}
+
+impl ExclusionError {
+    fn from_stderr(stderr: &str) -> Self {
+        let has = |needles: &[&str]| needles.iter().any(|n| stderr.contains(n));
+        if has(&["Operation not permitted", "Error (100002)"]) {
+            ExclusionError::PermissionDenied
+        } else if has(&["No such file or directory", "does not exist"]) {
+            ExclusionError::PathNotFound
+        } else if has(&["Read-only file system", "Operation not supported", "not supported"]) {
+            ExclusionError::VolumeNotSupported
+        } else if has(&["No space left on device"]) {
+            ExclusionError::DiskFull
+        } else {
+            ExclusionError::Unknown(stderr.to_string())
+        }
+    }
+
+    /// Failures that retrying won't fix.
+    fn is_permanent(&self) -> bool {
+        !matches!(self, ExclusionError::Unknown(_))
+    }
+}
+
+impl fmt::Display for ExclusionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExclusionError::PermissionDenied => {
+                write!(f, "permission denied. {}", FULL_DISK_ACCESS_HINT)
+            }
+            ExclusionError::PathNotFound => write!(
+                f,
+                "the path no longer exists, probably removed during the run. The next run \
+                 won't find it"
+            ),
+            ExclusionError::VolumeNotSupported => write!(
+                f,
+                "its volume doesn't support exclusions (read-only or network volumes). Add it \
+                 to `disable_exclude_paths` or skip the directory with `--exclude-dir`"
+            ),
+            ExclusionError::DiskFull => {
+                write!(f, "the disk is full. Free some space and run again")
+            }
+            ExclusionError::Unknown(_) => write!(
+                f,
+                "unrecognized error. Check that Time Machine is set up with \
+                 `tmutil destinationinfo`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExclusionError {}
+
+/// Whether an error came from being refused access to the path.
+pub fn is_access_denied(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ExclusionError>() == Some(&ExclusionError::PermissionDenied)
+}
+
+/// The error for a failed tmutil `verb`: tmutil's message, caused by what it
+/// means (`ExclusionError`).
+fn tmutil_error(verb: &str, path: &Path, stderr: &[u8]) -> anyhow::Error {
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+    anyhow::Error::new(ExclusionError::from_stderr(&stderr))
+        .context(format!("tmutil {} failed for {}: {}", verb, path.display(), stderr))
+}
+
+/// Check if a path is already excluded from Time Machine backups, by its own
+/// exclusion or one on a directory above it.
 pub fn is_excluded(path: &Path) -> Result<bool> {
+    Ok(excluded_by(path)?.is_some())
+}
+
+/// The path whose exclusion keeps `path` out of backups: `path` itself or the
+/// nearest excluded directory above it. None if it isn't excluded.
+///
+/// Reads the attribute directly. With `use_tmutil` it asks `tmutil isexcluded`
+/// instead, which only answers yes or no, so a yes is reported as `path` itself.
+pub fn excluded_by(path: &Path) -> Result<Option<PathBuf>> {
+    if USE_TMUTIL.load(Ordering::Relaxed) {
+        return Ok(tmutil_is_excluded(path)?.then(|| path.to_path_buf()));
+    }
+    nearest_with_xattr(path, EXCLUDE_XATTR)
+}
+
+/// `path` or the nearest directory above it with the attribute `name`. Only
+/// being refused access to `path` itself is an error; directories above that
+/// can't be read just don't count.
+fn nearest_with_xattr(path: &Path, name: &str) -> Result<Option<PathBuf>> {
+    match xattr::get(path, name) {
+        Ok(Some(_)) => return Ok(Some(path.to_path_buf())),
+        Ok(None) => {}
+        Err(e) => {
+            let err = xattr_error("read", path, e);
+            if is_access_denied(&err) {
+                return Err(err);
+            }
+        }
+    }
+    Ok(path
+        .ancestors()
+        .skip(1)
+        .find(|dir| matches!(xattr::get(dir, name), Ok(Some(_))))
+        .map(Path::to_path_buf))
+}
+
+fn tmutil_is_excluded(path: &Path) -> Result<bool> {
     let output = Command::new("tmutil")
         .args(["isexcluded", &path.to_string_lossy()])
         .output()
         .with_context(|| format!("Failed to run tmutil isexcluded on {}", path.display()))?;
 
+    if !output.status.success() {
+        let err = tmutil_error("isexcluded", path, &output.stderr);
+        if is_access_denied(&err) {
+            return Err(err);
+        }
+    }
+
     let stdout = String::from_utf8_lossy(&output.stdout);
     // tmutil isexcluded outputs "[Excluded] <path>" or "[Included] <path>"
     Ok(stdout.contains("[Excluded]"))
 }
 
+/// Check and change exclusions by running `tmutil isexcluded`, `addexclusion`
+/// and `removeexclusion` rather than through the attribute directly
+/// (`use_tmutil` in the config, or `--use-tmutil`). Slower, as it spawns a
+/// process per path.
+pub fn use_tmutil(enabled: bool) {
+    USE_TMUTIL.store(enabled, Ordering::Relaxed);
+}
+
 /// Add a sticky exclusion to a path (writes extended attribute, no root needed).
 pub fn add_exclusion(path: &Path) -> Result<()> {
-    let output = Command::new("tmutil")
-        .args(["addexclusion", &path.to_string_lossy()])
+    with_retries(EXCLUSION_ATTEMPTS, RETRY_BACKOFF, || {
+        if USE_TMUTIL.load(Ordering::Relaxed) {
+            change_exclusion("addexclusion", path)
+        } else {
+            xattr::set(path, EXCLUDE_XATTR, EXCLUDE_XATTR_VALUE)
+                .map_err(|e| xattr_error("write", path, e))
+        }
+    })
+}
+
+/// Remove a sticky exclusion from a path. Paths without one are left as they are.
+pub fn remove_exclusion(path: &Path) -> Result<()> {
+    with_retries(EXCLUSION_ATTEMPTS, RETRY_BACKOFF, || {
+        if USE_TMUTIL.load(Ordering::Relaxed) {
+            return change_exclusion("removeexclusion", path);
+        }
+        match xattr::get(path, EXCLUDE_XATTR) {
+            Ok(None) => Ok(()),
+            Ok(Some(_)) => {
+                xattr::remove(path, EXCLUDE_XATTR).map_err(|e| xattr_error("remove", path, e))
+            }
+            Err(e) => Err(xattr_error("read", path, e)),
+        }
+    })
+}
+
+/// Remove every sticky exclusion on directories under `roots` by deleting the
+/// attribute directly, without `tmutil` or Spotlight (so it works before
+/// `mdfind` has indexed anything). Returns each directory that had one, with the
+/// outcome of removing it. Unreadable directories are passed over.
+pub fn remove_all_tmignore_exclusions(roots: &[PathBuf]) -> Vec<(PathBuf, Result<()>)> {
+    remove_xattr_under(roots, EXCLUDE_XATTR)
+}
+
+/// Find every directory under `roots` with a sticky exclusion by reading the
+/// attribute directly, without Spotlight. Unreadable directories are passed over.
+pub fn find_exclusions_under(roots: &[PathBuf]) -> Vec<PathBuf> {
+    dirs_with_xattr(roots, EXCLUDE_XATTR)
+}
+
+/// Ask Spotlight for every existing path on the system with a sticky exclusion.
+/// Misses anything `mdfind` hasn't indexed yet, e.g. right after boot.
+pub fn find_all_exclusions() -> Result<Vec<PathBuf>> {
+    let output = Command::new("mdfind")
+        .args(["com_apple_backup_excludeItem = 'com.apple.backupd'"])
         .output()
-        .with_context(|| format!("Failed to run tmutil addexclusion on {}", path.display()))?;
+        .context("Failed to run mdfind")?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("tmutil addexclusion failed for {}: {}", path.display(), stderr.trim());
+        anyhow::bail!("mdfind failed: {}", stderr.trim());
     }
 
-    Ok(())
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .map(|line| PathBuf::from(line.trim()))
+        .filter(|path| path.exists())
+        .collect())
 }
 
-/// Remove a sticky exclusion from a path.
-pub fn remove_exclusion(path: &Path) -> Result<()> {
+fn dirs_with_xattr(roots: &[PathBuf], name: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for root in roots {
+        let dirs = WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir());
+        for entry in dirs {
+            if let Ok(Some(_)) = xattr::get(entry.path(), name) {
+                found.push(entry.into_path());
+            }
+        }
+    }
+    found
+}
+
+fn remove_xattr_under(roots: &[PathBuf], name: &str) -> Vec<(PathBuf, Result<()>)> {
+    dirs_with_xattr(roots, name)
+        .into_iter()
+        .map(|path| {
+            let result = xattr::remove(&path, name)
+                .with_context(|| format!("Failed to remove the exclusion from {}", path.display()));
+            (path, result)
+        })
+        .collect()
+}
+
+/// The error for failing to `verb` the exclusion attribute: the OS error, caused
+/// by what it means (`ExclusionError`), as for tmutil's errors.
+fn xattr_error(verb: &str, path: &Path, err: std::io::Error) -> anyhow::Error {
+    use nix::errno::Errno;
+    use std::io::ErrorKind;
+    // Both spellings of "not supported", which are different errors on macOS
+    let unsupported = err.raw_os_error().is_some_and(|code| {
+        code == Errno::ENOTSUP as i32 || code == Errno::EOPNOTSUPP as i32
+    });
+    let kind = match err.kind() {
+        ErrorKind::PermissionDenied => ExclusionError::PermissionDenied,
+        ErrorKind::NotFound => ExclusionError::PathNotFound,
+        ErrorKind::ReadOnlyFilesystem | ErrorKind::Unsupported => {
+            ExclusionError::VolumeNotSupported
+        }
+        _ if unsupported => ExclusionError::VolumeNotSupported,
+        ErrorKind::StorageFull => ExclusionError::DiskFull,
+        _ => ExclusionError::Unknown(err.to_string()),
+    };
+    anyhow::Error::new(kind).context(format!(
+        "Failed to {} the exclusion attribute of {}: {}",
+        verb,
+        path.display(),
+        err
+    ))
+}
+
+fn change_exclusion(verb: &str, path: &Path) -> Result<()> {
     let output = Command::new("tmutil")
-        .args(["removeexclusion", &path.to_string_lossy()])
+        .args([verb, &path.to_string_lossy()])
         .output()
-        .with_context(|| format!("Failed to run tmutil removeexclusion on {}", path.display()))?;
+        .with_context(|| format!("Failed to run tmutil {} on {}", verb, path.display()))?;
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!(
-            "tmutil removeexclusion failed for {}: {}",
-            path.display(),
-            stderr.trim()
-        );
+        return Err(tmutil_error(verb, path, &output.stderr));
     }
 
     Ok(())
 }
 
-/// Get the size of a directory using `du -sh`.
-pub fn dir_size(path: &Path) -> String {
-    Command::new("du")
-        .args(["-sh", &path.to_string_lossy()])
-        .output()
-        .ok()
-        .and_then(|o| {
-            if o.status.success() {
-                let s = String::from_utf8_lossy(&o.stdout).to_string();
-                s.split_whitespace().next().map(|s| s.to_string())
-            } else {
-                None
+/// Run `op` up to `attempts` times, sleeping `backoff` (doubling each time)
+/// between failures. Errors tmutil explained (see `ExclusionError`) won't go away
+/// by waiting, so they are returned right away, as is the last error.
+fn with_retries(attempts: u32, backoff: Duration, mut op: impl FnMut() -> Result<()>) -> Result<()> {
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Ok(()) => return Ok(()),
+            Err(e)
+                if attempt >= attempts
+                    || e.downcast_ref::<ExclusionError>().is_some_and(|k| k.is_permanent()) =>
+            {
+                return Err(e);
             }
-        })
-        .unwrap_or_else(|| "?".to_string())
+            Err(e) => {
+                debug!("{:#} (attempt {} of {}), retrying", e, attempt, attempts);
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tmutil_error_recognizes_access_denied() {
+        let path = Path::new("/Users/me/Library/Mail/cache");
+        let denied = tmutil_error(
+            "addexclusion",
+            path,
+            b"/Users/me/Library/Mail/cache: Error (100002) while attempting to change exclusion setting.\n",
+        );
+        assert!(is_access_denied(&denied));
+        let denied = tmutil_error("isexcluded", path, b"Operation not permitted");
+        assert!(is_access_denied(&denied));
+
+        let other = tmutil_error("addexclusion", path, b"No such file or directory\n");
+        assert!(!is_access_denied(&other));
+        assert_eq!(
+            other.to_string(),
+            "tmutil addexclusion failed for /Users/me/Library/Mail/cache: No such file or directory"
+        );
+    }
+
+    #[test]
+    fn test_tmutil_error_explains_known_failures() {
+        let kind = |stderr: &str| ExclusionError::from_stderr(stderr);
+        assert_eq!(kind("/x: No such file or directory"), ExclusionError::PathNotFound);
+        assert_eq!(kind("/Volumes/NAS: Read-only file system"), ExclusionError::VolumeNotSupported);
+        assert_eq!(kind("No space left on device"), ExclusionError::DiskFull);
+        assert_eq!(kind("Error (-1)"), ExclusionError::Unknown("Error (-1)".to_string()));
+
+        // The chain has tmutil's message, then what it means and how to fix it
+        let err = tmutil_error("addexclusion", Path::new("/x"), b"Operation not permitted\n");
+        let chain = format!("{:#}", err);
+        assert!(chain.starts_with("tmutil addexclusion failed for /x: Operation not permitted: "));
+        assert!(chain.contains("permission denied. Grant Full Disk Access"));
+    }
+
+    #[test]
+    fn test_exclude_xattr_value_is_tmutils_bplist() {
+        let value = EXCLUDE_XATTR_VALUE;
+        assert!(value.starts_with(b"bplist00"));
+        // A string object of 17 bytes, the offset table pointing at it, then the
+        // trailer with one object and the offset table's position last
+        assert_eq!(&value[8..11], b"\x5f\x10\x11");
+        assert_eq!(&value[11..28], b"com.apple.backupd");
+        assert_eq!(value[28], 8);
+        assert_eq!(value.len(), 28 + 1 + 32);
+        assert_eq!(value[value.len() - 1], 28);
+    }
+
+    #[test]
+    fn test_xattr_error_explains_os_errors() {
+        let os_error = std::io::Error::from_raw_os_error;
+        let kind = |code: i32| {
+            let err = xattr_error("write", Path::new("/x"), os_error(code));
+            err.downcast_ref::<ExclusionError>().cloned().unwrap()
+        };
+        assert_eq!(kind(nix::errno::Errno::EPERM as i32), ExclusionError::PermissionDenied);
+        assert_eq!(kind(nix::errno::Errno::ENOENT as i32), ExclusionError::PathNotFound);
+        assert_eq!(kind(nix::errno::Errno::EROFS as i32), ExclusionError::VolumeNotSupported);
+        assert_eq!(kind(nix::errno::Errno::ENOTSUP as i32), ExclusionError::VolumeNotSupported);
+        assert_eq!(kind(nix::errno::Errno::ENOSPC as i32), ExclusionError::DiskFull);
+        assert!(matches!(kind(nix::errno::Errno::EIO as i32), ExclusionError::Unknown(_)));
+
+        let denied = xattr_error("write", Path::new("/x"), os_error(nix::errno::Errno::EACCES as i32));
+        assert!(is_access_denied(&denied));
+        let chain = format!("{:#}", denied);
+        assert!(chain.starts_with("Failed to write the exclusion attribute of /x"), "{chain}");
+    }
+
+    /// Exclusions written directly are the ones tmutil reads back, and removing
+    /// them is seen too.
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_native_exclusion_round_trips_with_tmutil() {
+        let dir = std::env::temp_dir().join("tmignore_test_native_exclusion");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        add_exclusion(&dir).unwrap();
+        assert_eq!(xattr::get(&dir, EXCLUDE_XATTR).unwrap().as_deref(), Some(EXCLUDE_XATTR_VALUE));
+        assert!(is_excluded(&dir).unwrap());
+        remove_exclusion(&dir).unwrap();
+        assert!(!is_excluded(&dir).unwrap());
+        // Removing an exclusion that isn't there is fine
+        remove_exclusion(&dir).unwrap();
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_nearest_with_xattr_checks_ancestors() {
+        let dir = std::env::temp_dir().join("tmignore_test_nearest_xattr");
+        let _ = std::fs::remove_dir_all(&dir);
+        let nested = dir.join("app/node_modules/pkg/lib");
+        std::fs::create_dir_all(&nested).unwrap();
+        let name = "user.tmignore_test";
+        assert_eq!(nearest_with_xattr(&nested, name).unwrap(), None);
+        // Only user attributes can be written off macOS; skip where even those aren't
+        if xattr::set(dir.join("app/node_modules"), name, b"1").is_err() {
+            return;
+        }
+
+        let excluded = dir.join("app/node_modules");
+        assert_eq!(nearest_with_xattr(&excluded, name).unwrap(), Some(excluded.clone()));
+        assert_eq!(nearest_with_xattr(&nested, name).unwrap(), Some(excluded.clone()));
+        assert_eq!(nearest_with_xattr(&dir.join("app"), name).unwrap(), None);
+        // The closest one wins
+        xattr::set(&nested, name, b"1").unwrap();
+        assert_eq!(nearest_with_xattr(&nested, name).unwrap(), Some(nested.clone()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// How long checking a thousand directories takes natively and with tmutil:
+    /// `cargo test compare_is_excluded -- --ignored --nocapture`.
+    #[cfg(target_os = "macos")]
+    #[test]
+    #[ignore]
+    fn bench_compare_is_excluded() {
+        let dir = std::env::temp_dir().join("tmignore_bench_is_excluded");
+        let _ = std::fs::remove_dir_all(&dir);
+        let dirs: Vec<PathBuf> =
+            (0..1000).map(|i| dir.join(format!("project-{i}/node_modules"))).collect();
+        for path in &dirs {
+            std::fs::create_dir_all(path).unwrap();
+            add_exclusion(path).unwrap();
+        }
+
+        let time = |check: &dyn Fn(&Path) -> bool| {
+            let started = std::time::Instant::now();
+            assert!(dirs.iter().all(|path| check(path)));
+            started.elapsed()
+        };
+        let native = time(&|path| nearest_with_xattr(path, EXCLUDE_XATTR).unwrap().is_some());
+        let tmutil = time(&|path| tmutil_is_excluded(path).unwrap());
+        println!(
+            "{} directories: native {:?} ({:?} each), tmutil {:?} ({:?} each)",
+            dirs.len(),
+            native,
+            native / dirs.len() as u32,
+            tmutil,
+            tmutil / dirs.len() as u32
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_remove_xattr_under() {
+        let dir = std::env::temp_dir().join("tmignore_test_remove_xattr");
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["a/node_modules", "b/target", "b/src"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        // Only user attributes can be written off macOS; skip where even those aren't
+        let name = "user.tmignore_test";
+        if xattr::set(dir.join("a/node_modules"), name, b"1").is_err() {
+            return;
+        }
+        xattr::set(dir.join("b/target"), name, b"1").unwrap();
+
+        let mut removed: Vec<PathBuf> = remove_xattr_under(&[dir.join("a"), dir.join("b")], name)
+            .into_iter()
+            .map(|(path, result)| {
+                assert!(result.is_ok());
+                path
+            })
+            .collect();
+        removed.sort();
+        assert_eq!(removed, vec![dir.join("a/node_modules"), dir.join("b/target")]);
+        assert!(xattr::get(dir.join("b/target"), name).unwrap().is_none());
+        assert!(remove_xattr_under(std::slice::from_ref(&dir), name).is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_with_retries() {
+        let mut calls = 0;
+        let result = with_retries(3, Duration::ZERO, || {
+            calls += 1;
+            if calls < 3 { anyhow::bail!("busy") } else { Ok(()) }
+        });
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = with_retries(3, Duration::ZERO, || {
+            calls += 1;
+            anyhow::bail!("busy")
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+
+        // Permission failures are not retried
+        let mut calls = 0;
+        let result = with_retries(3, Duration::ZERO, || {
+            calls += 1;
+            Err(tmutil_error("addexclusion", Path::new("/x"), b"Operation not permitted"))
+        });
+        assert!(is_access_denied(&result.unwrap_err()));
+        assert_eq!(calls, 1);
+
+        // Nor are missing paths
+        let mut calls = 0;
+        let _ = with_retries(3, Duration::ZERO, || {
+            calls += 1;
+            Err(tmutil_error("addexclusion", Path::new("/x"), b"No such file or directory"))
+        });
+        assert_eq!(calls, 1);
+    }
 }
@@ -0,0 +1,168 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use log::{debug, error, info, warn};
+
+/// Print command output (e.g. `export`) to stdout verbatim, bypassing log levels,
+/// prefixes and the log file, so it stays machine-readable.
+macro_rules! output {
+    ($($arg:tt)*) => {
+        println!($($arg)*)
+    };
+}
+pub(crate) use output;
+
+const LOG_FILE_NAME: &str = "tmignore.log";
+
+/// Console lines go to stdout (info and below) or stderr (warn and error). On a
+/// terminal they are printed as-is; otherwise (e.g. under the LaunchAgent) each line
+/// gets a `[TIMESTAMP] [LEVEL]` prefix. Every line is also appended to the log file,
+/// always prefixed.
+struct Logger {
+    structured_console: bool,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        let structured = (!message.trim().is_empty())
+            .then(|| format!("[{}] [{}] {}", timestamp(), record.level(), message.trim_start()));
+
+        let console = if self.structured_console {
+            structured.clone()
+        } else if record.level() == Level::Warn {
+            Some(format!("Warning: {}", message))
+        } else {
+            Some(message)
+        };
+        if let Some(line) = console {
+            if record.level() <= Level::Warn {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+
+        if let (Some(file), Some(line)) = (&self.file, structured)
+            && let Ok(mut file) = file.lock()
+        {
+            // Logging must never fail a run
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Parse a `$TMIGNORE_LOG` value. Unknown values fall back to `info`.
+fn parse_level(value: Option<&str>) -> LevelFilter {
+    value
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+/// Install the logger. The level comes from `$TMIGNORE_LOG` (`debug`, `info`, `warn`,
+/// `error`, `off`). Lines are also appended to `tmignore.log` in the log directory
+/// when it exists (it is created by `tmignore install`).
+pub fn init() {
+    let level = parse_level(std::env::var("TMIGNORE_LOG").ok().as_deref());
+
+    let log_dir = crate::service::get_log_dir();
+    let file = log_dir
+        .is_dir()
+        .then(|| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_dir.join(LOG_FILE_NAME))
+                .ok()
+        })
+        .flatten()
+        .map(Mutex::new);
+
+    let logger = Logger {
+        structured_console: !std::io::stdout().is_terminal(),
+        file,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Current UTC time as an ISO 8601 timestamp, e.g. "2024-03-01T12:00:00Z".
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    format_timestamp(secs)
+}
+
+pub(crate) fn format_timestamp(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64;
+    let secs_of_day = unix_secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) date in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_294_399), "2024-03-01T11:59:59Z");
+        assert_eq!(format_timestamp(4_102_444_800), "2100-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level(None), LevelFilter::Info);
+        assert_eq!(parse_level(Some("debug")), LevelFilter::Debug);
+        assert_eq!(parse_level(Some("WARN")), LevelFilter::Warn);
+        assert_eq!(parse_level(Some("off")), LevelFilter::Off);
+        assert_eq!(parse_level(Some("loud")), LevelFilter::Info);
+    }
+}
@@ -1,15 +1,29 @@
 mod config;
 mod excluder;
+mod logging;
+mod metrics;
+mod notify;
 mod patterns;
+mod report;
 mod scanner;
 mod service;
+mod size;
+mod snapshot;
 mod state;
+mod watch;
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use config::{contract_tilde, expand_tilde};
-use state::{ExcludedEntry, RunState};
-
+use logging::{error, info, output, warn};
+use state::{ExcludedEntry, RunState, WarningEntry};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -20,28 +34,65 @@ use state::{ExcludedEntry, RunState};
 struct Cli {
     #[command(subcommand)]
     command: Cmd,
+
+    /// Check and change exclusions by running tmutil for each path instead of
+    /// reading and writing their attribute directly (also `use_tmutil` in the
+    /// config)
+    #[arg(long, global = true)]
+    use_tmutil: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Cmd {
     /// Scan for dependency directories and exclude them from backups
-    Run {
-        /// Show what would be excluded without making changes
+    Run(RunArgs),
+
+    /// Show currently excluded paths from the last run
+    List {
+        /// Show the number of files in each excluded directory
         #[arg(long)]
-        dry_run: bool,
+        count_files: bool,
 
-        /// Print detailed output during scanning
-        #[arg(short, long)]
-        verbose: bool,
+        /// Instead of the last run, list paths first excluded since this time:
+        /// a duration back from now (e.g. `7d`, `2w`) or a UTC timestamp
+        /// (e.g. `2024-06-03` or `2024-06-03T09:00:00Z`)
+        #[arg(long, value_name = "TIME", value_parser = parse_since)]
+        since: Option<String>,
     },
 
-    /// Show currently excluded paths from the last run
-    List,
-
-    /// Add an arbitrary path to config and exclude it immediately
+    /// Add an arbitrary path to config and exclude it immediately (or a custom
+    /// pattern, with --pattern)
     Add {
         /// Path to exclude (supports ~ expansion)
-        path: String,
+        #[arg(required_unless_present = "pattern", conflicts_with = "pattern")]
+        path: Option<String>,
+
+        /// Ask for confirmation (or fail, when not run from a terminal) if the
+        /// path is larger than this size, e.g. 5G or 500MB
+        #[arg(long, value_name = "SIZE", value_parser = size::parse_size)]
+        check_size: Option<u64>,
+
+        /// Add a custom pattern to the config instead, asking for any of
+        /// --pattern-name, --directory and --sentinel not given
+        #[arg(long, conflicts_with = "check_size")]
+        pattern: bool,
+
+        /// Name for the new pattern, e.g. gen
+        #[arg(long, value_name = "NAME", conflicts_with = "path")]
+        pattern_name: Option<String>,
+
+        /// Directory name the new pattern excludes, e.g. generated
+        #[arg(long, value_name = "NAME", conflicts_with = "path")]
+        directory: Option<String>,
+
+        /// File or directory that must sit next to it, e.g. codegen.json
+        #[arg(long, value_name = "NAME", conflicts_with = "path")]
+        sentinel: Option<String>,
+
+        /// After adding the pattern, list what it matches under the scan roots
+        /// (nothing is excluded)
+        #[arg(long, conflicts_with = "path")]
+        test_pattern: bool,
     },
 
     /// Remove a path from config and un-exclude it
@@ -50,14 +101,78 @@ enum Cmd {
         path: String,
     },
 
+    /// Show how much disk space a path takes, without excluding it
+    Size {
+        /// File or directory to measure (supports ~ expansion)
+        path: String,
+
+        /// Emit a JSON object with the size in bytes and the file count
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Add exclude paths from a file (one per line, # comments allowed) to the config
+    Import {
+        /// File to read paths from
+        file: std::path::PathBuf,
+
+        /// Also exclude the imported paths from backups immediately
+        #[arg(long)]
+        apply: bool,
+
+        /// Show the config changes the import would make without saving them
+        #[arg(long, conflicts_with = "apply")]
+        dry_run: bool,
+    },
+
+    /// Print the effective exclude paths, one per line (suitable for `import`)
+    Export {
+        /// Emit JSON including whether each path is built-in or extra
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List, check or exclude every path tmignore knows about
+    Paths {
+        #[command(subcommand)]
+        action: PathsCmd,
+    },
+
+    /// Inspect the built-in and custom patterns
+    Patterns {
+        #[command(subcommand)]
+        action: PatternsCmd,
+    },
+
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCmd,
+    },
+
+    /// Same as `config validate`
+    ValidateConfig(ValidateArgs),
+
     /// Show service status and last run statistics
-    Status,
+    Status {
+        /// Emit a JSON object instead of text, for health checks
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Generate a default config file
     Init {
         /// Overwrite existing config file
         #[arg(long)]
         overwrite: bool,
+
+        /// Start from a preset tailored to a workflow (see --list-presets)
+        #[arg(long, value_name = "NAME", value_parser = clap::builder::PossibleValuesParser::new(config::PRESETS.iter().map(|p| p.name)))]
+        preset: Option<String>,
+
+        /// List the available presets and exit
+        #[arg(long, conflicts_with_all = ["overwrite", "preset"])]
+        list_presets: bool,
     },
 
     /// Install the LaunchAgent for automatic background runs
@@ -70,342 +185,2797 @@ enum Cmd {
     /// Remove the LaunchAgent
     Uninstall,
 
-    /// Remove backup exclusions set by tmignore
+    /// Delete tmignore's state files without touching any backup exclusions
+    Clean,
+
+    /// Delete the LaunchAgent's log files (the log directory itself is kept)
+    CleanLogs {
+        /// Only delete log files last modified more than this many days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+    },
+
+    /// Save, list and restore point-in-time copies of the backup exclusions
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCmd,
+    },
+
+    /// Remove backup exclusions set by tmignore, then delete the last run's
+    /// state so `list` and `status` start over
     Reset {
         /// Also remove ALL sticky exclusions on the system, including those set outside tmignore
         #[arg(long)]
         all: bool,
+
+        /// Keep the last run's state file, as a record of what was excluded
+        #[arg(long)]
+        keep_state: bool,
+
+        /// With --all, only remove exclusions under this directory (repeatable),
+        /// found by walking it instead of asking Spotlight
+        #[arg(long = "root", value_name = "PATH", requires = "all")]
+        roots: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PathsCmd {
+    /// Show built-in, configured and last-run scanned paths, grouped by source
+    List {
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+
+    /// Show whether each path is currently excluded from backups
+    Check,
+
+    /// Exclude any of the paths that aren't excluded yet, without scanning
+    Exclude,
+}
+
+#[derive(Subcommand, Debug)]
+enum PatternsCmd {
+    /// List every pattern by category, with what it matches and whether it is
+    /// enabled
+    List,
+
+    /// Show how a pattern matches, whether it is enabled and how large its
+    /// matches typically are
+    Info {
+        /// Pattern name, e.g. node
+        name: String,
+    },
+
+    /// Add a custom pattern to the config
+    Add {
+        /// Name for the pattern, e.g. gen
+        #[arg(long)]
+        name: String,
+
+        /// Directory name to exclude, e.g. generated
+        #[arg(long = "dir")]
+        directory: String,
+
+        /// File or directory that must sit next to it, e.g. codegen.json
+        #[arg(long)]
+        sentinel: String,
+
+        /// What the directory holds, shown by `patterns list`
+        #[arg(long)]
+        description: Option<String>,
+    },
+
+    /// Delete a custom pattern, or disable a built-in pattern or group
+    Remove {
+        /// Pattern name, e.g. node
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotCmd {
+    /// Record every path currently excluded from backups, plus the config file
+    Save {
+        /// Name for the snapshot, e.g. before-upgrade
+        #[arg(long)]
+        name: String,
+
+        /// Find exclusions by walking this directory instead of asking
+        /// Spotlight (repeatable)
+        #[arg(long = "root", value_name = "PATH")]
+        roots: Vec<String>,
+
+        /// Replace an existing snapshot with the same name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Show saved snapshots with when they were taken and how many paths they hold
+    List,
+
+    /// Exclude every path in a snapshot that still exists. Exclusions added
+    /// since are left alone
+    Restore {
+        name: String,
+
+        /// Also put the snapshot's copy of the config file back
+        #[arg(long)]
+        config: bool,
     },
+
+    /// Delete a saved snapshot
+    Delete { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCmd {
+    /// Check the config (with includes) and report every problem with its line.
+    /// Exits 0 if it is fine, 1 if there are only warnings and 2 on errors
+    Validate(ValidateArgs),
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    /// Also print the effective config, with includes (and --profile) merged in
+    #[arg(long)]
+    show_resolved: bool,
+
+    /// Merge the named `[profiles.<name>]` section in before validating
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+/// Kinds of per-path lines `run` prints, picked with `--show`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Shown {
+    /// Newly excluded paths, or with --dry-run those that would be
+    Excluded,
+    /// Paths that were already excluded
+    Already,
+    /// Matches left alone by older_than_days or min_size
+    Skipped,
+    /// Paths that couldn't be checked or excluded
+    Errors,
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Show what would be excluded without making changes
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print detailed output during scanning
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Number of paths to check and exclude in parallel (1-64), overriding
+    /// `exclusion_parallelism` from the config. Higher values may stress the
+    /// filesystem on spinning disks
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=config::MAX_PARALLELISM as i64))]
+    concurrency: Option<u8>,
+
+    /// Skip this directory during the scan, for this run only (repeatable, supports ~ expansion)
+    #[arg(long = "exclude-dir", value_name = "PATH")]
+    exclude_dirs: Vec<String>,
+
+    /// Merge the named `[profiles.<name>]` section into the config for this run
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Scan this directory instead of `scan_roots`, for this run only
+    /// (repeatable, supports ~ expansion). Exclude paths still apply
+    #[arg(long = "root", value_name = "PATH")]
+    roots: Vec<String>,
+
+    /// Only use patterns carrying this tag, for this run only (repeatable)
+    #[arg(long = "include-tag", value_name = "TAG")]
+    include_tags: Vec<String>,
+
+    /// Skip patterns carrying this tag, for this run only (repeatable), as if
+    /// it were in `disable_tags`
+    #[arg(long = "exclude-tag", value_name = "TAG")]
+    exclude_tags: Vec<String>,
+
+    /// Stop scanning after this many seconds and exclude what was found so far,
+    /// overriding `scan_time_budget` from the config
+    #[arg(long, value_name = "SECS")]
+    max_duration: Option<u64>,
+
+    /// Stop at the first error checking or excluding a path, without saving run
+    /// state. Exclusions already applied are kept
+    #[arg(long)]
+    stop_on_error: bool,
+
+    /// Count errors checking or excluding a path without printing them; the
+    /// summary still shows how many there were
+    #[arg(long, conflicts_with = "stop_on_error")]
+    ignore_errors: bool,
+
+    /// Reuse the previous complete scan's results for directories that haven't
+    /// changed since, falling back to a full scan if there is none or the config
+    /// or patterns changed
+    #[arg(long)]
+    incremental: bool,
+
+    /// Print only a one-line total (excluded count, bytes saved, errors) and
+    /// nothing per path, not even errors
+    #[arg(long, conflicts_with = "verbose")]
+    total_only: bool,
+
+    /// Don't list excluded paths, only errors, warnings and the summary.
+    /// --verbose wins if both are given
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Print one JSON object per line to stdout for each match, exclusion (or
+    /// would-be exclusion with --dry-run), skip and error, then a final summary
+    /// object, instead of the usual output.
+    /// Implies --quiet; warnings and errors still go to stderr
+    #[arg(long, conflicts_with_all = ["verbose", "total_only"])]
+    events: bool,
+
+    /// Show the number of files in each excluded directory alongside its size
+    #[arg(long)]
+    count_files: bool,
+
+    /// Don't scan for patterns; only apply the configured exclude paths
+    /// (built-ins and `extra_exclude_paths`). Much faster after editing them
+    #[arg(long, conflicts_with = "incremental")]
+    exclude_paths_only: bool,
+
+    /// Also size matches that are already excluded and report them, for a
+    /// complete picture of the space kept out of backups. Costs extra sizing
+    #[arg(long)]
+    re_check: bool,
+
+    /// Only exclude matches not modified within this long (e.g. `30d`, `2w`),
+    /// overriding `older_than_days` from the config and patterns. Recently
+    /// modified matches are left in backups and listed with --verbose
+    #[arg(long, value_name = "DURATION", value_parser = config::parse_age_days)]
+    older_than: Option<u64>,
+
+    /// Only exclude matches at least this large (e.g. `200MB`), overriding
+    /// `min_size` from the config. Patterns with their own threshold keep it
+    #[arg(long, value_name = "SIZE", value_parser = size::parse_size)]
+    min_size: Option<u64>,
+
+    /// Only check directories Spotlight saw change since this time (ISO 8601,
+    /// e.g. `2024-06-01` or `2024-06-01T09:30:00Z`) instead of walking the scan
+    /// roots. Falls back to a full scan if `mdfind` fails
+    #[arg(
+        long,
+        value_name = "TIMESTAMP",
+        value_parser = config::parse_timestamp,
+        conflicts_with_all = ["incremental", "exclude_paths_only"]
+    )]
+    changed_since: Option<String>,
+
+    /// After the scan, keep running and exclude new dependency directories as
+    /// they are created in the directories it walked, until interrupted
+    #[arg(long, conflicts_with_all = ["exclude_paths_only", "changed_since"])]
+    watch: bool,
+
+    /// After the run, write a report of what was excluded, the size saved,
+    /// matches per pattern and paths already excluded. Markdown for a `.md`
+    /// file, HTML for `.html`
+    #[arg(long, value_name = "PATH", value_parser = report::parse_report_path)]
+    report: Option<PathBuf>,
+
+    /// Only print these kinds of per-path lines, comma-separated (e.g.
+    /// `excluded,errors` to hide already excluded and skipped paths). The
+    /// summary still counts everything
+    #[arg(long, value_name = "KINDS", value_delimiter = ',')]
+    show: Vec<Shown>,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    logging::init();
+    // Commands that check or change exclusions also turn it on from the config
+    excluder::use_tmutil(cli.use_tmutil);
+
+    match cli.command {
+        Cmd::Run(args) => cmd_run(args),
+        Cmd::List { count_files, since } => match since {
+            Some(since) => cmd_list_since(&since, count_files),
+            None => cmd_list(count_files),
+        },
+        Cmd::Add {
+            path,
+            check_size,
+            pattern: _,
+            pattern_name,
+            directory,
+            sentinel,
+            test_pattern,
+        } => match path {
+            Some(path) => cmd_add(&path, check_size),
+            None => cmd_add_pattern(pattern_name, directory, sentinel, test_pattern),
+        },
+        Cmd::Size { path, json } => cmd_size(&path, json),
+        Cmd::Remove { path } => cmd_remove(&path),
+        Cmd::Import {
+            file,
+            apply,
+            dry_run,
+        } => cmd_import(&file, apply, dry_run),
+        Cmd::Export { json } => cmd_export(json),
+        Cmd::Paths { action } => cmd_paths(action),
+        Cmd::Patterns {
+            action: PatternsCmd::List,
+        } => cmd_patterns_list(),
+        Cmd::Patterns {
+            action: PatternsCmd::Info { name },
+        } => cmd_pattern_info(&name),
+        Cmd::Patterns {
+            action: PatternsCmd::Add {
+                name,
+                directory,
+                sentinel,
+                description,
+            },
+        } => cmd_pattern_add(&name, &directory, &sentinel, description),
+        Cmd::Patterns {
+            action: PatternsCmd::Remove { name },
+        } => cmd_pattern_remove(&name),
+        Cmd::Config {
+            action: ConfigCmd::Validate(args),
+        }
+        | Cmd::ValidateConfig(args) => cmd_validate_config(args),
+        Cmd::Status { json } => cmd_status(json),
+        Cmd::Init {
+            overwrite,
+            preset,
+            list_presets,
+        } => cmd_init(overwrite, preset.as_deref(), list_presets),
+        Cmd::Install { force } => service::install(force),
+        Cmd::Uninstall => service::uninstall(),
+        Cmd::Clean => cmd_clean(),
+        Cmd::CleanLogs { older_than } => cmd_clean_logs(older_than),
+        Cmd::Snapshot { action } => cmd_snapshot(action),
+        Cmd::Reset {
+            all,
+            keep_state,
+            roots,
+        } => cmd_reset(all, keep_state, &roots),
+    }
+}
+
+/// Check and change exclusions with tmutil if the config's `use_tmutil` asks for
+/// it. `--use-tmutil` has already turned it on in `main` otherwise.
+fn use_tmutil_from(config: &config::Config) {
+    if config.use_tmutil {
+        excluder::use_tmutil(true);
+    }
+}
+
+/// Set on SIGINT/SIGTERM/SIGHUP while `run` is working. The run stops at the next
+/// directory or match and saves what it has; a second signal exits immediately.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit code for a run stopped by a signal (128 + SIGINT).
+const EXIT_INTERRUPTED: i32 = 130;
+/// Exit statuses of `config validate`.
+const EXIT_CONFIG_WARNINGS: i32 = 1;
+const EXIT_CONFIG_ERRORS: i32 = 2;
+
+fn install_interrupt_handler() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+    });
+    if let Err(e) = result {
+        warn!("could not install signal handler: {}", e);
+    }
+}
+
+fn cmd_run(args: RunArgs) -> Result<()> {
+    let RunArgs {
+        dry_run,
+        verbose,
+        concurrency,
+        exclude_dirs,
+        profile,
+        roots,
+        include_tags,
+        exclude_tags,
+        max_duration,
+        stop_on_error,
+        ignore_errors,
+        incremental,
+        total_only,
+        quiet,
+        events,
+        count_files,
+        exclude_paths_only,
+        re_check,
+        older_than,
+        min_size,
+        changed_since,
+        watch,
+        report,
+        show,
+    } = args;
+    let output = if total_only {
+        Output::TotalOnly
+    } else if verbose {
+        Output::Verbose
+    } else if quiet || events {
+        Output::Quiet
+    } else {
+        Output::Normal
+    };
+
+    install_interrupt_handler();
+
+    let mut config = config::load_config()?;
+    use_tmutil_from(&config);
+    if let Some(name) = &profile {
+        config.apply_profile(name)?;
+        if verbose {
+            info!("Using profile {:?}.", name);
+        }
+    }
+    let roots_overridden = !roots.is_empty();
+    if roots_overridden {
+        config.scan_roots = roots;
+    }
+    check_tags(&config, include_tags.iter().chain(&exclude_tags))?;
+    let patterns_narrowed = !include_tags.is_empty() || !exclude_tags.is_empty();
+    config.disable_tags.extend(exclude_tags);
+    let scan_disabled = !config.scan_enabled;
+    let exclude_paths_only = exclude_paths_only || scan_disabled;
+    if watch && exclude_paths_only {
+        anyhow::bail!("--watch needs a scan, but scan_enabled is false in the config");
+    }
+    // With no patterns the scanner skips the walk and only checks exclude paths
+    let active_patterns = if exclude_paths_only {
+        Vec::new()
+    } else {
+        let mut active = patterns::config_patterns(&config);
+        if !include_tags.is_empty() {
+            active.retain(|p| include_tags.iter().any(|t| p.has_tag(t)));
+        }
+        active
+    };
+    // Patterns only used under a `root_patterns` path get thresholds and counts too
+    let root_only_patterns: Vec<patterns::Pattern> = if active_patterns.is_empty() {
+        Vec::new()
+    } else {
+        let custom = config.root_patterns.values().flat_map(|r| &r.custom_patterns);
+        custom.map(patterns::Pattern::from).collect()
+    };
+    let known_patterns = || active_patterns.iter().chain(&root_only_patterns);
+
+    let parallelism = concurrency.map(usize::from).unwrap_or(config.exclusion_parallelism);
+    if !(1..=config::MAX_PARALLELISM).contains(&parallelism) {
+        anyhow::bail!(
+            "exclusion_parallelism must be between 1 and {}, got {}",
+            config::MAX_PARALLELISM,
+            parallelism
+        );
+    }
+
+    if verbose && scan_disabled {
+        info!("scan_enabled is false; applying exclude paths only.");
+    } else if verbose && exclude_paths_only {
+        info!("Applying exclude paths only; not scanning for patterns.");
+    } else if verbose {
+        info!(
+            "Scanning with {} active patterns across {} root(s)...",
+            active_patterns.len(),
+            config.scan_roots.len()
+        );
+    }
+
+    let mut scan_options = scanner::ScanOptions {
+        extra_skip_paths: exclude_dirs,
+        time_budget: max_duration
+            .or(config.scan_time_budget)
+            .map(std::time::Duration::from_secs),
+        prior: None,
+        cancel: Some(&INTERRUPTED),
+        only_dirs: None,
+        record_walked: watch,
+    };
+    let mut previous_state = state::load_state().ok().flatten();
+    let fingerprint = scanner::fingerprint(&config, &active_patterns, &scan_options);
+    if incremental {
+        let report = |message: &str| {
+            if output >= Output::Normal {
+                info!("{}", message);
+            }
+        };
+        let cache = previous_state.as_mut().and_then(|s| s.scan_cache.take());
+        match cache {
+            Some(cache) if cache.fingerprint == fingerprint => {
+                scan_options.prior = Some(prior_scan(cache));
+            }
+            Some(_) => report("Config or patterns changed since the last scan; doing a full scan."),
+            None => report("No complete previous scan to build on; doing a full scan."),
+        }
+    }
+    if let Some(since) = &changed_since
+        && !exclude_paths_only
+    {
+        match changed_dirs_since(&config, since, verbose) {
+            Ok(dirs) => scan_options.only_dirs = Some(dirs),
+            Err(e) if output >= Output::Quiet => warn!("{:#}; doing a full scan.", e),
+            Err(_) => {}
+        }
+    }
+    let spotlight_scan = scan_options.only_dirs.is_some();
+    let global_min_size = min_size.or(config.min_size.map(|s| s.0));
+    let rules_by_pattern: HashMap<&str, MatchRules> = known_patterns()
+        .map(|p| {
+            let rules = MatchRules {
+                // An explicit override may be 0 to opt a pattern out of the global threshold
+                min_size: config
+                    .pattern_min_size
+                    .get(&p.name)
+                    .map(|s| s.0)
+                    .or((p.min_size > 0).then_some(p.min_size))
+                    .or(global_min_size)
+                    .unwrap_or(0),
+                older_than_days: older_than.or(p.older_than_days).or(config.older_than_days),
+            };
+            (p.name.as_str(), rules)
+        })
+        .collect();
+    // Exclude paths have no pattern entry, so they get no thresholds
+    let rules_for = |m: &scanner::ScanMatch| {
+        rules_by_pattern
+            .get(m.pattern_name.as_str())
+            .copied()
+            .unwrap_or_default()
+    };
+
+    let sizes = size::SizeCache::new(
+        previous_state
+            .as_mut()
+            .map(|s| std::mem::take(&mut s.size_cache))
+            .unwrap_or_default(),
+    );
+    let previous_entries = match previous_state.as_mut() {
+        Some(s) if config.accumulate_state => std::mem::take(&mut s.entries),
+        _ => Vec::new(),
+    };
+    let previous_complete_run = previous_state.and_then(|s| {
+        s.last_complete_run
+            .or_else(|| (!s.partial).then_some(s.last_run))
+    });
+
+    if verbose && parallelism > 1 {
+        info!("Processing with {} parallel workers.", parallelism);
+    }
+
+    // Matches are checked and excluded as the scan finds them
+    let scan_started = std::time::SystemTime::now();
+    let mut tally = RunTally {
+        count_files,
+        events,
+        ignore_errors,
+        show: show.clone(),
+        already_excluded_bytes: re_check.then_some(0),
+        pattern_counts: known_patterns()
+            .map(|p| p.name.clone())
+            .chain(std::iter::once("exclude_path".to_string()))
+            .chain(
+                (config.use_gitignore && !active_patterns.is_empty())
+                    .then(|| scanner::GITIGNORE_PATTERN.to_string()),
+            )
+            .map(|name| (name, 0))
+            .collect(),
+        size_hints: if dry_run && verbose {
+            known_patterns()
+                .filter_map(|p| Some((p.name.clone(), p.expected_size_hint.clone()?)))
+                .collect()
+        } else {
+            HashMap::new()
+        },
+        descriptions: if verbose {
+            known_patterns()
+                .filter_map(|p| {
+                    let description = p.description.clone()?;
+                    Some((p.name.clone(), (p.category().to_string(), description)))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        },
+        ..RunTally::default()
+    };
+    let mut checkpoint = Checkpoint {
+        // --stop-on-error promises no state at all for a run that fails
+        enabled: !dry_run && !stop_on_error,
+        profile: &profile,
+        last_complete_run: &previous_complete_run,
+        accumulate: config.accumulate_state.then_some(previous_entries.as_slice()),
+        last_saved: Instant::now(),
+        saved_entries: 0,
+    };
+    let mut handle = |m: &scanner::ScanMatch, outcome: MatchOutcome| {
+        let failed = outcome.is_error();
+        tally.record(m, outcome, output);
+        checkpoint.maybe_save(&tally);
+        if (stop_on_error && failed) || INTERRUPTED.load(Ordering::Relaxed) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    };
+    let scanner::ScanSummary { warnings, mut stats } = if parallelism == 1 {
+        scanner::scan_streaming(&config, &active_patterns, &scan_options, |m| {
+            let outcome = process_match(&m, dry_run, re_check, rules_for(&m), &sizes);
+            handle(&m, outcome)
+        })
+    } else {
+        let process =
+            |m: &scanner::ScanMatch| process_match(m, dry_run, re_check, rules_for(m), &sizes);
+        scan_and_process_parallel(
+            &config,
+            &active_patterns,
+            &scan_options,
+            parallelism,
+            &process,
+            &mut handle,
+        )
+    };
+
+    // Errors after a signal are likely tmutil being interrupted too; the run
+    // is reported as interrupted instead
+    let interrupted = INTERRUPTED.load(Ordering::Relaxed);
+
+    // Workers may have finished matches after the first error; those are
+    // recorded too, so the warning counts every exclusion that was applied
+    if stop_on_error && tally.error_count > 0 && !interrupted {
+        return Err(stopped_on_error(&tally, dry_run));
+    }
+
+    if stats.truncated && output >= Output::Quiet {
+        warn!(
+            "scan time budget reached after {:.0}s; results are partial.",
+            stats.elapsed.as_secs_f64()
+        );
+    }
+
+    let hidden_denied = if output >= Output::Quiet {
+        report_scan_warnings(&warnings, verbose)
+    } else {
+        0
+    };
+
+    if verbose {
+        // Exact duplicates are reported as warnings
+        for (nested, covering) in stats.nested_roots.iter().filter(|(n, c)| n != c) {
+            info!(
+                "Skipped scan root {} (already covered by {})",
+                contract_tilde(&nested.to_string_lossy()),
+                contract_tilde(&covering.to_string_lossy())
+            );
+        }
+        info!(
+            "Scanned {} directories in {:.1}s.",
+            stats.dirs_visited,
+            stats.elapsed.as_secs_f64()
+        );
+        if stats.reused > 0 {
+            info!("Reused {} results from the previous scan (--incremental).", stats.reused);
+        }
+        if stats.hidden_pruned > 0 {
+            info!("Skipped {} hidden directories (skip_hidden).", stats.hidden_pruned);
+        }
+        info!("Found {} candidate directories.", tally.matches.len());
+    }
+
+    // Print summary
+    if events {
+        tally.emit_done();
+    }
+    if interrupted {
+        warn!(
+            "Interrupted: {} {} before shutdown",
+            tally.newly_excluded.len(),
+            if dry_run { "would have been excluded" } else { "excluded" }
+        );
+    } else if output == Output::TotalOnly {
+        info!(
+            "{}{} newly excluded ({}), {} already excluded{}, {} errors{}",
+            if dry_run { "Dry run: " } else { "" },
+            tally.newly_excluded.len(),
+            size::format_size(tally.newly_excluded_bytes()),
+            tally.already_excluded_count,
+            tally
+                .already_excluded_bytes
+                .map_or(String::new(), |b| format!(" ({})", size::format_size(b))),
+            tally.error_count,
+            if stats.truncated { " (partial scan)" } else { "" }
+        );
+    } else if !events {
+        print_summary(&tally, dry_run, verbose, stats.truncated, hidden_denied);
+    }
+    if tally.access_denied_count > 0 && !ignore_errors {
+        warn!(
+            "tmutil was denied access to {} {}{}. {}",
+            tally.access_denied_count,
+            if tally.access_denied_count == 1 { "path" } else { "paths" },
+            if verbose { "" } else { " (run with --verbose to list)" },
+            excluder::FULL_DISK_ACCESS_HINT
+        );
+    }
+
+    if config.notify_on_run && !dry_run && !interrupted {
+        let message = format!(
+            "{} newly excluded ({}), {} already excluded, {} errors",
+            tally.newly_excluded.len(),
+            size::format_size(tally.newly_excluded_bytes()),
+            tally.already_excluded_count,
+            tally.error_count
+        );
+        if let Err(e) = notify::send("tmignore", &message) {
+            warn!("could not send notification: {}", e);
+        }
+    }
+
+    if let Some(report_path) = report.as_deref().filter(|_| !interrupted) {
+        let run_report = report::RunReport {
+            run_at: &logging::timestamp(),
+            dry_run,
+            partial: stats.truncated,
+            pattern_counts: &tally.pattern_counts,
+            excluded: &tally.newly_excluded,
+            bytes_saved: tally.newly_excluded_bytes(),
+            already_excluded: &tally.already_excluded,
+            errors: tally.error_count,
+        };
+        match report::write(report_path, &run_report) {
+            Ok(()) if verbose => info!("Wrote report to {}.", report_path.display()),
+            Ok(()) => {}
+            Err(e) => warn!("could not write report: {:#}", e),
+        }
+    }
+
+    let write_metrics = !dry_run && !interrupted;
+    if let Some(metrics_path) = config.metrics_path.as_deref().filter(|_| write_metrics) {
+        let run_metrics = metrics::RunMetrics {
+            excluded_total: tally.newly_excluded.len(),
+            already_excluded_total: tally.already_excluded_count,
+            errors_total: tally.error_count,
+            bytes_saved: tally.newly_excluded_bytes(),
+            last_run_timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        };
+        if let Err(e) = expand_tilde(metrics_path).and_then(|p| metrics::write(&p, &run_metrics)) {
+            warn!("could not write metrics: {:#}", e);
+        }
+    }
+
+    let watch_from: Vec<PathBuf> = if watch && !interrupted {
+        tally.matches.iter().map(|(path, _)| path.clone()).collect()
+    } else {
+        Vec::new()
+    };
+    let watch_dirs = std::mem::take(&mut stats.walked);
+
+    // Save state (even for dry-run, to record what was found)
+    if !dry_run {
+        let now = logging::timestamp();
+        // Only a full pattern scan of the configured roots counts as complete or
+        // is worth reusing
+        let scanned_everything = !stats.truncated
+            && !interrupted
+            && !exclude_paths_only
+            && !roots_overridden
+            && !patterns_narrowed
+            && !spotlight_scan;
+        let last_complete_run = if scanned_everything {
+            Some(now.clone())
+        } else {
+            previous_complete_run
+        };
+        let record = state::HistoryRecord {
+            run_at: now.clone(),
+            entries: tally.newly_excluded.clone(),
+        };
+        if let Err(e) = state::append_history(&record) {
+            warn!("could not record run history: {:#}", e);
+        }
+        let matched = tally.matched_paths();
+        let mut run_state = RunState {
+            state_version: state::STATE_VERSION,
+            last_run: now,
+            excluded_count: tally.newly_excluded.len(),
+            total_size_bytes: tally.newly_excluded_bytes(),
+            already_excluded_count: tally.already_excluded_count,
+            already_excluded_bytes: tally.already_excluded_bytes,
+            entries: tally.newly_excluded,
+            pattern_counts: tally.pattern_counts,
+            warning_count: warnings.len(),
+            warning_kinds: scanner::count_by_kind(&warnings),
+            warnings: warnings
+                .iter()
+                .take(state::MAX_SAVED_WARNINGS)
+                .map(|w| WarningEntry {
+                    path: contract_tilde(&w.path.to_string_lossy()),
+                    kind: w.kind.as_str().to_string(),
+                    message: w.message.clone(),
+                })
+                .collect(),
+            partial: stats.truncated || interrupted,
+            last_complete_run,
+            profile,
+            scan_cache: scanned_everything.then(|| state::ScanCache {
+                fingerprint,
+                scanned_at: scan_started
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+                matches: tally
+                    .matches
+                    .iter()
+                    .filter(|(_, pattern)| pattern != "exclude_path")
+                    .map(|(path, pattern)| state::CachedMatch {
+                        path: path.clone(),
+                        pattern: pattern.clone(),
+                        excluded: tally.excluded_paths.contains(path),
+                    })
+                    .collect(),
+            }),
+            size_cache: sizes.into_entries(),
+        };
+        if config.accumulate_state {
+            state::accumulate(&mut run_state, &previous_entries, matched.iter().map(String::as_str));
+        }
+        state::save_state(&run_state)?;
+    }
+
+    if interrupted {
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+    if watch {
+        let sizes = size::SizeCache::new(Default::default());
+        let process =
+            |m: &scanner::ScanMatch| process_match(m, dry_run, re_check, rules_for(m), &sizes);
+        let matcher =
+            scanner::DirectoryMatcher::new(&config, &active_patterns, &scan_options, watch_from);
+        if stats.truncated && output >= Output::Quiet {
+            warn!("only the directories the scan reached before its time budget are watched.");
+        }
+        // A fresh tally, so the session's summary only counts what it found
+        let tally = RunTally {
+            count_files,
+            events,
+            ignore_errors,
+            show,
+            ..RunTally::default()
+        };
+        watch_for_new_matches(matcher, &watch_dirs, &process, output, tally, !dry_run)?;
+    }
+    Ok(())
+}
+
+/// Reject `--include-tag`/`--exclude-tag` values no pattern carries, which are
+/// most likely typos.
+fn check_tags<'a>(config: &config::Config, tags: impl Iterator<Item = &'a String>) -> Result<()> {
+    let patterns: Vec<patterns::Pattern> = patterns::builtin_patterns()
+        .into_iter()
+        .chain(config.custom_patterns.iter().map(patterns::Pattern::from))
+        .collect();
+    for tag in tags {
+        if !patterns.iter().any(|p| p.has_tag(tag)) {
+            anyhow::bail!("No pattern is tagged {:?}", tag);
+        }
+    }
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// `run --watch`: exclude matching directories as they are created in `dirs`
+/// (the directories the scan walked) or in new directories the walk would
+/// have entered, until interrupted, then add what was excluded to the saved
+/// state. Outcomes are recorded in `tally`; with `--events` the session ends
+/// with its own `done` event instead of a summary line.
+fn watch_for_new_matches(
+    mut matcher: scanner::DirectoryMatcher,
+    dirs: &[PathBuf],
+    process: &dyn Fn(&scanner::ScanMatch) -> MatchOutcome,
+    output: Output,
+    mut tally: RunTally,
+    save: bool,
+) -> Result<()> {
+    let roots = matcher.roots().to_vec();
+    if roots.is_empty() || dirs.is_empty() {
+        anyhow::bail!("None of the scan roots exist, so there is nothing to watch");
+    }
+    watch::check_capacity(dirs.len())?;
+    if !tally.events {
+        info!("");
+        info!(
+            "[watching] {} directories under {} for new directories (Ctrl-C to stop)",
+            size::format_count(dirs.len() as u64),
+            roots
+                .iter()
+                .map(|r| contract_tilde(&r.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    watch::watch_directories(dirs, &INTERRUPTED, |path| {
+        if let Some(m) = matcher.check(path) {
+            let outcome = process(&m);
+            tally.record(&m, outcome, output);
+        }
+        matcher.walks(path)
+    })?;
+
+    if tally.events {
+        tally.emit_done();
+    } else {
+        info!(
+            "Stopped watching: {} newly excluded, {} errors",
+            tally.newly_excluded.len(),
+            tally.error_count
+        );
+    }
+    if save
+        && !tally.newly_excluded.is_empty()
+        && let Some(mut run_state) = state::load_state()?
+    {
+        let record = state::HistoryRecord {
+            run_at: logging::timestamp(),
+            entries: tally.newly_excluded.clone(),
+        };
+        if let Err(e) = state::append_history(&record) {
+            warn!("could not record run history: {:#}", e);
+        }
+        run_state.excluded_count += tally.newly_excluded.len();
+        run_state.total_size_bytes += tally.newly_excluded_bytes();
+        run_state.entries.extend(tally.newly_excluded);
+        state::save_state(&run_state)?;
+    }
+    Ok(())
+}
+
+/// Print the end-of-run summary: totals plus counts of anything skipped.
+fn print_summary(
+    tally: &RunTally,
+    dry_run: bool,
+    verbose: bool,
+    truncated: bool,
+    hidden_denied: usize,
+) {
+    info!("");
+    if dry_run {
+        info!("Dry run complete.");
+    }
+    match tally.already_excluded_bytes {
+        Some(already_bytes) => {
+            let newly_bytes = tally.newly_excluded_bytes();
+            info!(
+                "  {} newly excluded ({}), {} already excluded ({}), {} errors{}",
+                tally.newly_excluded.len(),
+                size::format_size(newly_bytes),
+                tally.already_excluded_count,
+                size::format_size(already_bytes),
+                tally.error_count,
+                if truncated { " (partial scan)" } else { "" }
+            );
+            info!(
+                "  {} kept out of backups in total",
+                size::format_size(newly_bytes + already_bytes)
+            );
+        }
+        None => info!(
+            "  {} newly excluded, {} already excluded, {} errors{}",
+            tally.newly_excluded.len(),
+            tally.already_excluded_count,
+            tally.error_count,
+            if truncated { " (partial scan)" } else { "" }
+        ),
+    }
+    if verbose {
+        info!("  Matches per pattern: {}", format_pattern_counts(&tally.pattern_counts));
+        let mut matched: Vec<(&String, &u64)> =
+            tally.pattern_counts.iter().filter(|(_, n)| **n > 0).collect();
+        matched.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        for (name, _) in matched {
+            if let Some((category, description)) = tally.descriptions.get(name) {
+                info!("    {} ({}): {}", name, category, description);
+            }
+        }
+    }
+    if tally.too_recent_count > 0 && !verbose {
+        info!(
+            "  {} modified too recently (run with --verbose to list)",
+            tally.too_recent_count
+        );
+    }
+    if tally.below_min_size_count > 0 && !verbose {
+        info!(
+            "  {} below min_size (run with --verbose to list)",
+            tally.below_min_size_count
+        );
+    }
+    if hidden_denied > 0 {
+        info!(
+            "  skipped {} directories due to permission errors (run with --verbose to list)",
+            hidden_denied
+        );
+    }
+}
+
+/// Pattern match counts, most matches first, e.g. "node: 142, cargo: 37, my-build: 0".
+fn format_pattern_counts(counts: &BTreeMap<String, u64>) -> String {
+    let mut sorted: Vec<(&String, &u64)> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    sorted
+        .iter()
+        .map(|(name, count)| format!("{}: {}", name, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Print scan warnings. Permission errors are routine when scanning `~` (folders
+/// protected by macOS privacy controls), so they are only listed when `verbose`;
+/// returns how many were left out.
+fn report_scan_warnings(warnings: &[scanner::ScanWarning], verbose: bool) -> usize {
+    let mut hidden = 0;
+    for w in warnings {
+        if w.kind == scanner::ScanWarningKind::PermissionDenied && !verbose {
+            hidden += 1;
+        } else {
+            warn!("{}", w);
+        }
+    }
+    hidden
+}
+
+/// Turn a saved scan cache into results the scanner can reuse.
+fn prior_scan(cache: state::ScanCache) -> scanner::PriorScan {
+    scanner::PriorScan {
+        scanned_at: std::time::UNIX_EPOCH + std::time::Duration::from_secs(cache.scanned_at),
+        matches: cache
+            .matches
+            .into_iter()
+            .map(|m| {
+                let prior = scanner::PriorMatch {
+                    pattern_name: m.pattern,
+                    excluded: m.excluded,
+                };
+                (m.path, prior)
+            })
+            .collect(),
+    }
+}
+
+/// Build the error `run --stop-on-error` returns, warning first that exclusions
+/// already applied stay in place. State is not saved for a stopped run.
+fn stopped_on_error(tally: &RunTally, dry_run: bool) -> anyhow::Error {
+    if !dry_run && !tally.newly_excluded.is_empty() {
+        warn!(
+            "{} path(s) excluded before the error remain excluded (not rolled back).",
+            tally.newly_excluded.len()
+        );
+    }
+    anyhow::anyhow!("Stopped at the first error (--stop-on-error); run state was not saved")
+}
+
+/// Per-pattern conditions a match must meet before it is excluded.
+#[derive(Debug, Default, Clone, Copy)]
+struct MatchRules {
+    min_size: u64,
+    older_than_days: Option<u64>,
+}
+
+/// Result of checking (and, outside dry-run, excluding) a single scan match.
+enum MatchOutcome {
+    /// Sized only with `--re-check`.
+    AlreadyExcluded { usage: Option<size::Usage> },
+    TooRecent { age_days: u64, older_than_days: u64 },
+    BelowMinSize { size: u64, min_size: u64 },
+    Excluded { usage: size::Usage },
+    WouldExclude { usage: size::Usage },
+    ExcludeFailed(anyhow::Error),
+    CheckFailed(anyhow::Error),
+}
+
+impl MatchOutcome {
+    fn is_error(&self) -> bool {
+        matches!(self, MatchOutcome::ExcludeFailed(_) | MatchOutcome::CheckFailed(_))
+    }
+}
+
+/// Check a match and exclude it if needed. Does not print anything, so it is safe
+/// to call from worker threads.
+fn process_match(
+    m: &scanner::ScanMatch,
+    dry_run: bool,
+    re_check: bool,
+    rules: MatchRules,
+    sizes: &size::SizeCache,
+) -> MatchOutcome {
+    let age_days = || {
+        scanner::last_modified(&m.path)
+            .and_then(|t| t.elapsed().ok())
+            .map_or(0, |d| d.as_secs() / 86400)
+    };
+
+    let already_excluded = || MatchOutcome::AlreadyExcluded {
+        usage: re_check.then(|| sizes.usage(&m.path)),
+    };
+
+    if m.known_excluded {
+        return already_excluded();
+    }
+
+    match excluder::is_excluded(&m.path) {
+        Ok(true) => already_excluded(),
+        Ok(false) if rules.older_than_days.is_some_and(|days| age_days() < days) => {
+            MatchOutcome::TooRecent {
+                age_days: age_days(),
+                older_than_days: rules.older_than_days.unwrap_or_default(),
+            }
+        }
+        Ok(false) if rules.min_size > 0 && sizes.size(&m.path) < rules.min_size => {
+            MatchOutcome::BelowMinSize {
+                size: sizes.size(&m.path),
+                min_size: rules.min_size,
+            }
+        }
+        Ok(false) if dry_run => MatchOutcome::WouldExclude {
+            usage: sizes.usage(&m.path),
+        },
+        Ok(false) => match excluder::add_exclusion(&m.path) {
+            Ok(()) => MatchOutcome::Excluded {
+                usage: sizes.usage(&m.path),
+            },
+            Err(e) => MatchOutcome::ExcludeFailed(e),
+        },
+        Err(e) => MatchOutcome::CheckFailed(e),
+    }
+}
+
+/// Scan while a pool of worker threads checks and excludes matches. The scan runs
+/// on this thread and queues each match for the workers; their outcomes are handed
+/// to `handle` back on this thread as they arrive. Once `handle` breaks, no new
+/// matches are started and the scan stops.
+fn scan_and_process_parallel(
+    config: &config::Config,
+    patterns: &[patterns::Pattern],
+    options: &scanner::ScanOptions,
+    workers: usize,
+    process: &(dyn Fn(&scanner::ScanMatch) -> MatchOutcome + Sync),
+    handle: &mut dyn FnMut(&scanner::ScanMatch, MatchOutcome) -> ControlFlow<()>,
+) -> scanner::ScanSummary {
+    let stop = AtomicBool::new(false);
+    let (match_tx, match_rx) = mpsc::channel::<scanner::ScanMatch>();
+    let match_rx = Mutex::new(match_rx);
+    let (outcome_tx, outcome_rx) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let (match_rx, stop, outcome_tx) = (&match_rx, &stop, outcome_tx.clone());
+            scope.spawn(move || {
+                loop {
+                    let next = match_rx.lock().expect("match queue lock poisoned").recv();
+                    let Ok(m) = next else { break };
+                    // After a stop, drain the queue without starting anything new
+                    if stop.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let outcome = process(&m);
+                    if outcome_tx.send((m, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(outcome_tx);
+
+        let mut deliver = |(m, outcome): (scanner::ScanMatch, MatchOutcome)| {
+            if handle(&m, outcome).is_break() {
+                stop.store(true, Ordering::Relaxed);
+            }
+        };
+        let summary = scanner::scan_streaming(config, patterns, options, |m| {
+            while let Ok(done) = outcome_rx.try_recv() {
+                deliver(done);
+            }
+            if stop.load(Ordering::Relaxed) {
+                return ControlFlow::Break(());
+            }
+            // Workers only hang up after the queue closes, so this can't fail
+            let _ = match_tx.send(m);
+            ControlFlow::Continue(())
+        });
+        drop(match_tx);
+        for done in outcome_rx {
+            deliver(done);
+        }
+        summary
+    })
+}
+
+/// Saves partial run state every so often while matches are processed, so a run
+/// that is killed part-way still leaves a valid state file behind.
+struct Checkpoint<'a> {
+    enabled: bool,
+    profile: &'a Option<String>,
+    last_complete_run: &'a Option<String>,
+    /// Earlier runs' entries to keep, with `accumulate_state`.
+    accumulate: Option<&'a [ExcludedEntry]>,
+    last_saved: Instant,
+    saved_entries: usize,
+}
+
+impl Checkpoint<'_> {
+    const INTERVAL: Duration = Duration::from_secs(2);
+
+    fn maybe_save(&mut self, tally: &RunTally) {
+        if !self.enabled
+            || tally.newly_excluded.len() == self.saved_entries
+            || self.last_saved.elapsed() < Self::INTERVAL
+        {
+            return;
+        }
+
+        let mut run_state = RunState {
+            state_version: state::STATE_VERSION,
+            last_run: logging::timestamp(),
+            excluded_count: tally.newly_excluded.len(),
+            total_size_bytes: tally.newly_excluded_bytes(),
+            already_excluded_count: tally.already_excluded_count,
+            already_excluded_bytes: tally.already_excluded_bytes,
+            entries: tally.newly_excluded.clone(),
+            pattern_counts: tally.pattern_counts.clone(),
+            warning_count: 0,
+            warning_kinds: Default::default(),
+            warnings: Vec::new(),
+            partial: true,
+            last_complete_run: self.last_complete_run.clone(),
+            profile: self.profile.clone(),
+            scan_cache: None,
+            size_cache: Default::default(),
+        };
+        if let Some(previous) = self.accumulate {
+            let matched = tally.matched_paths();
+            state::accumulate(&mut run_state, previous, matched.iter().map(String::as_str));
+        }
+        if let Err(e) = state::save_state(&run_state) {
+            warn!("could not save progress: {:#}", e);
+        }
+        self.last_saved = Instant::now();
+        self.saved_entries = tally.newly_excluded.len();
+    }
+}
+
+/// How much `run` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Output {
+    /// Only a one-line total.
+    TotalOnly,
+    /// Errors, warnings and the summary, but no excluded paths.
+    Quiet,
+    /// Excluded paths, errors and a summary.
+    Normal,
+    /// Also skipped paths and scan details.
+    Verbose,
+}
+
+/// Running counters for `cmd_run`, updated as each match outcome is printed.
+#[derive(Default)]
+struct RunTally {
+    newly_excluded: Vec<ExcludedEntry>,
+    /// Every match left excluded by this run, newly or already.
+    excluded_paths: HashSet<PathBuf>,
+    /// Path and pattern name of every match recorded.
+    matches: Vec<(PathBuf, String)>,
+    /// Matches per pattern name, seeded with zero for every active pattern.
+    pattern_counts: BTreeMap<String, u64>,
+    already_excluded_count: usize,
+    /// Display paths of matches that were already excluded.
+    already_excluded: Vec<String>,
+    /// Total size of already excluded matches, with `--re-check`.
+    already_excluded_bytes: Option<u64>,
+    too_recent_count: usize,
+    below_min_size_count: usize,
+    error_count: usize,
+    /// Errors from tmutil being refused access, also counted in `error_count`.
+    access_denied_count: usize,
+    /// Print file counts next to sizes (`--count-files`).
+    count_files: bool,
+    /// Also print each outcome as a JSON line on stdout (`--events`).
+    events: bool,
+    /// Count errors without printing them (`--ignore-errors`).
+    ignore_errors: bool,
+    /// Kinds of per-path lines to print (`--show`); empty for all of them.
+    show: Vec<Shown>,
+    /// Expected size per pattern name, shown next to dry-run matches with `--verbose`.
+    size_hints: HashMap<String, String>,
+    /// Category and description per pattern name, for the `--verbose` summary.
+    descriptions: HashMap<String, (String, String)>,
+}
+
+impl RunTally {
+    fn shows(&self, kind: Shown) -> bool {
+        self.show.is_empty() || self.show.contains(&kind)
+    }
+
+    /// Total size of newly excluded entries in bytes.
+    fn newly_excluded_bytes(&self) -> u64 {
+        self.newly_excluded.iter().map(|e| e.size).sum()
+    }
+
+    /// Display paths of every match recorded, as they appear in state entries.
+    fn matched_paths(&self) -> Vec<String> {
+        self.matches
+            .iter()
+            .map(|(path, _)| contract_tilde(&path.to_string_lossy()))
+            .collect()
+    }
+
+    /// Print one `--events` line. Paths in events are absolute, not `~`-relative.
+    fn emit(&self, event: serde_json::Value) {
+        if self.events {
+            output!("{}", event);
+        }
+    }
+
+    fn emit_done(&self) {
+        self.emit(self.done_event());
+    }
+
+    /// The `--events` line closing a run (or a watch session) with its totals.
+    fn done_event(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "done",
+            "newly_excluded": self.newly_excluded.len(),
+            "already_excluded": self.already_excluded_count,
+            "errors": self.error_count,
+        })
+    }
+
+    /// The `--events` line announcing a match, ahead of its outcome.
+    fn match_event(m: &scanner::ScanMatch) -> serde_json::Value {
+        serde_json::json!({
+            "type": "match",
+            "path": m.path.to_string_lossy(),
+            "pattern": m.pattern_name,
+            "also_matched": m.also_matched,
+        })
+    }
+
+    /// The `--events` line for a match's outcome.
+    fn outcome_event(path: &str, outcome: &MatchOutcome) -> serde_json::Value {
+        use serde_json::json;
+        let skip = |reason: &str| json!({ "type": "skip", "path": path, "reason": reason });
+        match outcome {
+            MatchOutcome::AlreadyExcluded { .. } => skip("already_excluded"),
+            MatchOutcome::TooRecent { .. } => skip("too_recent"),
+            MatchOutcome::BelowMinSize { .. } => skip("below_min_size"),
+            MatchOutcome::Excluded { usage } => {
+                json!({ "type": "excluded", "path": path, "size": usage.bytes })
+            }
+            MatchOutcome::WouldExclude { usage } => {
+                json!({ "type": "would_exclude", "path": path, "size": usage.bytes })
+            }
+            MatchOutcome::ExcludeFailed(e) | MatchOutcome::CheckFailed(e) => {
+                json!({ "type": "error", "path": path, "message": format!("{:#}", e) })
+            }
+        }
+    }
+
+    /// Whether `record` prints a line for `outcome` at this output level, given
+    /// `--show` and `--ignore-errors`. Outcomes are counted either way.
+    fn prints(&self, outcome: &MatchOutcome, output: Output) -> bool {
+        let verbose = output == Output::Verbose;
+        let normal = output >= Output::Normal;
+        match outcome {
+            // Sized ones (with --re-check) are listed like exclusions
+            MatchOutcome::AlreadyExcluded { usage } => {
+                (if usage.is_some() { normal } else { verbose }) && self.shows(Shown::Already)
+            }
+            MatchOutcome::TooRecent { .. } | MatchOutcome::BelowMinSize { .. } => {
+                verbose && self.shows(Shown::Skipped)
+            }
+            MatchOutcome::Excluded { .. } | MatchOutcome::WouldExclude { .. } => {
+                normal && self.shows(Shown::Excluded)
+            }
+            // Access denied errors tend to hit every path at once; they are
+            // summed up with one hint at the end instead
+            MatchOutcome::ExcludeFailed(e) | MatchOutcome::CheckFailed(e) => {
+                output >= Output::Quiet
+                    && self.shows(Shown::Errors)
+                    && !self.ignore_errors
+                    && (verbose || !excluder::is_access_denied(e))
+            }
+        }
+    }
+
+    fn record(&mut self, m: &scanner::ScanMatch, outcome: MatchOutcome, output: Output) {
+        self.matches.push((m.path.clone(), m.pattern_name.clone()));
+        for name in std::iter::once(&m.pattern_name).chain(&m.also_matched) {
+            *self.pattern_counts.entry(name.clone()).or_default() += 1;
+        }
+        if self.events {
+            self.emit(Self::match_event(m));
+            self.emit(Self::outcome_event(&m.path.to_string_lossy(), &outcome));
+        }
+        let display_path = contract_tilde(&m.path.to_string_lossy());
+        let print = self.prints(&outcome, output);
+
+        match outcome {
+            MatchOutcome::AlreadyExcluded { usage: Some(usage) } => {
+                self.already_excluded_count += 1;
+                self.excluded_paths.insert(m.path.clone());
+                *self.already_excluded_bytes.get_or_insert(0) += usage.bytes;
+                let entry = excluded_entry(display_path, m, usage);
+                if print {
+                    info!(
+                        "  [already] {} ({})",
+                        entry.path,
+                        entry_details(&entry, self.count_files)
+                    );
+                }
+                self.already_excluded.push(entry.path);
+            }
+            MatchOutcome::AlreadyExcluded { usage: None } => {
+                self.already_excluded_count += 1;
+                self.excluded_paths.insert(m.path.clone());
+                if print {
+                    info!("  [skip] {} (already excluded)", display_path);
+                }
+                self.already_excluded.push(display_path);
+            }
+            MatchOutcome::TooRecent {
+                age_days,
+                older_than_days,
+            } => {
+                self.too_recent_count += 1;
+                if print {
+                    info!(
+                        "  [skip] {} (modified {} days ago, older_than_days {})",
+                        display_path, age_days, older_than_days
+                    );
+                }
+            }
+            MatchOutcome::BelowMinSize { size, min_size } => {
+                self.below_min_size_count += 1;
+                if print {
+                    info!(
+                        "  [skip] {} (below min_size {}: {})",
+                        display_path,
+                        size::format_size(min_size),
+                        size::format_size(size)
+                    );
+                }
+            }
+            MatchOutcome::WouldExclude { usage } => {
+                let entry = excluded_entry(display_path, m, usage);
+                let verbose = output == Output::Verbose;
+                let hint = self.size_hints.get(&m.pattern_name).filter(|_| verbose && print);
+                if let Some(hint) = hint {
+                    info!(
+                        "  [dry-run] {} ({}; expected {})",
+                        entry.path,
+                        entry_details(&entry, self.count_files),
+                        hint
+                    );
+                } else if print {
+                    info!(
+                        "  [dry-run] {} ({})",
+                        entry.path,
+                        entry_details(&entry, self.count_files)
+                    );
+                }
+                self.newly_excluded.push(entry);
+            }
+            MatchOutcome::Excluded { usage } => {
+                self.excluded_paths.insert(m.path.clone());
+                let entry = excluded_entry(display_path, m, usage);
+                if print {
+                    info!(
+                        "  [excluded] {} ({})",
+                        entry.path,
+                        entry_details(&entry, self.count_files)
+                    );
+                }
+                self.newly_excluded.push(entry);
+            }
+            MatchOutcome::ExcludeFailed(e) | MatchOutcome::CheckFailed(e)
+                if excluder::is_access_denied(&e) =>
+            {
+                if print {
+                    error!("  [error] {}", e);
+                }
+                self.access_denied_count += 1;
+                self.error_count += 1;
+            }
+            MatchOutcome::ExcludeFailed(e) => {
+                if print {
+                    error!("  [error] {}: {}", display_path, e);
+                }
+                self.error_count += 1;
+            }
+            MatchOutcome::CheckFailed(e) => {
+                if print {
+                    error!("  [error] checking {}: {}", display_path, e);
+                }
+                self.error_count += 1;
+            }
+        }
+    }
+}
+
+fn excluded_entry(path: String, m: &scanner::ScanMatch, usage: size::Usage) -> ExcludedEntry {
+    ExcludedEntry {
+        path,
+        pattern: m.pattern_name.clone(),
+        also_matched: m.also_matched.clone(),
+        size: usage.bytes,
+        files: Some(usage.files),
+    }
+}
+
+/// What's shown in parentheses after an excluded path, e.g. "node, 1.2GB",
+/// "composer + bundler, 48MB" when several patterns matched or, with
+/// `count_files`, "node, 1.2GB, 42,103 files".
+fn entry_details(entry: &ExcludedEntry, count_files: bool) -> String {
+    let patterns = std::iter::once(&entry.pattern)
+        .chain(&entry.also_matched)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" + ");
+    match entry.files.filter(|_| count_files) {
+        Some(files) => format!(
+            "{}, {}, {} files",
+            patterns,
+            size::format_size(entry.size),
+            size::format_count(files)
+        ),
+        None => format!("{}, {}", patterns, size::format_size(entry.size)),
+    }
+}
+
+fn cmd_list(count_files: bool) -> Result<()> {
+    match state::load_state()? {
+        Some(run_state) => {
+            if run_state.entries.is_empty() {
+                output!("No paths were excluded in the last run.");
+            } else {
+                output!("Paths excluded in last run ({}):", run_state.last_run);
+                output!("");
+                for entry in &run_state.entries {
+                    output!("  {} ({})", entry.path, entry_details(entry, count_files));
+                }
+                output!("");
+                output!(
+                    "  {} excluded, {} already excluded",
+                    run_state.excluded_count, run_state.already_excluded_count
+                );
+            }
+        }
+        None => {
+            output!("No previous run found. Run `tmignore run` first.");
+        }
+    }
+    Ok(())
+}
+
+fn cmd_list_since(since: &str, count_files: bool) -> Result<()> {
+    let found = state::first_excluded_since(&state::load_history()?, since);
+    if found.is_empty() {
+        output!("No paths were first excluded since {}.", since);
+        return Ok(());
+    }
+
+    output!("Paths first excluded since {} ({}):", since, found.len());
+    output!("");
+    for (run_at, entry) in &found {
+        let date = run_at.split('T').next().unwrap_or(run_at);
+        output!("  {}  {} ({})", date, entry.path, entry_details(entry, count_files));
+    }
+    let total: u64 = found.iter().map(|(_, e)| e.size).sum();
+    output!("");
+    output!("  {} newly excluded ({})", found.len(), size::format_size(total));
+    Ok(())
+}
+
+/// Parse `list --since` into a UTC timestamp comparable with the run history's.
+fn parse_since(input: &str) -> Result<String> {
+    if let Ok(days) = config::parse_age_days(input) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        return Ok(logging::format_timestamp(now.saturating_sub(days * 86400)));
+    }
+    let timestamp = config::parse_timestamp(input)?;
+    // History times are UTC and compared as text, so offsets can't be honored
+    if timestamp.split_once('T').is_some_and(|(_, time)| time.contains(['+', '-'])) {
+        anyhow::bail!("Use a UTC time for --since (e.g. 2024-06-03T09:00:00Z), got {:?}", input);
+    }
+    Ok(timestamp.trim_end_matches('Z').to_string())
+}
+
+fn cmd_add(path_str: &str, check_size: Option<u64>) -> Result<()> {
+    let expanded = expand_tilde(path_str)?;
+    let canonical = if expanded.exists() {
+        expanded
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve path: {}", expanded.display()))?
+    } else {
+        anyhow::bail!("Path does not exist: {}", expanded.display());
+    };
+
+    if let Some(threshold) = check_size {
+        // Sizing stops at the threshold, so huge trees don't need a full walk
+        let size = size::dir_size_bytes(&canonical, Some(threshold));
+        if size >= threshold {
+            let message = format!(
+                "{} is at least {}, above the --check-size threshold of {}",
+                contract_tilde(&canonical.to_string_lossy()),
+                size::format_size(size),
+                size::format_size(threshold)
+            );
+            if !std::io::stdin().is_terminal() {
+                anyhow::bail!("{}; not excluding it", message);
+            }
+            warn!("{}.", message);
+            if !confirm("Exclude it from backups anyway?")? {
+                info!("Nothing changed.");
+                return Ok(());
+            }
+        }
+    }
+
+    // Add to config
+    let tilde_path = contract_tilde(&canonical.to_string_lossy());
+    let mut cfg = config::load_base_config()?;
+    use_tmutil_from(&cfg);
+
+    if !config::config_path().exists() {
+        let path = config::write_default_config(std::slice::from_ref(&tilde_path))?;
+        info!(
+            "Created config at {} and added {}.",
+            contract_tilde(&path.to_string_lossy()),
+            tilde_path
+        );
+    } else if cfg.extra_exclude_paths.contains(&tilde_path) {
+        info!("{} is already in exclude_paths.", tilde_path);
+    } else {
+        cfg.extra_exclude_paths.push(tilde_path.clone());
+        config::save_config(&cfg)?;
+        info!("Added {} to config.", tilde_path);
+    }
+
+    // Exclude immediately
+    if excluder::is_excluded(&canonical)? {
+        info!("{} is already excluded from backups.", tilde_path);
+    } else {
+        excluder::add_exclusion(&canonical)?;
+        info!("Excluded {} from backups.", tilde_path);
+    }
+
+    Ok(())
+}
+
+/// `add --pattern`: add a custom pattern from flags, asking on the terminal for
+/// any that are missing, then optionally show what it would match.
+fn cmd_add_pattern(
+    name: Option<String>,
+    directory: Option<String>,
+    sentinel: Option<String>,
+    test: bool,
+) -> Result<()> {
+    let name = pattern_field(name, "--pattern-name", "Pattern name (e.g. gen)")?;
+    let directory =
+        pattern_field(directory, "--directory", "Directory name to exclude (e.g. generated)")?;
+    let sentinel = pattern_field(
+        sentinel,
+        "--sentinel",
+        "File or directory that must sit next to it (e.g. codegen.json)",
+    )?;
+    let pattern = config::CustomPattern::new(&name, &directory, &sentinel);
+    if !update_config(|cfg| cfg.add_custom_pattern(pattern.clone()).map(|()| true))? || !test {
+        return Ok(());
+    }
+
+    install_interrupt_handler();
+    let config = config::load_config()?;
+    info!("Scanning {} root(s) for {}...", config.scan_roots.len(), name);
+    let options = scanner::ScanOptions {
+        extra_skip_paths: Vec::new(),
+        time_budget: config.scan_time_budget.map(std::time::Duration::from_secs),
+        prior: None,
+        cancel: Some(&INTERRUPTED),
+        only_dirs: None,
+        record_walked: false,
+    };
+    let patterns = [patterns::Pattern::from(&pattern)];
+    let mut found = 0;
+    // Exclude paths come through the scan too; only the new pattern's matches count
+    scanner::scan_streaming(&config, &patterns, &options, |m| {
+        if m.pattern_name == name {
+            output!("{}", contract_tilde(&m.path.to_string_lossy()));
+            found += 1;
+        }
+        ControlFlow::Continue(())
+    });
+    info!("Found {} directories matching {} (nothing was excluded).", found, name);
+    Ok(())
+}
+
+/// A field for `add --pattern`: the flag's value, or an answer typed on the
+/// terminal. Either way it can't be blank.
+fn pattern_field(value: Option<String>, flag: &str, question: &str) -> Result<String> {
+    let value = match value {
+        Some(value) => value,
+        None if std::io::stdin().is_terminal() => {
+            print!("{}: ", question);
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            answer
+        }
+        None => anyhow::bail!("{} is required when not run from a terminal", flag),
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        anyhow::bail!("{} can't be empty", flag);
+    }
+    Ok(value.to_string())
+}
+
+/// Ask a yes/no question on the terminal. Anything but "y" or "yes" means no.
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+fn cmd_remove(path_str: &str) -> Result<()> {
+    let expanded = expand_tilde(path_str)?;
+    let canonical = if expanded.exists() {
+        expanded
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve path: {}", expanded.display()))?
+    } else {
+        // Path might not exist anymore, but still try to remove from config
+        expanded.clone()
+    };
+
+    // Remove from config
+    if !config::config_path().exists() {
+        let path = config::write_default_config(&[])?;
+        info!("Created default config at {}", contract_tilde(&path.to_string_lossy()));
+    }
+    let mut cfg = config::load_base_config()?;
+    use_tmutil_from(&cfg);
+    let tilde_path = contract_tilde(&canonical.to_string_lossy());
+    let original_len = cfg.extra_exclude_paths.len();
+    cfg.extra_exclude_paths.retain(|p| p != &tilde_path);
+
+    if cfg.extra_exclude_paths.len() < original_len {
+        config::save_config(&cfg)?;
+        info!("Removed {} from config.", tilde_path);
+    } else {
+        // A glob can't be narrowed to leave out one path, so it stays; the
+        // path itself is still un-excluded below
+        let globs = cfg.exclude_globs_matching(&canonical);
+        let globs = if globs.is_empty() { cfg.exclude_globs_matching(&expanded) } else { globs };
+        if globs.is_empty() {
+            info!("{} was not in exclude_paths.", tilde_path);
+        }
+        for glob in globs {
+            warn!(
+                "{} matches {} in extra_exclude_paths, which is left in the config; \
+                 edit it to stop covering this path.",
+                tilde_path, glob
+            );
+        }
+    }
+
+    // Un-exclude
+    if canonical.exists() {
+        match excluder::excluded_by(&canonical)? {
+            Some(by) if by == canonical => {
+                excluder::remove_exclusion(&canonical)?;
+                info!("Removed backup exclusion for {}.", tilde_path);
+            }
+            Some(by) => info!(
+                "{} stays excluded from backups, as {} is excluded.",
+                tilde_path,
+                contract_tilde(&by.to_string_lossy())
+            ),
+            None => info!("{} was not excluded from backups.", tilde_path),
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_size(path_str: &str, json: bool) -> Result<()> {
+    let expanded = expand_tilde(path_str)?;
+    if !expanded.exists() {
+        anyhow::bail!("Path does not exist: {}", expanded.display());
+    }
+    let usage = size::dir_usage(&expanded, None);
+    let display_path = contract_tilde(&expanded.to_string_lossy());
+
+    if json {
+        let value = serde_json::json!({
+            "path": display_path,
+            "bytes": usage.bytes,
+            "files": usage.files,
+        });
+        output!("{}", serde_json::to_string_pretty(&value)?);
+    } else {
+        output!(
+            "{}: {} ({} files)",
+            display_path,
+            size::format_size(usage.bytes),
+            size::format_count(usage.files)
+        );
+    }
+    Ok(())
+}
+
+fn cmd_import(file: &std::path::Path, apply: bool, dry_run: bool) -> Result<()> {
+    let contents = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+
+    // Check against the merged config, but only write to the main file
+    let mut present = config::load_config()?.resolved_exclude_paths();
+    let cfg = config::load_base_config()?;
+    use_tmutil_from(&cfg);
+    let mut added: Vec<String> = Vec::new();
+    let mut already_count: usize = 0;
+    let mut invalid_count: usize = 0;
+
+    for line in config::parse_path_list(&contents) {
+        if !(line.starts_with('/') || line.starts_with('~')) {
+            warn!("  [invalid] {} (not an absolute or ~ path)", line);
+            invalid_count += 1;
+            continue;
+        }
+
+        let expanded = match expand_tilde(&line) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!("  [invalid] {} ({})", line, e);
+                invalid_count += 1;
+                continue;
+            }
+        };
+        let resolved = expanded.canonicalize().unwrap_or(expanded);
+        let tilde_path = contract_tilde(&resolved.to_string_lossy());
+
+        if present.contains(&tilde_path) {
+            already_count += 1;
+            continue;
+        }
+
+        if !resolved.exists() {
+            info!("  [note] {} does not exist on this machine", tilde_path);
+        }
+        present.push(tilde_path.clone());
+        added.push(tilde_path);
+    }
+
+    let mut updated = cfg.clone();
+    updated.extra_exclude_paths.extend(added.iter().cloned());
+    let changes = config::Config::diff(&cfg, &updated);
+
+    if dry_run {
+        if changes.is_empty() {
+            info!("Import would not change the config.");
+        } else {
+            info!("Import would make these config changes:");
+            for change in &changes {
+                info!("  {}", change);
+            }
+        }
+        return Ok(());
+    }
+
+    for change in &changes {
+        info!("  {}", change);
+    }
+    if !changes.is_empty() {
+        config::save_config(&updated)?;
+    }
+
+    if apply {
+        for tilde_path in &added {
+            let Ok(path) = expand_tilde(tilde_path) else { continue };
+            if !path.exists() {
+                continue;
+            }
+            match excluder::is_excluded(&path) {
+                Ok(true) => {}
+                Ok(false) => match excluder::add_exclusion(&path) {
+                    Ok(()) => info!("  [excluded] {}", tilde_path),
+                    Err(e) => error!("  [error] {}: {}", tilde_path, e),
+                },
+                Err(e) => error!("  [error] checking {}: {}", tilde_path, e),
+            }
+        }
+    }
+
+    info!("");
+    info!(
+        "  {} added, {} already present, {} invalid",
+        added.len(),
+        already_count,
+        invalid_count
+    );
+    if !apply && !added.is_empty() {
+        info!("  Run `tmignore run` (or import with --apply) to exclude them now.");
+    }
+
+    Ok(())
+}
+
+fn cmd_export(json: bool) -> Result<()> {
+    let cfg = config::load_config()?;
+    let builtins = config::builtin_exclude_paths();
+    let paths: Vec<String> = cfg
+        .resolved_exclude_paths()
+        .iter()
+        .map(|p| contract_tilde(p))
+        .collect();
+
+    if json {
+        let entries: Vec<serde_json::Value> = paths
+            .iter()
+            .map(|p| {
+                let source = if builtins.contains(&p.as_str()) { "builtin" } else { "extra" };
+                serde_json::json!({ "path": p, "source": source })
+            })
+            .collect();
+        output!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for p in &paths {
+            output!("{}", p);
+        }
+    }
+
+    Ok(())
+}
+
+/// Where a path `tmignore paths` shows comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathSource {
+    Builtin,
+    Config,
+    Scanned,
+}
+
+impl PathSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            PathSource::Builtin => "builtin",
+            PathSource::Config => "config",
+            PathSource::Scanned => "scanned",
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            PathSource::Builtin => "Built-in exclude paths",
+            PathSource::Config => "Exclude paths from config",
+            PathSource::Scanned => "Matched by the last run",
+        }
+    }
+}
+
+/// Every path tmignore manages, in display (`~`) form: the effective exclude
+/// paths plus the matches of the last run. Each path is listed once, under its
+/// first source.
+fn known_paths(cfg: &config::Config, last_run: Option<RunState>) -> Vec<(String, PathSource)> {
+    let builtins = config::builtin_exclude_paths();
+    let mut paths: Vec<(String, PathSource)> = cfg
+        .resolved_exclude_paths()
+        .iter()
+        .map(|p| {
+            let source = if builtins.contains(&p.as_str()) {
+                PathSource::Builtin
+            } else {
+                PathSource::Config
+            };
+            (contract_tilde(p), source)
+        })
+        .collect();
+
+    // A complete scan remembers every match; otherwise fall back to the paths the
+    // last run excluded
+    let scanned: Vec<String> = match last_run {
+        Some(RunState {
+            scan_cache: Some(cache),
+            ..
+        }) => cache
+            .matches
+            .iter()
+            .map(|m| contract_tilde(&m.path.to_string_lossy()))
+            .collect(),
+        Some(run_state) => run_state.entries.into_iter().map(|e| e.path).collect(),
+        None => Vec::new(),
+    };
+    for path in scanned {
+        if !paths.iter().any(|(p, _)| *p == path) {
+            paths.push((path, PathSource::Scanned));
+        }
+    }
+    paths
+}
+
+/// Whether a known path is excluded: None if it doesn't exist on this machine.
+fn path_excluded(path: &str) -> Result<Option<bool>> {
+    let expanded = expand_tilde(path)?;
+    if !expanded.exists() {
+        return Ok(None);
+    }
+    excluder::is_excluded(&expanded).map(Some)
+}
+
+fn cmd_paths(action: PathsCmd) -> Result<()> {
+    let cfg = config::load_config()?;
+    use_tmutil_from(&cfg);
+    let paths = known_paths(&cfg, state::load_state()?);
+
+    match action {
+        PathsCmd::List {
+            format: Format::Json,
+        } => {
+            let entries: Vec<serde_json::Value> = paths
+                .iter()
+                .map(|(path, source)| {
+                    let excluded = matches!(path_excluded(path), Ok(Some(true)));
+                    serde_json::json!({ "path": path, "source": source.as_str(), "excluded": excluded })
+                })
+                .collect();
+            output!("{}", serde_json::to_string_pretty(&entries)?);
+        }
+        PathsCmd::List {
+            format: Format::Text,
+        } => {
+            for source in [PathSource::Builtin, PathSource::Config, PathSource::Scanned] {
+                let group: Vec<&String> = paths
+                    .iter()
+                    .filter(|(_, s)| *s == source)
+                    .map(|(p, _)| p)
+                    .collect();
+                if group.is_empty() {
+                    continue;
+                }
+                output!("{} ({}):", source.heading(), group.len());
+                for path in group {
+                    output!("  {}", path);
+                }
+                output!("");
+            }
+            if !paths.iter().any(|(_, s)| *s == PathSource::Scanned) {
+                output!("No scanned paths yet. Run `tmignore run` first.");
+            }
+        }
+        PathsCmd::Check => {
+            let (mut excluded, mut not_excluded, mut missing, mut errors) = (0, 0, 0, 0);
+            for (path, source) in &paths {
+                match path_excluded(path) {
+                    Ok(Some(true)) => {
+                        excluded += 1;
+                        output!("  [excluded] {} ({})", path, source.as_str());
+                    }
+                    Ok(Some(false)) => {
+                        not_excluded += 1;
+                        output!("  [not excluded] {} ({})", path, source.as_str());
+                    }
+                    Ok(None) => missing += 1,
+                    Err(e) => {
+                        errors += 1;
+                        error!("  [error] checking {}: {}", path, e);
+                    }
+                }
+            }
+            output!("");
+            output!(
+                "  {} excluded, {} not excluded, {} missing, {} errors",
+                excluded, not_excluded, missing, errors
+            );
+            if not_excluded > 0 {
+                output!("  Run `tmignore paths exclude` to exclude them.");
+            }
+        }
+        PathsCmd::Exclude => {
+            let (mut newly, mut already, mut errors) = (0, 0, 0);
+            for (path, _) in &paths {
+                let result = path_excluded(path).and_then(|status| match status {
+                    Some(false) => excluder::add_exclusion(&expand_tilde(path)?).map(|()| true),
+                    _ => Ok(false),
+                });
+                match result {
+                    Ok(true) => {
+                        newly += 1;
+                        info!("  [excluded] {}", path);
+                    }
+                    Ok(false) => already += 1,
+                    Err(e) => {
+                        errors += 1;
+                        error!("  [error] {}: {}", path, e);
+                    }
+                }
+            }
+            info!("");
+            info!(
+                "  {} newly excluded, {} already excluded or missing, {} errors",
+                newly, already, errors
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_status(json: bool) -> Result<()> {
+    let (installed, running) = service::status()?;
+
+    if json {
+        let last_run = state::load_state()?;
+        let status = serde_json::json!({
+            "installed": installed,
+            "running": running,
+            "label": service::label(),
+            "last_run": last_run.as_ref().map(|s| &s.last_run),
+            "excluded_count": last_run.as_ref().map(|s| s.excluded_count),
+            "already_excluded_count": last_run.as_ref().map(|s| s.already_excluded_count),
+            "config_path": contract_tilde(&config::config_path().to_string_lossy()),
+            "plist_path": contract_tilde(&service::get_plist_path().to_string_lossy()),
+            "log_dir": contract_tilde(&service::get_log_dir().to_string_lossy()),
+        });
+        output!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    output!("Service:     {}", service::label());
+    output!("Installed:   {}", if installed { "yes" } else { "no" });
+    output!("Running:     {}", if running { "yes" } else { "no" });
+    output!("");
+
+    // Show last run info
+    match state::load_state()? {
+        Some(run_state) => {
+            if run_state.partial {
+                output!("Last run:    {} (partial scan)", run_state.last_run);
+                output!(
+                    "Last full:   {}",
+                    run_state.last_complete_run.as_deref().unwrap_or("never")
+                );
+            } else {
+                output!("Last run:    {}", run_state.last_run);
+            }
+            if let Some(profile) = &run_state.profile {
+                output!("Profile:     {}", profile);
+            }
+            output!(
+                "  {} excluded ({}), {} already excluded{}",
+                run_state.excluded_count,
+                size::format_size(run_state.total_size_bytes),
+                run_state.already_excluded_count,
+                run_state
+                    .already_excluded_bytes
+                    .map_or(String::new(), |b| format!(" ({})", size::format_size(b)))
+            );
+            if !run_state.pattern_counts.is_empty() {
+                let matched: BTreeMap<String, u64> = run_state
+                    .pattern_counts
+                    .iter()
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(name, count)| (name.clone(), *count))
+                    .collect();
+                let unmatched = run_state.pattern_counts.len() - matched.len();
+                if matched.is_empty() {
+                    output!("  No pattern matched anything");
+                } else {
+                    output!(
+                        "  Matches: {} ({} patterns matched nothing)",
+                        format_pattern_counts(&matched),
+                        unmatched
+                    );
+                }
+            }
+            if run_state.warning_count > 0 {
+                let kinds: Vec<String> = run_state
+                    .warning_kinds
+                    .iter()
+                    .map(|(kind, count)| format!("{} {}", count, kind))
+                    .collect();
+                if kinds.is_empty() {
+                    output!("  {} scan warnings", run_state.warning_count);
+                } else {
+                    output!("  {} scan warnings ({})", run_state.warning_count, kinds.join(", "));
+                }
+            }
+        }
+        None => {
+            output!("Last run:    never");
+        }
+    }
+
+    output!("");
+    output!("Paths:");
+    output!(
+        "  Config: {}",
+        contract_tilde(&config::config_path().to_string_lossy())
+    );
+    output!(
+        "  Plist:  {}",
+        contract_tilde(&service::get_plist_path().to_string_lossy())
+    );
+    output!(
+        "  Logs:   {}",
+        contract_tilde(&service::get_log_dir().to_string_lossy())
+    );
+
+    Ok(())
+}
 
-    match cli.command {
-        Cmd::Run { dry_run, verbose } => cmd_run(dry_run, verbose),
-        Cmd::List => cmd_list(),
-        Cmd::Add { path } => cmd_add(&path),
-        Cmd::Remove { path } => cmd_remove(&path),
-        Cmd::Status => cmd_status(),
-        Cmd::Init { overwrite } => cmd_init(overwrite),
-        Cmd::Install { force } => service::install(force),
-        Cmd::Uninstall => service::uninstall(),
-        Cmd::Reset { all } => cmd_reset(all),
+fn cmd_init(overwrite: bool, preset: Option<&str>, list_presets: bool) -> Result<()> {
+    if list_presets {
+        let width = config::PRESETS.iter().map(|p| p.name.len()).max().unwrap_or(0);
+        for p in config::PRESETS {
+            output!("{:<width$}  {}", p.name, p.description);
+        }
+        return Ok(());
     }
-}
 
-fn cmd_run(dry_run: bool, verbose: bool) -> Result<()> {
-    let config = config::load_config()?;
-    let active_patterns = patterns::resolve_patterns(&config.disable_patterns, &config.custom_patterns);
+    let path = config::config_path();
 
-    if verbose {
-        println!(
-            "Scanning with {} active patterns across {} root(s)...",
-            active_patterns.len(),
-            config.scan_roots.len()
+    if path.exists() && !overwrite {
+        anyhow::bail!(
+            "Config already exists at {}\nUse --overwrite to replace it.",
+            path.display()
         );
     }
 
-    let matches = scanner::scan_optimized(&config, &active_patterns);
+    match preset.and_then(config::find_preset) {
+        Some(preset) => {
+            config::write_preset_config(preset)?;
+            info!(
+                "Created config from the {} preset at {}",
+                preset.name,
+                contract_tilde(&path.to_string_lossy())
+            );
+        }
+        None => {
+            config::write_default_config(&[])?;
+            info!("Created default config at {}", contract_tilde(&path.to_string_lossy()));
+        }
+    }
+    Ok(())
+}
+
+fn cmd_patterns_list() -> Result<()> {
+    let cfg = config::load_config()?;
+    let active: HashSet<String> =
+        patterns::config_patterns(&cfg).into_iter().map(|p| p.name).collect();
+    let all: Vec<patterns::Pattern> = patterns::builtin_patterns()
+        .into_iter()
+        .chain(cfg.custom_patterns.iter().map(patterns::Pattern::from))
+        .collect();
 
-    if verbose {
-        println!("Found {} candidate directories.", matches.len());
+    // Categories in the order their first pattern appears
+    let mut categories: Vec<&str> = Vec::new();
+    for p in &all {
+        if !categories.contains(&p.category()) {
+            categories.push(p.category());
+        }
+    }
+    let name_width = all.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    for category in categories {
+        output!("{}", category);
+        for p in all.iter().filter(|p| p.category() == category) {
+            output!(
+                "  {:<width$}  {}{}",
+                p.name,
+                p.description.as_deref().unwrap_or(&p.directory),
+                if active.contains(&p.name) { "" } else { " (disabled)" },
+                width = name_width
+            );
+        }
     }
+    Ok(())
+}
 
-    let mut newly_excluded: Vec<ExcludedEntry> = Vec::new();
-    let mut already_excluded_count: usize = 0;
-    let mut error_count: usize = 0;
+fn cmd_pattern_info(name: &str) -> Result<()> {
+    let cfg = config::load_config()?;
+    let active = patterns::config_patterns(&cfg);
+    let (pattern, enabled) = match active.into_iter().find(|p| p.name == name) {
+        Some(p) => (p, true),
+        None => match patterns::builtin_patterns().into_iter().find(|p| p.name == name) {
+            Some(p) => (p, false),
+            None => anyhow::bail!("Unknown pattern {:?}", name),
+        },
+    };
+    let source = if cfg.custom_patterns.iter().any(|c| c.name == name) {
+        "custom"
+    } else {
+        "built-in"
+    };
 
-    for m in &matches {
-        match excluder::is_excluded(&m.path) {
-            Ok(true) => {
-                already_excluded_count += 1;
-                if verbose {
-                    println!(
-                        "  [skip] {} (already excluded)",
-                        contract_tilde(&m.path.to_string_lossy())
-                    );
-                }
+    output!(
+        "{} ({}, {})",
+        pattern.name,
+        source,
+        if enabled { "enabled" } else { "disabled" }
+    );
+    if let Some(description) = &pattern.description {
+        output!("  {}", description);
+    }
+    output!("  Category:       {}", pattern.category());
+    let sentinel_kind = match pattern.sentinel_type {
+        config::SentinelType::Dir => " (directory)",
+        config::SentinelType::File => " (file)",
+        config::SentinelType::Any => "",
+    };
+    match pattern.kind {
+        config::PatternKind::Directory => {
+            output!("  Directory:      {}", pattern.directory);
+            let location = match pattern.sentinel_location {
+                config::SentinelLocation::Parent => "in the project directory",
+                config::SentinelLocation::Inside => "inside the matched directory",
+            };
+            if pattern.sentinel.is_empty() {
+                output!("  Sentinel:       none, matches the name anywhere");
+            } else {
+                output!("  Sentinel:       {}{}, {}", pattern.sentinel, sentinel_kind, location);
             }
-            Ok(false) => {
-                let display_path = contract_tilde(&m.path.to_string_lossy());
-
-                if dry_run {
-                    let size = excluder::dir_size(&m.path);
-                    println!("  [dry-run] {} ({}, {})", display_path, m.pattern_name, size);
-                    newly_excluded.push(ExcludedEntry {
-                        path: display_path,
-                        pattern: m.pattern_name.clone(),
-                        size,
-                    });
-                } else {
-                    match excluder::add_exclusion(&m.path) {
-                        Ok(()) => {
-                            let size = excluder::dir_size(&m.path);
-                            println!("  [excluded] {} ({}, {})", display_path, m.pattern_name, size);
-                            newly_excluded.push(ExcludedEntry {
-                                path: display_path,
-                                pattern: m.pattern_name.clone(),
-                                size,
-                            });
-                        }
-                        Err(e) => {
-                            eprintln!("  [error] {}: {}", display_path, e);
-                            error_count += 1;
-                        }
-                    }
-                }
+        }
+        config::PatternKind::Link => {
+            output!("  Symlinks:       {}", pattern.directory);
+            output!("  Sentinel:       {}{}, next to the symlink", pattern.sentinel, sentinel_kind);
+            if let Some(anchor) = &pattern.link_anchor {
+                output!(
+                    "  Excludes:       the directory holding {} in the symlink's target",
+                    anchor
+                );
             }
-            Err(e) => {
-                eprintln!(
-                    "  [error] checking {}: {}",
-                    contract_tilde(&m.path.to_string_lossy()),
-                    e
+        }
+        config::PatternKind::File => {
+            output!("  Files:          {}", pattern.directory);
+            if !pattern.sentinel.is_empty() {
+                output!(
+                    "  Sentinel:       {}{}, next to the file",
+                    pattern.sentinel,
+                    sentinel_kind
                 );
-                error_count += 1;
             }
         }
     }
-
-    // Print summary
-    println!();
-    if dry_run {
-        println!("Dry run complete.");
+    if !pattern.any_sentinels.is_empty() {
+        output!("  Or instead:     {}", pattern.any_sentinels.join(", "));
+    }
+    if !pattern.all_sentinels.is_empty() {
+        output!("  Also requires:  {}", pattern.all_sentinels.join(", "));
+    }
+    if pattern.sentinel_depth > 1 {
+        output!("  Sentinel depth: {} levels", pattern.sentinel_depth);
+    }
+    if pattern.min_size > 0 {
+        output!("  Min size:       {}", size::format_size(pattern.min_size));
     }
-    println!(
-        "  {} newly excluded, {} already excluded, {} errors",
-        newly_excluded.len(),
-        already_excluded_count,
-        error_count
+    if let Some(days) = pattern.older_than_days {
+        output!("  Older than:     {} days", days);
+    }
+    if !pattern.tags.is_empty() {
+        output!("  Tags:           {}", pattern.tags.join(", "));
+    }
+    if let Some(group) = &pattern.group {
+        let list = if pattern.enabled_by_default { "disable" } else { "enable" };
+        output!("  Group:          {} ({list} them all with {list}_patterns)", group);
+    }
+    if pattern.descend {
+        output!("  Descends:       yes, nested projects are matched too");
+    }
+    output!(
+        "  Expected size:  {}",
+        pattern.expected_size_hint.as_deref().unwrap_or("unknown")
     );
+    Ok(())
+}
 
-    // Save state (even for dry-run, to record what was found)
-    if !dry_run {
-        let run_state = RunState {
-            last_run: chrono_now(),
-            excluded_count: newly_excluded.len(),
-            already_excluded_count,
-            entries: newly_excluded,
-        };
-        state::save_state(&run_state)?;
-    }
+fn cmd_pattern_add(
+    name: &str,
+    directory: &str,
+    sentinel: &str,
+    description: Option<String>,
+) -> Result<()> {
+    let mut pattern = config::CustomPattern::new(name, directory, sentinel);
+    pattern.description = description;
+    update_config(|cfg| cfg.add_custom_pattern(pattern).map(|()| true))?;
+    Ok(())
+}
 
+fn cmd_pattern_remove(name: &str) -> Result<()> {
+    if update_config(|cfg| cfg.remove_pattern(name))? {
+        info!("Matches already excluded stay excluded; `tmignore reset` clears them.");
+    }
     Ok(())
 }
 
-fn cmd_list() -> Result<()> {
-    match state::load_state()? {
-        Some(run_state) => {
-            if run_state.entries.is_empty() {
-                println!("No paths were excluded in the last run.");
-            } else {
-                println!("Paths excluded in last run ({}):", run_state.last_run);
-                println!();
-                for entry in &run_state.entries {
-                    println!("  {} ({}, {})", entry.path, entry.pattern, entry.size);
-                }
-                println!();
-                println!(
-                    "  {} excluded, {} already excluded",
-                    run_state.excluded_count, run_state.already_excluded_count
-                );
-            }
+/// Apply `change` to the main config file, creating it first if needed, and save
+/// if the result is valid. `change` returns false when there was nothing to do;
+/// so does this.
+fn update_config(change: impl FnOnce(&mut config::Config) -> Result<bool>) -> Result<bool> {
+    if !config::config_path().exists() {
+        let path = config::write_default_config(&[])?;
+        info!("Created default config at {}", contract_tilde(&path.to_string_lossy()));
+    }
+    let cfg = config::load_base_config()?;
+    let mut updated = cfg.clone();
+    if !change(&mut updated)? {
+        info!("Nothing changed.");
+        return Ok(false);
+    }
+    updated.validate()?;
+
+    for change in config::Config::diff(&cfg, &updated) {
+        info!("  {}", change);
+    }
+    config::save_config(&updated)?;
+    info!("Saved {}.", contract_tilde(&config::config_path().to_string_lossy()));
+    Ok(true)
+}
+
+fn cmd_validate_config(args: ValidateArgs) -> Result<()> {
+    let path = config::config_path();
+    let display = contract_tilde(&path.to_string_lossy());
+    if !path.exists() {
+        output!("No config file at {}; the defaults are used.", display);
+        if args.show_resolved {
+            output!("");
+            output!("{}", toml::to_string_pretty(&config::Config::default())?);
         }
-        None => {
-            println!("No previous run found. Run `tmignore run` first.");
+        return Ok(());
+    }
+    let format = config::ConfigFormat::of(&path);
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if let Some((line, message)) = format.syntax_error(&contents) {
+        match line {
+            Some(line) => output!("{}:{}: error: {}", display, line, message),
+            None => output!("{}: error: {}", display, message),
         }
+        std::process::exit(EXIT_CONFIG_ERRORS);
     }
-    Ok(())
-}
 
-fn cmd_add(path_str: &str) -> Result<()> {
-    let expanded = expand_tilde(path_str);
-    let canonical = if expanded.exists() {
-        expanded
-            .canonicalize()
-            .with_context(|| format!("Failed to resolve path: {}", expanded.display()))?
-    } else {
-        anyhow::bail!("Path does not exist: {}", expanded.display());
+    let loaded = config::load_unvalidated(&path).and_then(|(mut cfg, files)| {
+        if let Some(name) = &args.profile {
+            cfg.apply_profile(name)?;
+        }
+        Ok((cfg, files))
+    });
+    let (cfg, included) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            output!("{}: error: {:#}", display, e);
+            std::process::exit(EXIT_CONFIG_ERRORS);
+        }
     };
 
-    // Add to config
-    let mut cfg = config::load_config()?;
-    let tilde_path = contract_tilde(&canonical.to_string_lossy());
-
-    if cfg.extra_exclude_paths.contains(&tilde_path) {
-        println!("{} is already in exclude_paths.", tilde_path);
-    } else {
-        cfg.extra_exclude_paths.push(tilde_path.clone());
-        config::save_config(&cfg)?;
-        println!("Added {} to config.", tilde_path);
+    // Point each issue at the first file (main file first) that mentions it
+    let mut sources = vec![(display.clone(), contents)];
+    for file in &included {
+        if let Ok(text) = std::fs::read_to_string(file) {
+            sources.push((contract_tilde(&file.to_string_lossy()), text));
+        }
+    }
+    let issues = cfg.issues();
+    for issue in &issues {
+        let location = sources
+            .iter()
+            .find_map(|(name, text)| {
+                config::find_line(text, &issue.needle).map(|line| format!("{}:{}", name, line))
+            })
+            .unwrap_or_else(|| display.clone());
+        output!("{}: {}: {}", location, issue.severity, issue.message);
     }
 
-    // Exclude immediately
-    if excluder::is_excluded(&canonical)? {
-        println!("{} is already excluded from backups.", tilde_path);
-    } else {
-        excluder::add_exclusion(&canonical)?;
-        println!("Excluded {} from backups.", tilde_path);
+    if args.show_resolved {
+        if !issues.is_empty() {
+            output!("");
+        }
+        output!("{}", format.serialize(&cfg)?);
     }
 
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == config::Severity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+    if errors > 0 {
+        output!("{} error(s), {} warning(s)", errors, warnings);
+        std::process::exit(EXIT_CONFIG_ERRORS);
+    }
+    if warnings > 0 {
+        output!("{} warning(s)", warnings);
+        std::process::exit(EXIT_CONFIG_WARNINGS);
+    }
+    if !args.show_resolved {
+        output!("{} is valid.", display);
+    }
     Ok(())
 }
 
-fn cmd_remove(path_str: &str) -> Result<()> {
-    let expanded = expand_tilde(path_str);
-    let canonical = if expanded.exists() {
-        expanded
-            .canonicalize()
-            .with_context(|| format!("Failed to resolve path: {}", expanded.display()))?
-    } else {
-        // Path might not exist anymore, but still try to remove from config
-        expanded
-    };
-
-    // Remove from config
-    let mut cfg = config::load_config()?;
-    let tilde_path = contract_tilde(&canonical.to_string_lossy());
-    let original_len = cfg.extra_exclude_paths.len();
-    cfg.extra_exclude_paths.retain(|p| p != &tilde_path);
+fn cmd_clean() -> Result<()> {
+    let removed = state::clear_state()?;
 
-    if cfg.extra_exclude_paths.len() < original_len {
-        config::save_config(&cfg)?;
-        println!("Removed {} from config.", tilde_path);
+    if removed.is_empty() {
+        info!("No state to clean.");
     } else {
-        println!("{} was not in exclude_paths.", tilde_path);
-    }
-
-    // Un-exclude
-    if canonical.exists() {
-        if excluder::is_excluded(&canonical)? {
-            excluder::remove_exclusion(&canonical)?;
-            println!("Removed backup exclusion for {}.", tilde_path);
-        } else {
-            println!("{} was not excluded from backups.", tilde_path);
+        for path in &removed {
+            info!("  [removed] {}", contract_tilde(&path.to_string_lossy()));
         }
+        info!("");
+        info!("Cleared {} state file(s). Backup exclusions were not changed.", removed.len());
     }
 
     Ok(())
 }
 
-fn cmd_status() -> Result<()> {
-    let (installed, running) = service::status()?;
+fn cmd_clean_logs(older_than_days: Option<u64>) -> Result<()> {
+    let log_dir = service::get_log_dir();
+    if !log_dir.exists() {
+        info!("No logs at {}.", contract_tilde(&log_dir.to_string_lossy()));
+        return Ok(());
+    }
 
-    println!("Service:     {}", service::label());
-    println!("Installed:   {}", if installed { "yes" } else { "no" });
-    println!("Running:     {}", if running { "yes" } else { "no" });
-    println!();
+    let cutoff = older_than_days.map(|days| std::time::Duration::from_secs(days * 86400));
+    let mut removed_count: usize = 0;
+    let mut freed: u64 = 0;
 
-    // Show last run info
-    match state::load_state()? {
-        Some(run_state) => {
-            println!("Last run:    {}", run_state.last_run);
-            println!(
-                "  {} excluded, {} already excluded",
-                run_state.excluded_count, run_state.already_excluded_count
-            );
+    let entries = std::fs::read_dir(&log_dir)
+        .with_context(|| format!("Failed to read {}", log_dir.display()))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read {}", log_dir.display()))?;
+        let meta = entry.metadata()?;
+        if !meta.is_file() {
+            continue;
         }
-        None => {
-            println!("Last run:    never");
+        if let Some(cutoff) = cutoff {
+            let age = meta.modified().ok().and_then(|t| t.elapsed().ok());
+            if age.is_none_or(|age| age < cutoff) {
+                continue;
+            }
+        }
+
+        let path = entry.path();
+        let display_path = contract_tilde(&path.to_string_lossy());
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                info!("  [removed] {} ({})", display_path, size::format_size(meta.len()));
+                removed_count += 1;
+                freed += meta.len();
+            }
+            Err(e) => error!("  [error] {}: {}", display_path, e),
         }
     }
 
-    println!();
-    println!("Paths:");
-    println!(
-        "  Config: {}",
-        contract_tilde(&config::config_path().to_string_lossy())
-    );
-    println!(
-        "  Plist:  {}",
-        contract_tilde(&service::get_plist_path().to_string_lossy())
-    );
-    println!(
-        "  Logs:   {}",
-        contract_tilde(&service::get_log_dir().to_string_lossy())
+    info!("");
+    info!(
+        "  {} log files removed, {} freed",
+        removed_count,
+        size::format_size(freed)
     );
-
     Ok(())
 }
 
-fn cmd_init(overwrite: bool) -> Result<()> {
-    let path = config::config_path();
-
-    if path.exists() && !overwrite {
-        anyhow::bail!(
-            "Config already exists at {}\nUse --overwrite to replace it.",
-            path.display()
-        );
+/// Directories under the scan roots that Spotlight saw change since `since`,
+/// for `run --changed-since`.
+fn changed_dirs_since(config: &config::Config, since: &str, verbose: bool) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for root in &config.scan_roots {
+        // The scan reports roots that don't exist
+        let Some(root) = expand_tilde(root).ok().and_then(|r| r.canonicalize().ok()) else {
+            continue;
+        };
+        if verbose {
+            let args: Vec<String> = scanner::changed_since_query(&root, since)
+                .into_iter()
+                .map(|a| if a.contains([' ', '$']) { format!("'{a}'") } else { a })
+                .collect();
+            info!("Running: mdfind {}", args.join(" "));
+        }
+        dirs.extend(scanner::find_changed_dirs(&root, since)?);
     }
+    Ok(dirs)
+}
 
-    std::fs::create_dir_all(config::config_dir()).context("Failed to create config directory")?;
-    std::fs::write(&path, config::Config::default_toml())
-        .with_context(|| format!("Failed to write {}", path.display()))?;
-
-    println!("Created default config at {}", contract_tilde(&path.to_string_lossy()));
+fn cmd_snapshot(action: SnapshotCmd) -> Result<()> {
+    match action {
+        SnapshotCmd::Save { name, roots, force } => {
+            if snapshot::exists(&name)? && !force {
+                anyhow::bail!("Snapshot {:?} already exists. Use --force to replace it.", name);
+            }
+            let paths = if roots.is_empty() {
+                info!("Finding all sticky backup exclusions on the system...");
+                excluder::find_all_exclusions()?
+            } else {
+                let roots = roots
+                    .iter()
+                    .map(|r| expand_tilde(r))
+                    .collect::<Result<Vec<_>>>()?;
+                info!("Finding sticky backup exclusions under {} root(s)...", roots.len());
+                excluder::find_exclusions_under(&roots)
+            };
+            let config_path = config::config_path();
+            let config = if config_path.exists() {
+                Some(std::fs::read_to_string(&config_path).with_context(|| {
+                    format!("Failed to read {}", config_path.display())
+                })?)
+            } else {
+                None
+            };
+            let snapshot = snapshot::Snapshot {
+                name,
+                created_at: logging::timestamp(),
+                config,
+                paths: paths.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+            };
+            let path = snapshot::save(&snapshot)?;
+            info!(
+                "Saved snapshot {:?} with {} excluded paths to {}",
+                snapshot.name,
+                snapshot.paths.len(),
+                contract_tilde(&path.to_string_lossy())
+            );
+        }
+        SnapshotCmd::List => {
+            let snapshots = snapshot::list()?;
+            if snapshots.is_empty() {
+                output!(
+                    "No snapshots saved. Create one with `tmignore snapshot save --name=<name>`."
+                );
+            }
+            for s in &snapshots {
+                let config = if s.config.is_some() { ", with config" } else { "" };
+                output!("  {}  {}  {} paths{}", s.name, s.created_at, s.paths.len(), config);
+            }
+        }
+        SnapshotCmd::Restore { name, config } => {
+            let snapshot = snapshot::load(&name)?;
+            use_tmutil_from(&config::load_base_config()?);
+            let (mut restored, mut already, mut missing, mut errors) = (0, 0, 0, 0);
+            for path in snapshot.paths.iter().map(PathBuf::from) {
+                let display_path = contract_tilde(&path.to_string_lossy());
+                if !path.exists() {
+                    missing += 1;
+                    continue;
+                }
+                let result = excluder::is_excluded(&path).and_then(|excluded| {
+                    if excluded {
+                        return Ok(false);
+                    }
+                    excluder::add_exclusion(&path).map(|()| true)
+                });
+                match result {
+                    Ok(true) => {
+                        info!("  [excluded] {}", display_path);
+                        restored += 1;
+                    }
+                    Ok(false) => already += 1,
+                    Err(e) => {
+                        error!("  [error] {}: {:#}", display_path, e);
+                        errors += 1;
+                    }
+                }
+            }
+            if config {
+                match &snapshot.config {
+                    Some(contents) => {
+                        let path = config::config_path();
+                        if let Some(dir) = path.parent() {
+                            std::fs::create_dir_all(dir).context("Failed to create config directory")?;
+                        }
+                        std::fs::write(&path, contents)
+                            .with_context(|| format!("Failed to write {}", path.display()))?;
+                        info!("Restored config to {}", contract_tilde(&path.to_string_lossy()));
+                    }
+                    None => warn!("Snapshot {:?} has no config file to restore.", name),
+                }
+            }
+            info!("");
+            info!(
+                "  {} restored, {} already excluded, {} missing, {} errors",
+                restored, already, missing, errors
+            );
+        }
+        SnapshotCmd::Delete { name } => {
+            snapshot::delete(&name)?;
+            info!("Deleted snapshot {:?}.", name);
+        }
+    }
     Ok(())
 }
 
-fn cmd_reset(all: bool) -> Result<()> {
+fn cmd_reset(all: bool, keep_state: bool, roots: &[String]) -> Result<()> {
+    let config = config::load_config()?;
+    use_tmutil_from(&config);
     let mut removed_count: usize = 0;
     let mut error_count: usize = 0;
 
-    if all {
+    if all && !roots.is_empty() {
+        let roots = roots
+            .iter()
+            .map(|r| expand_tilde(r))
+            .collect::<Result<Vec<_>>>()?;
+        info!("Finding sticky backup exclusions under {} root(s)...", roots.len());
+        for (path, result) in excluder::remove_all_tmignore_exclusions(&roots) {
+            let display_path = contract_tilde(&path.to_string_lossy());
+            match result {
+                Ok(()) => {
+                    info!("  [removed] {}", display_path);
+                    removed_count += 1;
+                }
+                Err(e) => {
+                    error!("  [error] {}: {:#}", display_path, e);
+                    error_count += 1;
+                }
+            }
+        }
+    } else if all {
         // Find ALL sticky exclusions on the system using mdfind
-        println!("Finding all sticky backup exclusions on the system...");
-        let output = std::process::Command::new("mdfind")
-            .args(["com_apple_backup_excludeItem = 'com.apple.backupd'"])
-            .output()
-            .context("Failed to run mdfind")?;
-
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                let path = std::path::PathBuf::from(line.trim());
-                if !path.exists() {
-                    continue;
+        info!("Finding all sticky backup exclusions on the system...");
+        let paths = excluder::find_all_exclusions().unwrap_or_else(|e| {
+            warn!("{:#}", e);
+            Vec::new()
+        });
+        for path in paths {
+            let display_path = contract_tilde(&path.to_string_lossy());
+            match excluder::remove_exclusion(&path) {
+                Ok(()) => {
+                    info!("  [removed] {}", display_path);
+                    removed_count += 1;
                 }
-                let display_path = contract_tilde(&path.to_string_lossy());
-                match excluder::remove_exclusion(&path) {
-                    Ok(()) => {
-                        println!("  [removed] {}", display_path);
-                        removed_count += 1;
-                    }
-                    Err(e) => {
-                        eprintln!("  [error] {}: {}", display_path, e);
-                        error_count += 1;
-                    }
+                Err(e) => {
+                    error!("  [error] {}: {}", display_path, e);
+                    error_count += 1;
                 }
             }
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("Warning: mdfind failed: {}", stderr.trim());
         }
     } else {
         // Only remove exclusions tmignore would manage: scanned patterns + exclude_paths
-        let config = config::load_config()?;
-        let active_patterns =
-            patterns::resolve_patterns(&config.disable_patterns, &config.custom_patterns);
-        let matches = scanner::scan_optimized(&config, &active_patterns);
-
-        for m in &matches {
-            match excluder::is_excluded(&m.path) {
+        let active_patterns = patterns::config_patterns(&config);
+        let options = scanner::ScanOptions::default();
+        let summary = scanner::scan_streaming(&config, &active_patterns, &options, |m| {
+            // Exclusions inherited from a directory above aren't this match's to remove
+            match excluder::excluded_by(&m.path).map(|by| by.as_ref() == Some(&m.path)) {
                 Ok(true) => {
                     let display_path = contract_tilde(&m.path.to_string_lossy());
                     match excluder::remove_exclusion(&m.path) {
                         Ok(()) => {
-                            println!("  [removed] {}", display_path);
+                            info!("  [removed] {}", display_path);
                             removed_count += 1;
                         }
                         Err(e) => {
-                            eprintln!("  [error] {}: {}", display_path, e);
+                            error!("  [error] {}: {}", display_path, e);
                             error_count += 1;
                         }
                     }
                 }
                 Ok(false) => {}
                 Err(e) => {
-                    eprintln!(
+                    error!(
                         "  [error] checking {}: {}",
                         contract_tilde(&m.path.to_string_lossy()),
                         e
@@ -413,50 +2983,153 @@ fn cmd_reset(all: bool) -> Result<()> {
                     error_count += 1;
                 }
             }
+            ControlFlow::Continue(())
+        });
+        let hidden_denied = report_scan_warnings(&summary.warnings, false);
+        if hidden_denied > 0 {
+            info!("Skipped {} directories due to permission errors.", hidden_denied);
         }
 
         for path_str in config.resolved_exclude_paths() {
-            let path = expand_tilde(&path_str);
-            if path.exists() {
-                if let Ok(true) = excluder::is_excluded(&path) {
-                    let display_path = contract_tilde(&path.to_string_lossy());
-                    match excluder::remove_exclusion(&path) {
-                        Ok(()) => {
-                            println!("  [removed] {}", display_path);
-                            removed_count += 1;
-                        }
-                        Err(e) => {
-                            eprintln!("  [error] {}: {}", display_path, e);
-                            error_count += 1;
-                        }
+            let Ok(path) = expand_tilde(&path_str) else { continue };
+            if path.exists()
+                && let Ok(Some(by)) = excluder::excluded_by(&path)
+                && by == path
+            {
+                let display_path = contract_tilde(&path.to_string_lossy());
+                match excluder::remove_exclusion(&path) {
+                    Ok(()) => {
+                        info!("  [removed] {}", display_path);
+                        removed_count += 1;
+                    }
+                    Err(e) => {
+                        error!("  [error] {}: {}", display_path, e);
+                        error_count += 1;
                     }
                 }
             }
         }
     }
 
-    // Clear state file
-    let state_path = std::path::PathBuf::from(std::env::var("HOME").expect("HOME not set"))
-        .join(".local/state/tmignore/state.json");
-    if state_path.exists() {
-        std::fs::remove_file(&state_path).ok();
+    info!("");
+    info!("  {} exclusions removed, {} errors", removed_count, error_count);
+    if !keep_state {
+        match state::remove_state() {
+            Ok(true) => info!("  Cleared the last run's state (keep it with --keep-state)"),
+            Ok(false) => {}
+            Err(e) => warn!("could not clear run state: {:#}", e),
+        }
     }
 
-    println!();
-    println!("  {} exclusions removed, {} errors", removed_count, error_count);
-
     Ok(())
 }
 
-/// Simple ISO 8601 timestamp without pulling in chrono.
-fn chrono_now() -> String {
-    let output = std::process::Command::new("date")
-        .args(["-u", "+%Y-%m-%dT%H:%M:%SZ"])
-        .output()
-        .ok();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
 
-    match output {
-        Some(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim().to_string(),
-        _ => "unknown".to_string(),
+    fn usage(bytes: u64) -> size::Usage {
+        size::Usage { bytes, files: 3 }
+    }
+
+    #[test]
+    fn test_outcome_events() {
+        let path = "/Users/me/app/node_modules";
+        let skip = |reason: &str| json!({ "type": "skip", "path": path, "reason": reason });
+        let error = |message: &str| json!({ "type": "error", "path": path, "message": message });
+        let cases = [
+            (MatchOutcome::AlreadyExcluded { usage: None }, skip("already_excluded")),
+            (MatchOutcome::AlreadyExcluded { usage: Some(usage(1)) }, skip("already_excluded")),
+            (MatchOutcome::TooRecent { age_days: 1, older_than_days: 7 }, skip("too_recent")),
+            (MatchOutcome::BelowMinSize { size: 1, min_size: 2 }, skip("below_min_size")),
+            (
+                MatchOutcome::Excluded { usage: usage(4096) },
+                json!({ "type": "excluded", "path": path, "size": 4096 }),
+            ),
+            (
+                MatchOutcome::WouldExclude { usage: usage(4096) },
+                json!({ "type": "would_exclude", "path": path, "size": 4096 }),
+            ),
+            (
+                MatchOutcome::ExcludeFailed(anyhow::anyhow!("denied").context("write failed")),
+                error("write failed: denied"),
+            ),
+            (MatchOutcome::CheckFailed(anyhow::anyhow!("gone")), error("gone")),
+        ];
+        for (outcome, expected) in cases {
+            assert_eq!(RunTally::outcome_event(path, &outcome), expected);
+        }
+    }
+
+    #[test]
+    fn test_match_and_done_events() {
+        let m = scanner::ScanMatch {
+            path: PathBuf::from("/Users/me/vendor"),
+            pattern_name: "composer".to_string(),
+            also_matched: vec!["bundler".to_string()],
+            known_excluded: false,
+        };
+        assert_eq!(
+            RunTally::match_event(&m),
+            json!({
+                "type": "match",
+                "path": "/Users/me/vendor",
+                "pattern": "composer",
+                "also_matched": ["bundler"],
+            })
+        );
+
+        let mut tally = RunTally::default();
+        tally.record(&m, MatchOutcome::Excluded { usage: usage(1) }, Output::Quiet);
+        tally.record(&m, MatchOutcome::AlreadyExcluded { usage: None }, Output::Quiet);
+        tally.record(&m, MatchOutcome::CheckFailed(anyhow::anyhow!("gone")), Output::TotalOnly);
+        assert_eq!(
+            tally.done_event(),
+            json!({ "type": "done", "newly_excluded": 1, "already_excluded": 1, "errors": 1 })
+        );
+    }
+
+    #[test]
+    fn test_printed_outcomes() {
+        use MatchOutcome::*;
+        let excluded = || Excluded { usage: usage(1) };
+        let already = || AlreadyExcluded { usage: None };
+        let recent = || TooRecent { age_days: 1, older_than_days: 7 };
+        let failed = || CheckFailed(anyhow::anyhow!("gone"));
+        let denied = || CheckFailed(anyhow::Error::new(excluder::ExclusionError::PermissionDenied));
+        let printed = |tally: &RunTally, output: Output| {
+            [excluded(), already(), recent(), failed(), denied()]
+                .map(|outcome| tally.prints(&outcome, output))
+        };
+
+        let mut tally = RunTally::default();
+        assert_eq!(printed(&tally, Output::Verbose), [true; 5]);
+        assert_eq!(printed(&tally, Output::Normal), [true, false, false, true, false]);
+        assert_eq!(printed(&tally, Output::Quiet), [false, false, false, true, false]);
+        assert_eq!(printed(&tally, Output::TotalOnly), [false; 5]);
+        // Sized already excluded paths (--re-check) are listed without --verbose
+        let sized = AlreadyExcluded { usage: Some(usage(1)) };
+        assert!(tally.prints(&sized, Output::Normal));
+
+        tally.show = vec![Shown::Excluded, Shown::Errors];
+        assert_eq!(printed(&tally, Output::Verbose), [true, false, false, true, true]);
+        tally.show = vec![Shown::Already];
+        assert_eq!(printed(&tally, Output::Verbose), [false, true, false, false, false]);
+
+        // --ignore-errors hides errors but still counts them
+        tally.show.clear();
+        tally.ignore_errors = true;
+        assert_eq!(printed(&tally, Output::Verbose), [true, true, true, false, false]);
+        let m = scanner::ScanMatch {
+            path: PathBuf::from("/Users/me/app/node_modules"),
+            pattern_name: "node".to_string(),
+            also_matched: Vec::new(),
+            known_excluded: false,
+        };
+        tally.record(&m, failed(), Output::Quiet);
+        tally.record(&m, denied(), Output::Quiet);
+        assert_eq!(tally.error_count, 2);
+        assert_eq!(tally.access_denied_count, 1);
     }
 }
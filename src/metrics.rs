@@ -0,0 +1,98 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// Counters from a single run, written for external monitoring.
+#[derive(Debug, Serialize)]
+pub struct RunMetrics {
+    pub excluded_total: usize,
+    pub already_excluded_total: usize,
+    pub errors_total: usize,
+    pub bytes_saved: u64,
+    /// Unix timestamp (seconds) of the run.
+    pub last_run_timestamp: u64,
+}
+
+/// Render metrics in the Prometheus text exposition format.
+pub fn render_prometheus(m: &RunMetrics) -> String {
+    let metrics: [(&str, &str, u64); 5] = [
+        ("tmignore_excluded_total", "Paths newly excluded in the last run.", m.excluded_total as u64),
+        (
+            "tmignore_already_excluded_total",
+            "Matched paths that were already excluded in the last run.",
+            m.already_excluded_total as u64,
+        ),
+        ("tmignore_errors_total", "Errors during the last run.", m.errors_total as u64),
+        ("tmignore_bytes_saved", "Size of paths newly excluded in the last run.", m.bytes_saved),
+        ("tmignore_last_run_timestamp", "Unix time of the last run.", m.last_run_timestamp),
+    ];
+
+    let mut out = String::new();
+    for (name, help, value) in metrics {
+        out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n"));
+    }
+    out
+}
+
+/// Write metrics to `path`: JSON when it ends in `.json`, Prometheus text otherwise.
+/// Writes to a temporary file and renames it so collectors never read a partial file.
+pub fn write(path: &Path, m: &RunMetrics) -> Result<()> {
+    let contents = if path.extension().is_some_and(|e| e == "json") {
+        serde_json::to_string_pretty(m).context("Failed to serialize metrics")?
+    } else {
+        render_prometheus(m)
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> RunMetrics {
+        RunMetrics {
+            excluded_total: 3,
+            already_excluded_total: 40,
+            errors_total: 1,
+            bytes_saved: 1024,
+            last_run_timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let text = render_prometheus(&sample());
+        assert!(text.contains("# TYPE tmignore_excluded_total gauge\ntmignore_excluded_total 3\n"));
+        assert!(text.contains("tmignore_already_excluded_total 40\n"));
+        assert!(text.contains("tmignore_errors_total 1\n"));
+        assert!(text.contains("tmignore_bytes_saved 1024\n"));
+        assert!(text.contains("tmignore_last_run_timestamp 1700000000\n"));
+    }
+
+    #[test]
+    fn test_write_json_by_extension() {
+        let dir = std::env::temp_dir().join("tmignore_test_metrics");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let path = dir.join("metrics.json");
+        write(&path, &sample()).unwrap();
+        let value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value["excluded_total"], 3);
+
+        let prom = dir.join("tmignore.prom");
+        write(&prom, &sample()).unwrap();
+        assert!(std::fs::read_to_string(&prom).unwrap().starts_with("# HELP"));
+        assert!(!dir.join("tmignore.tmp").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
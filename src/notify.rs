@@ -0,0 +1,28 @@
+use anyhow::{Context, Result};
+use std::process::Command;
+
+/// Quote a string as an AppleScript string literal.
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Show a macOS notification via `osascript`.
+pub fn send(title: &str, message: &str) -> Result<()> {
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(message),
+        applescript_string(title)
+    );
+
+    let output = Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .context("Failed to run osascript")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("osascript failed: {}", stderr.trim());
+    }
+
+    Ok(())
+}
@@ -1,20 +1,235 @@
-use crate::config::CustomPattern;
+use crate::config::{
+    Config, CustomPattern, PatternKind, RootPatterns, SentinelLocation, SentinelType,
+};
+
+/// Prefix of `disable_patterns`/`enable_patterns` entries that select a whole
+/// category, e.g. `category:jvm`.
+pub const CATEGORY_PREFIX: &str = "category:";
 
 #[derive(Debug, Clone)]
 pub struct Pattern {
     pub name: String,
+    /// Directory name to match. May span several components (e.g. "priv/static").
+    /// For file patterns, a glob matched against file names (e.g. "*.qcow2").
+    /// The last component may be a glob (e.g. "cmake-build-*").
     pub directory: String,
+    /// The last component of `directory` compiled once up front, when it is a glob.
+    pub directory_glob: Option<glob::Pattern>,
+    pub kind: PatternKind,
+    /// Empty for file patterns that match without a sentinel.
     pub sentinel: String,
+    /// `sentinel` compiled once up front, when it is a glob (e.g. "*.csproj").
+    pub sentinel_glob: Option<glob::Pattern>,
+    pub sentinel_location: SentinelLocation,
+    /// Whether the sentinel must be a file, a directory, or either.
+    pub sentinel_type: SentinelType,
+    /// Alternatives to `sentinel`: any one of them will do instead (e.g.
+    /// `setup.py` for a project without a `pyproject.toml`).
+    pub any_sentinels: Vec<String>,
+    /// Additional sentinels that must all exist alongside `sentinel`.
+    /// A trailing `/` requires the entry to be a directory.
+    pub all_sentinels: Vec<String>,
+    /// How many directory levels to search for the sentinels, starting at the
+    /// usual location and moving up (1 = only the usual location).
+    pub sentinel_depth: usize,
+    /// Matches smaller than this many bytes are left alone (0 = no minimum).
+    pub min_size: u64,
+    /// Only exclude matches untouched for this many days (None = defer to config).
+    pub older_than_days: Option<u64>,
+    /// Keep walking inside matches instead of skipping them.
+    pub descend: bool,
+    /// Typical size of a match (e.g. "100MB–2GB"), for display only.
+    pub expected_size_hint: Option<String>,
+    /// For link patterns: exclude the parent of the nearest directory with this
+    /// name in the link's target, rather than the target itself (e.g. Bazel's
+    /// output base, above `execroot`).
+    pub link_anchor: Option<String>,
+    /// A family of patterns that `disable_patterns` can turn off by this one
+    /// name (e.g. "python-caches").
+    pub group: Option<String>,
+    /// Off-by-default patterns only run when `enable_patterns` names them or
+    /// their group.
+    pub enabled_by_default: bool,
+    /// Broad labels such as a language or ecosystem ("javascript", "jvm"), for
+    /// turning many patterns off at once with `disable_tags`. The first is the
+    /// pattern's category.
+    pub tags: Vec<String>,
+    /// What the matched directories hold, in a few words, for listings.
+    pub description: Option<String>,
 }
 
 impl Pattern {
-    fn new(name: &str, directory: &str, sentinel: &str) -> Self {
+    pub(crate) fn new(name: &str, directory: &str, sentinel: &str) -> Self {
         Self {
             name: name.to_string(),
             directory: directory.to_string(),
+            directory_glob: compile_glob(leaf(directory)),
+            kind: PatternKind::Directory,
             sentinel: sentinel.to_string(),
+            sentinel_glob: compile_glob(sentinel),
+            sentinel_location: SentinelLocation::Parent,
+            sentinel_type: SentinelType::Any,
+            any_sentinels: Vec::new(),
+            all_sentinels: Vec::new(),
+            sentinel_depth: 1,
+            min_size: 0,
+            older_than_days: None,
+            descend: false,
+            expected_size_hint: None,
+            link_anchor: None,
+            group: None,
+            enabled_by_default: true,
+            tags: Vec::new(),
+            description: None,
+        }
+    }
+
+    /// Match files whose name matches `glob` and that are at least `min_size`
+    /// bytes, wherever they are.
+    pub(crate) fn file(name: &str, glob: &str, min_size: u64) -> Self {
+        Self {
+            kind: PatternKind::File,
+            min_size,
+            ..Self::new(name, glob, "")
+        }
+    }
+
+    /// Match symlinks whose name matches `glob` and that sit next to the
+    /// sentinel, excluding the parent of the `anchor` directory they point into.
+    fn link(name: &str, glob: &str, sentinel: &str, anchor: &str) -> Self {
+        Self {
+            kind: PatternKind::Link,
+            link_anchor: Some(anchor.to_string()),
+            ..Self::new(name, glob, sentinel)
         }
     }
+
+    /// Require the sentinel inside the matched directory instead of next to it.
+    pub(crate) fn sentinel_inside(mut self) -> Self {
+        self.sentinel_location = SentinelLocation::Inside;
+        self
+    }
+
+    /// Require the sentinel to be a directory (e.g. an `*.xcodeproj` bundle).
+    fn sentinel_dir(mut self) -> Self {
+        self.sentinel_type = SentinelType::Dir;
+        self
+    }
+
+    /// Document how large a match typically is.
+    fn size_hint(mut self, hint: &str) -> Self {
+        self.expected_size_hint = Some(hint.to_string());
+        self
+    }
+
+    /// Accept any of these sentinels in place of the primary one.
+    fn or_sentinels(mut self, sentinels: &[&str]) -> Self {
+        self.any_sentinels = sentinels.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Search this many directory levels for the sentinels (see `sentinel_depth`).
+    fn sentinel_depth(mut self, depth: usize) -> Self {
+        self.sentinel_depth = depth;
+        self
+    }
+
+    fn in_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    fn tagged(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    fn described(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// The family a pattern belongs to, for grouping listings and for
+    /// `category:<name>` selectors: its first tag, or "custom" for an untagged
+    /// custom pattern.
+    pub fn category(&self) -> &str {
+        self.tags.first().map_or("custom", String::as_str)
+    }
+
+    /// Only run the pattern when `enable_patterns` asks for it.
+    fn off_by_default(mut self) -> Self {
+        self.enabled_by_default = false;
+        self
+    }
+
+    /// Whether a `disable_patterns` or `enable_patterns` entry names this
+    /// pattern, its group, or its category as `category:<name>`.
+    pub fn selected_by(&self, name: &str) -> bool {
+        match name.strip_prefix(CATEGORY_PREFIX) {
+            Some(category) => self.category() == category,
+            None => self.name == name || self.group.as_deref() == Some(name),
+        }
+    }
+
+    /// Require additional sentinels to exist alongside the primary one.
+    fn requires(mut self, sentinels: &[&str]) -> Self {
+        self.all_sentinels = sentinels.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// How specific the pattern's evidence is, for ranking patterns that share a
+    /// directory name (e.g. `vendor`): a longer directory path first, then more
+    /// required sentinels, then an exact sentinel name over a glob. Higher is
+    /// more specific.
+    pub fn specificity(&self) -> (usize, usize, bool) {
+        (
+            self.directory.split('/').count(),
+            1 + self.all_sentinels.len(),
+            self.sentinel_glob.is_none(),
+        )
+    }
+
+    /// Last component of `directory`, used to index patterns by directory name.
+    pub fn leaf_name(&self) -> &str {
+        leaf(&self.directory)
+    }
+
+    /// The project directory a match belongs to: the directory containing the
+    /// full `directory` path. Returns None if `path` doesn't end with it.
+    pub fn project_root<'a>(&self, path: &'a std::path::Path) -> Option<&'a std::path::Path> {
+        let matches = match &self.directory_glob {
+            Some(glob) => {
+                let name_matches = path.file_name()?.to_str().is_some_and(|n| glob.matches(n));
+                let parents = self.directory.rsplit_once('/').map(|(parents, _)| parents);
+                name_matches && parents.is_none_or(|p| path.parent().is_some_and(|d| d.ends_with(p)))
+            }
+            None => path.ends_with(&self.directory),
+        };
+        if !matches {
+            return None;
+        }
+        let depth = self.directory.split('/').count();
+        path.ancestors().nth(depth)
+    }
+}
+
+fn leaf(directory: &str) -> &str {
+    directory.rsplit('/').next().unwrap_or(directory)
+}
+
+/// Whether a sentinel name contains glob wildcards.
+pub fn is_glob(name: &str) -> bool {
+    name.contains('*') || name.contains('?') || name.contains('[')
+}
+
+fn compile_glob(name: &str) -> Option<glob::Pattern> {
+    is_glob(name)
+        .then(|| glob::Pattern::new(name).ok())
+        .flatten()
 }
 
 impl From<&CustomPattern> for Pattern {
@@ -22,70 +237,497 @@ impl From<&CustomPattern> for Pattern {
         Self {
             name: cp.name.clone(),
             directory: cp.directory.clone(),
+            directory_glob: compile_glob(leaf(&cp.directory)),
+            kind: cp.kind,
             sentinel: cp.sentinel.clone(),
+            sentinel_glob: compile_glob(&cp.sentinel),
+            sentinel_location: cp.sentinel_location,
+            sentinel_type: cp.sentinel_type,
+            any_sentinels: Vec::new(),
+            all_sentinels: cp.all_sentinels.clone(),
+            sentinel_depth: cp.sentinel_depth.unwrap_or(1).max(1),
+            min_size: cp.min_size.map_or(0, |s| s.0),
+            older_than_days: cp.older_than_days,
+            descend: cp.descend,
+            expected_size_hint: cp.expected_size_hint.clone(),
+            link_anchor: None,
+            group: None,
+            enabled_by_default: true,
+            tags: cp.tags.clone(),
+            description: cp.description.clone(),
         }
     }
 }
 
 pub fn builtin_patterns() -> Vec<Pattern> {
     vec![
-        Pattern::new("node", "node_modules", "package.json"),
-        Pattern::new("next", ".next", "package.json"),
-        Pattern::new("nuxt", ".nuxt", "package.json"),
-        Pattern::new("svelte-kit", ".svelte-kit", "package.json"),
-        Pattern::new("angular", ".angular", "package.json"),
-        Pattern::new("parcel", ".parcel-cache", "package.json"),
-        Pattern::new("turbo", ".turbo", "package.json"),
-        Pattern::new("bower", "bower_components", "bower.json"),
-        Pattern::new("yarn", ".yarn", ".yarnrc.yml"),
-        Pattern::new("composer", "vendor", "composer.json"),
-        Pattern::new("bundler", "vendor", "Gemfile"),
-        Pattern::new("cargo", "target", "Cargo.toml"),
-        Pattern::new("go", "vendor", "go.mod"),
-        Pattern::new("maven", "target", "pom.xml"),
-        Pattern::new("gradle", ".gradle", "build.gradle"),
-        Pattern::new("gradle-kts", ".gradle", "build.gradle.kts"),
-        Pattern::new("sbt", "target", "build.sbt"),
-        Pattern::new("swift", ".build", "Package.swift"),
-        Pattern::new("cocoapods", "Pods", "Podfile"),
-        Pattern::new("carthage", "Carthage", "Cartfile"),
-        Pattern::new("flutter", ".dart_tool", "pubspec.yaml"),
-        Pattern::new("pub", ".packages", "pubspec.yaml"),
-        Pattern::new("python-venv", ".venv", "pyproject.toml"),
-        Pattern::new("python-tox", ".tox", "tox.ini"),
-        Pattern::new("python-nox", ".nox", "noxfile.py"),
-        Pattern::new("elixir-deps", "deps", "mix.exs"),
-        Pattern::new("elixir-build", "_build", "mix.exs"),
-        Pattern::new("haskell", ".stack-work", "stack.yaml"),
-        Pattern::new("vagrant", ".vagrant", "Vagrantfile"),
-        Pattern::new("terraform", ".terraform", ".terraform.lock.hcl"),
-        Pattern::new("terragrunt", ".terragrunt-cache", "terragrunt.hcl"),
-        Pattern::new("cdk", "cdk.out", "cdk.json"),
-        Pattern::new("dotnet-bin", "bin", "*.csproj"),
-        Pattern::new("dotnet-obj", "obj", "*.csproj"),
-        Pattern::new("zig", "zig-cache", "build.zig"),
-        Pattern::new("ocaml", "_build", "dune-project"),
-        Pattern::new("godot", ".godot", "project.godot"),
-        Pattern::new("clojure", ".cpcache", "deps.edn"),
-        Pattern::new("renv", "renv", "renv.lock"),
-        Pattern::new("devbox", ".devbox", "devbox.json"),
+        Pattern::new("node", "node_modules", "package.json")
+            .described("Packages installed by npm, yarn or pnpm")
+            .tagged(&["javascript"])
+            .size_hint("100MB–2GB"),
+        Pattern::new("next", ".next", "package.json")
+            .described("Next.js build output and cache")
+            .tagged(&["javascript"])
+            .size_hint("50MB–1GB"),
+        Pattern::new("nuxt", ".nuxt", "package.json")
+            .described("Nuxt build output")
+            .tagged(&["javascript"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("svelte-kit", ".svelte-kit", "package.json")
+            .described("SvelteKit generated files and build output")
+            .tagged(&["javascript"])
+            .size_hint("10MB–200MB"),
+        Pattern::new("angular", ".angular", "package.json")
+            .described("Angular CLI build cache")
+            .tagged(&["javascript"])
+            .size_hint("50MB–1GB"),
+        Pattern::new("parcel", ".parcel-cache", "package.json")
+            .described("Parcel bundler cache")
+            .tagged(&["javascript"])
+            .size_hint("50MB–1GB"),
+        Pattern::new("turbo", ".turbo", "package.json")
+            .described("Turborepo task cache")
+            .tagged(&["javascript"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("vite-cache", ".vite", "package.json")
+            .described("Vite dependency pre-bundling cache")
+            .tagged(&["javascript"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("wrangler", ".wrangler", "package.json")
+            .described("Cloudflare Wrangler local state and build output")
+            .tagged(&["javascript"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("astro-cache", ".astro", "package.json")
+            .described("Astro content and asset cache")
+            .tagged(&["javascript"])
+            .size_hint("under 10MB"),
+        // Names other tools use too, so these only run when asked for
+        Pattern::new("js-cache", ".cache", "package.json")
+            .in_group("js-extras")
+            .off_by_default()
+            .described("Build tool caches in .cache (Gatsby, Babel, ESLint, ...)")
+            .tagged(&["javascript"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("js-coverage", "coverage", "package.json")
+            .in_group("js-extras")
+            .off_by_default()
+            .described("Test coverage reports (Jest, nyc, Vitest)")
+            .tagged(&["javascript"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("astro", "dist", "astro.config.mjs")
+            .described("Astro build output")
+            .tagged(&["javascript"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("remix", "build", "remix.config.js")
+            .described("Remix build output")
+            .tagged(&["javascript"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("vite", "dist", "vite.config.ts")
+            .requires(&["package.json"])
+            .described("Vite build output")
+            .tagged(&["javascript"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("storybook", "storybook-static", ".storybook/main.js")
+            .described("Static Storybook build")
+            .tagged(&["javascript"])
+            .size_hint("5MB–100MB"),
+        Pattern::new("bower", "bower_components", "bower.json")
+            .described("Packages installed by Bower")
+            .tagged(&["javascript"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("yarn", ".yarn", ".yarnrc.yml")
+            .described("Yarn Berry cache and install state")
+            .tagged(&["javascript"])
+            .size_hint("50MB–1GB"),
+        Pattern::new("composer", "vendor", "composer.json")
+            .described("PHP packages installed by Composer")
+            .tagged(&["php"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("bundler", "vendor", "Gemfile")
+            .described("Ruby gems vendored by Bundler")
+            .tagged(&["ruby"])
+            .size_hint("50MB–1GB"),
+        Pattern::new("cargo", "target", "Cargo.toml")
+            .described("Rust build artifacts from Cargo")
+            .tagged(&["rust"])
+            .size_hint("500MB–20GB"),
+        Pattern::new("go", "vendor", "go.mod")
+            .described("Go modules vendored by go mod vendor")
+            .tagged(&["go"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("maven", "target", "pom.xml")
+            .described("Maven build output")
+            .tagged(&["jvm"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("gradle", ".gradle", "build.gradle")
+            .described("Gradle project cache")
+            .tagged(&["jvm"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("gradle-kts", ".gradle", "build.gradle.kts")
+            .described("Gradle project cache (Kotlin build scripts)")
+            .tagged(&["jvm"])
+            .size_hint("10MB–500MB"),
+        // The root project's build output sits next to settings.gradle
+        Pattern::new("android-build", "build", "settings.gradle")
+            .described("Gradle root project build output")
+            .tagged(&["jvm", "mobile"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("android-build-kts", "build", "settings.gradle.kts")
+            .described("Gradle root project build output (Kotlin build scripts)")
+            .tagged(&["jvm", "mobile"])
+            .size_hint("10MB–1GB"),
+        // Android Gradle builds leave intermediates/ in every module's build
+        // directory, which sets them apart from other tools' `build`
+        Pattern::new("android-module-build", "build", "intermediates")
+            .sentinel_inside()
+            .sentinel_dir()
+            .described("Android module build output")
+            .tagged(&["mobile"])
+            .size_hint("100MB–5GB"),
+        Pattern::new("android-cxx", ".cxx", "build.gradle")
+            .or_sentinels(&["build.gradle.kts"])
+            .described("Android native code (CMake, ndk-build) intermediates")
+            .tagged(&["mobile"])
+            .size_hint("100MB–2GB"),
+        Pattern::new("expo", ".expo", "package.json")
+            .described("Expo (React Native) local project state")
+            .tagged(&["javascript", "mobile"])
+            .size_hint("under 10MB"),
+        Pattern::new("sbt", "target", "build.sbt")
+            .described("Scala build output from sbt")
+            .tagged(&["jvm"])
+            .size_hint("50MB–2GB"),
+        Pattern::new("swift", ".build", "Package.swift")
+            .described("Swift Package Manager build output and checkouts")
+            .tagged(&["apple"])
+            .size_hint("100MB–5GB"),
+        Pattern::new("cocoapods", "Pods", "Podfile")
+            .described("iOS and macOS dependencies installed by CocoaPods")
+            .tagged(&["apple"])
+            .size_hint("100MB–2GB"),
+        Pattern::new("carthage", "Carthage", "Cartfile")
+            .described("iOS and macOS dependencies built by Carthage")
+            .tagged(&["apple"])
+            .size_hint("100MB–5GB"),
+        Pattern::new("xcode-build", "build", "*.xcodeproj")
+            .sentinel_dir()
+            .described("Xcode build output next to the project")
+            .tagged(&["apple"])
+            .size_hint("100MB–5GB"),
+        Pattern::new("flutter", ".dart_tool", "pubspec.yaml")
+            .described("Dart and Flutter tool cache and generated files")
+            .tagged(&["dart", "mobile"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("pub", ".packages", "pubspec.yaml")
+            .described("Dart package map written by older SDKs")
+            .tagged(&["dart"])
+            .size_hint("under 1MB"),
+        Pattern::new("python-venv", ".venv", "pyvenv.cfg")
+            .sentinel_inside()
+            .described("Python virtual environments")
+            .tagged(&["python"])
+            .size_hint("50MB–2GB"),
+        Pattern::new("python-tox", ".tox", "tox.ini")
+            .described("Environments created by tox")
+            .tagged(&["python"])
+            .size_hint("100MB–2GB"),
+        Pattern::new("python-nox", ".nox", "noxfile.py")
+            .described("Environments created by nox")
+            .tagged(&["python"])
+            .size_hint("100MB–2GB"),
+        Pattern::new("elixir-deps", "deps", "mix.exs")
+            .described("Elixir dependencies fetched by Mix")
+            .tagged(&["elixir"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("elixir-build", "_build", "mix.exs")
+            .described("Elixir compiled build output")
+            .tagged(&["elixir"])
+            .size_hint("50MB–2GB"),
+        Pattern::new("mix-release", "_rel", "mix.exs")
+            .described("Elixir releases (Distillery, relx)")
+            .tagged(&["elixir"])
+            .size_hint("20MB–500MB"),
+        Pattern::new("phoenix-static", "priv/static", "mix.exs")
+            .requires(&["assets/"])
+            .described("Phoenix compiled static assets")
+            .tagged(&["elixir"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("rebar3", "_build", "rebar.config")
+            .described("Erlang build output from rebar3")
+            .tagged(&["erlang"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("haskell", ".stack-work", "stack.yaml")
+            .described("Haskell Stack build output")
+            .tagged(&["haskell"])
+            .size_hint("500MB–10GB"),
+        Pattern::new("vagrant", ".vagrant", "Vagrantfile")
+            .described("Vagrant machine state")
+            .tagged(&["infra"])
+            .size_hint("under 10MB"),
+        Pattern::new("terraform", ".terraform", ".terraform.lock.hcl")
+            .described("Terraform providers and modules")
+            .tagged(&["infra"])
+            .size_hint("50MB–1GB"),
+        Pattern::new("terragrunt", ".terragrunt-cache", "terragrunt.hcl")
+            .described("Terragrunt's copies of Terraform modules")
+            .tagged(&["infra"])
+            .size_hint("100MB–5GB"),
+        Pattern::new("cdk", "cdk.out", "cdk.json")
+            .described("AWS CDK synthesized CloudFormation output")
+            .tagged(&["infra"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("dotnet-bin", "bin", "*.csproj")
+            .described(".NET build output")
+            .tagged(&["dotnet"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("dotnet-obj", "obj", "*.csproj")
+            .described(".NET intermediate build files")
+            .tagged(&["dotnet"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("zig", "zig-cache", "build.zig")
+            .described("Zig build cache")
+            .tagged(&["zig"])
+            .size_hint("100MB–5GB"),
+        Pattern::new("ocaml", "_build", "dune-project")
+            .described("OCaml build output from dune")
+            .tagged(&["ocaml"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("godot", ".godot", "project.godot")
+            .described("Godot imported assets and editor cache")
+            .tagged(&["gamedev"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("clojure", ".cpcache", "deps.edn")
+            .described("Clojure CLI classpath cache")
+            .tagged(&["jvm"])
+            .size_hint("under 1MB"),
+        Pattern::new("renv", "renv", "renv.lock")
+            .described("R packages installed by renv")
+            .tagged(&["r", "data-science"])
+            .size_hint("100MB–2GB"),
+        Pattern::new("devbox", ".devbox", "devbox.json")
+            .described("Devbox Nix environment state")
+            .tagged(&["infra"])
+            .size_hint("1MB–100MB"),
+        // `public` is a common name, so Hugo and Zola sites also need their
+        // content folder. Both use config.toml; `hugo new site` adds archetypes/
+        // and Zola keeps templates/ where Hugo has layouts/
+        Pattern::new("hugo", "public", "hugo.toml")
+            .or_sentinels(&["hugo.yaml", "hugo.json"])
+            .requires(&["content/"])
+            .described("Hugo site output")
+            .tagged(&["static-sites"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("hugo-legacy", "public", "config.toml")
+            .requires(&["content/", "archetypes/"])
+            .described("Hugo site output (config.toml sites)")
+            .tagged(&["static-sites"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("hugo-resources", "resources/_gen", "hugo.toml")
+            .or_sentinels(&["hugo.yaml", "hugo.json", "config.toml"])
+            .described("Hugo processed images and assets")
+            .tagged(&["static-sites"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("zola", "public", "config.toml")
+            .requires(&["content/", "templates/"])
+            .described("Zola site output")
+            .tagged(&["static-sites"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("jekyll", "_site", "_config.yml")
+            .described("Jekyll site output")
+            .tagged(&["ruby", "static-sites"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("jekyll-cache", ".jekyll-cache", "_config.yml")
+            .described("Jekyll build cache")
+            .tagged(&["ruby", "static-sites"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("eleventy", "_site", ".eleventy.js")
+            .or_sentinels(&["eleventy.config.js", "eleventy.config.mjs", "eleventy.config.cjs"])
+            .described("Eleventy site output")
+            .tagged(&["javascript", "static-sites"])
+            .size_hint("1MB–500MB"),
+        Pattern::new("docusaurus", ".docusaurus", "docusaurus.config.js")
+            .or_sentinels(&["docusaurus.config.ts"])
+            .described("Docusaurus generated files and cache")
+            .tagged(&["javascript", "static-sites"])
+            .size_hint("1MB–100MB"),
+        // `build` is too common a name to go by CMakeLists.txt alone, but only
+        // CMake writes a CMakeCache.txt into its build tree
+        Pattern::new("cmake", "build", "CMakeCache.txt")
+            .sentinel_inside()
+            .described("CMake build trees")
+            .tagged(&["cpp"])
+            .size_hint("50MB–5GB"),
+        Pattern::new("cmake-clion", "cmake-build-*", "CMakeLists.txt")
+            .described("CLion's CMake build directories")
+            .tagged(&["cpp"])
+            .size_hint("50MB–5GB"),
+        Pattern::new("cmake-presets", "out", "CMakePresets.json")
+            .requires(&["CMakeLists.txt"])
+            .described("CMake build directories from presets (Visual Studio, VS Code)")
+            .tagged(&["cpp"])
+            .size_hint("50MB–5GB"),
+        Pattern::new("clangd", ".cache/clangd", "CMakeLists.txt")
+            .or_sentinels(&["compile_commands.json"])
+            .described("clangd index cache")
+            .tagged(&["cpp"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("meson", "builddir", "meson.build")
+            .described("Meson build directory")
+            .tagged(&["cpp"])
+            .size_hint("50MB–5GB"),
+        // Unity projects have no single telltale file, so require both folders
+        Pattern::new("unity", "Library", "ProjectSettings")
+            .sentinel_dir()
+            .requires(&["Assets/"])
+            .described("Unity imported assets and caches")
+            .tagged(&["gamedev"])
+            .size_hint("1GB–20GB"),
+        Pattern::new("unity-temp", "Temp", "ProjectSettings")
+            .sentinel_dir()
+            .requires(&["Assets/"])
+            .described("Unity temporary build files")
+            .tagged(&["gamedev"])
+            .size_hint("10MB–1GB"),
+        Pattern::new("unity-obj", "obj", "ProjectSettings")
+            .sentinel_dir()
+            .requires(&["Assets/"])
+            .described("Unity script compilation output")
+            .tagged(&["gamedev"])
+            .size_hint("1MB–100MB"),
+        Pattern::new("unity-logs", "Logs", "ProjectSettings")
+            .sentinel_dir()
+            .requires(&["Assets/"])
+            .described("Unity editor logs")
+            .tagged(&["gamedev"])
+            .size_hint("under 10MB"),
+        // __pycache__ sits in every package, well below the project root
+        Pattern::new("python-pycache", "__pycache__", "pyproject.toml")
+            .or_sentinels(&["setup.py", "setup.cfg"])
+            .sentinel_depth(10)
+            .in_group("python-caches")
+            .described("Compiled Python bytecode")
+            .tagged(&["python"])
+            .size_hint("under 1MB"),
+        Pattern::new("python-pytest", ".pytest_cache", "pyproject.toml")
+            .or_sentinels(&["setup.py", "setup.cfg"])
+            .in_group("python-caches")
+            .described("pytest cache")
+            .tagged(&["python"])
+            .size_hint("under 1MB"),
+        Pattern::new("python-mypy", ".mypy_cache", "pyproject.toml")
+            .or_sentinels(&["setup.py", "setup.cfg"])
+            .in_group("python-caches")
+            .described("mypy type checking cache")
+            .tagged(&["python"])
+            .size_hint("10MB–500MB"),
+        Pattern::new("python-ruff", ".ruff_cache", "pyproject.toml")
+            .or_sentinels(&["setup.py", "setup.cfg"])
+            .in_group("python-caches")
+            .described("Ruff linter cache")
+            .tagged(&["python"])
+            .size_hint("under 10MB"),
+        Pattern::new("jupyter", ".ipynb_checkpoints", "*.ipynb")
+            .described("Jupyter notebook checkpoints")
+            .tagged(&["python", "data-science"])
+            .size_hint("under 10MB"),
+        Pattern::new("wandb", "wandb", "requirements.txt")
+            .or_sentinels(&["pyproject.toml", "environment.yml"])
+            .described("Weights & Biases local run logs")
+            .tagged(&["python", "data-science"])
+            .size_hint("10MB–5GB"),
+        Pattern::new("mlflow", "mlruns", "requirements.txt")
+            .or_sentinels(&["pyproject.toml", "environment.yml"])
+            .described("MLflow local experiment tracking runs")
+            .tagged(&["python", "data-science"])
+            .size_hint("10MB–10GB"),
+        // Every conda install has conda-meta/ next to its envs, so a stray envs
+        // folder isn't matched
+        Pattern::new("conda-envs", "envs", "conda-meta")
+            .sentinel_dir()
+            .described("Conda environments")
+            .tagged(&["python", "data-science"])
+            .size_hint("1GB–20GB"),
+        // bazel-out, bazel-bin, bazel-<workspace>, ... all point into the output base
+        Pattern::link("bazel", "bazel-*", "WORKSPACE*", "execroot")
+            .described("Bazel output base (WORKSPACE projects)")
+            .tagged(&["bazel"])
+            .size_hint("1GB–50GB"),
+        Pattern::link("bazel-module", "bazel-*", "MODULE.bazel", "execroot")
+            .described("Bazel output base (Bzlmod projects)")
+            .tagged(&["bazel"])
+            .size_hint("1GB–50GB"),
+        Pattern::file("vm-qcow2", "*.qcow2", 1 << 30)
+            .described("QEMU and UTM disk images over 1GB")
+            .tagged(&["vm"])
+            .size_hint("1GB–100GB"),
+        Pattern::file("vm-vmdk", "*.vmdk", 1 << 30)
+            .described("VMware disk images over 1GB")
+            .tagged(&["vm"])
+            .size_hint("1GB–100GB"),
+        Pattern::file("vm-vdi", "*.vdi", 1 << 30)
+            .described("VirtualBox disk images over 1GB")
+            .tagged(&["vm"])
+            .size_hint("1GB–100GB"),
     ]
 }
 
-/// Resolve active patterns: built-ins minus disabled, plus custom patterns.
-pub fn resolve_patterns(disable: &[String], custom: &[CustomPattern]) -> Vec<Pattern> {
-    let mut patterns: Vec<Pattern> = builtin_patterns()
+/// Resolve active patterns: built-ins that are on by default or enabled, minus
+/// disabled ones (both by name or group), plus custom patterns. Patterns of
+/// either kind carrying a disabled tag are left out.
+pub fn resolve_patterns(
+    disable: &[String],
+    enable: &[String],
+    disable_tags: &[String],
+    custom: &[CustomPattern],
+) -> Vec<Pattern> {
+    builtin_patterns()
         .into_iter()
-        .filter(|p| !disable.iter().any(|d| d == &p.name))
-        .collect();
+        .filter(|p| p.enabled_by_default || enable.iter().any(|e| p.selected_by(e)))
+        .filter(|p| !disable.iter().any(|d| p.selected_by(d)))
+        .chain(custom.iter().map(Pattern::from))
+        .filter(|p| !disable_tags.iter().any(|t| p.has_tag(t)))
+        .collect()
+}
 
-    for cp in custom {
-        patterns.push(Pattern::from(cp));
+/// Resolve a config's active patterns, with its `pattern_min_size` overrides
+/// applied.
+pub fn config_patterns(config: &Config) -> Vec<Pattern> {
+    let mut patterns = resolve_patterns(
+        &config.disable_patterns,
+        &config.enable_patterns,
+        &config.disable_tags,
+        &config.custom_patterns,
+    );
+    for p in &mut patterns {
+        if let Some(size) = config.pattern_min_size.get(&p.name) {
+            p.min_size = size.0;
+        }
     }
-
     patterns
 }
 
+/// `patterns` as they apply under a `root_patterns` path: without the ones it
+/// disables, plus its own custom patterns.
+pub fn scoped_patterns(
+    config: &Config,
+    patterns: &[Pattern],
+    scope: &RootPatterns,
+) -> Vec<Pattern> {
+    let mut scoped: Vec<Pattern> = patterns
+        .iter()
+        .filter(|p| !scope.disable_patterns.iter().any(|name| p.selected_by(name)))
+        .cloned()
+        .collect();
+    for custom in &scope.custom_patterns {
+        let mut p = Pattern::from(custom);
+        if let Some(size) = config.pattern_min_size.get(&p.name) {
+            p.min_size = size.0;
+        }
+        scoped.push(p);
+    }
+    scoped
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,29 +740,178 @@ mod tests {
 
     #[test]
     fn test_resolve_patterns_disable() {
-        let patterns = resolve_patterns(&["node".to_string(), "cargo".to_string()], &[]);
+        let patterns = resolve_patterns(&["node".to_string(), "cargo".to_string()], &[], &[], &[]);
         assert!(!patterns.iter().any(|p| p.name == "node"));
         assert!(!patterns.iter().any(|p| p.name == "cargo"));
         assert!(patterns.iter().any(|p| p.name == "next"));
     }
 
+    #[test]
+    fn test_resolve_patterns_disable_group() {
+        let patterns = resolve_patterns(&["python-caches".to_string()], &[], &[], &[]);
+        assert!(!patterns.iter().any(|p| p.group.as_deref() == Some("python-caches")));
+        assert!(patterns.iter().any(|p| p.name == "python-venv"));
+
+        let patterns = resolve_patterns(&["python-mypy".to_string()], &[], &[], &[]);
+        assert!(!patterns.iter().any(|p| p.name == "python-mypy"));
+        assert!(patterns.iter().any(|p| p.name == "python-ruff"));
+    }
+
+    #[test]
+    fn test_resolve_patterns_disable_tags() {
+        let mut custom = CustomPattern::new("my-bundle", "bundle", "webpack.config.js");
+        custom.tags = vec!["javascript".to_string()];
+        let patterns = resolve_patterns(&[], &[], &["javascript".to_string()], &[custom]);
+        assert!(!patterns.iter().any(|p| p.has_tag("javascript")));
+        assert!(!patterns.iter().any(|p| p.name == "node" || p.name == "my-bundle"));
+        assert!(patterns.iter().any(|p| p.name == "cargo"));
+        // Patterns with another tag too are still dropped
+        assert!(!patterns.iter().any(|p| p.name == "expo"));
+        assert!(patterns.iter().any(|p| p.name == "android-cxx"));
+    }
+
+    #[test]
+    fn test_every_builtin_is_tagged() {
+        for p in builtin_patterns() {
+            assert!(!p.tags.is_empty(), "{} has no tags", p.name);
+            assert!(p.description.is_some(), "{} has no description", p.name);
+        }
+    }
+
+    #[test]
+    fn test_resolve_patterns_category_selectors() {
+        let strings = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let disable = strings(&["category:jvm", "node", "python-caches"]);
+        let patterns = resolve_patterns(&disable, &[], &[], &[]);
+        let has = |name: &str| patterns.iter().any(|p| p.name == name);
+        assert!(!has("maven") && !has("clojure") && !has("android-build"));
+        assert!(!has("node") && !has("python-pytest"));
+        assert!(has("next") && has("cargo") && has("python-venv"));
+
+        // Only the first tag counts: expo is tagged mobile, but is javascript
+        let patterns = resolve_patterns(&strings(&["category:mobile"]), &[], &[], &[]);
+        assert!(!patterns.iter().any(|p| p.name == "android-cxx"));
+        assert!(patterns.iter().any(|p| p.name == "expo"));
+
+        // Categories select off-by-default patterns for enable_patterns too
+        let enabled = resolve_patterns(&[], &strings(&["category:javascript"]), &[], &[]);
+        assert!(enabled.iter().any(|p| p.name == "js-coverage"));
+        assert!(resolve_patterns(&strings(&["category:cobol"]), &[], &[], &[]).len() > 80);
+    }
+
+    #[test]
+    fn test_resolve_patterns_enable_group() {
+        let in_js_extras = |p: &Pattern| p.group.as_deref() == Some("js-extras");
+        assert!(!resolve_patterns(&[], &[], &[], &[]).iter().any(in_js_extras));
+
+        let enable = ["js-extras".to_string()];
+        let patterns = resolve_patterns(&[], &enable, &[], &[]);
+        assert_eq!(patterns.iter().filter(|p| in_js_extras(p)).count(), 2);
+
+        let patterns = resolve_patterns(&[], &["js-coverage".to_string()], &[], &[]);
+        assert!(patterns.iter().any(|p| p.name == "js-coverage"));
+        assert!(!patterns.iter().any(|p| p.name == "js-cache"));
+
+        // Disabling wins over enabling
+        let patterns = resolve_patterns(&["js-cache".to_string()], &enable, &[], &[]);
+        assert!(patterns.iter().any(|p| p.name == "js-coverage"));
+        assert!(!patterns.iter().any(|p| p.name == "js-cache"));
+    }
+
     #[test]
     fn test_resolve_patterns_custom() {
         let custom = vec![CustomPattern {
             name: "my-build".to_string(),
             directory: "dist".to_string(),
             sentinel: "turbo.json".to_string(),
+            match_anywhere: false,
+            sentinel_location: SentinelLocation::Parent,
+            sentinel_type: SentinelType::Any,
+            all_sentinels: Vec::new(),
+            kind: PatternKind::Directory,
+            sentinel_depth: None,
+            min_size: None,
+            older_than_days: None,
+            descend: false,
+            expected_size_hint: None,
+            tags: Vec::new(),
+            description: None,
         }];
-        let patterns = resolve_patterns(&[], &custom);
+        let patterns = resolve_patterns(&[], &[], &[], &custom);
         assert!(patterns.iter().any(|p| p.name == "my-build"));
     }
 
+    #[test]
+    fn test_config_patterns_apply_min_size_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+disable_patterns = ["node"]
+pattern_min_size = { cargo = "500MB", vm-vdi = "4GB" }
+"#,
+        )
+        .unwrap();
+        let patterns = config_patterns(&config);
+        let min_size = |name: &str| patterns.iter().find(|p| p.name == name).map(|p| p.min_size);
+        assert_eq!(min_size("cargo"), Some(500 << 20));
+        assert_eq!(min_size("vm-vdi"), Some(4 << 30));
+        assert_eq!(min_size("vm-vmdk"), Some(1 << 30));
+        assert_eq!(min_size("go"), Some(0));
+        assert_eq!(min_size("node"), None);
+    }
+
+    #[test]
+    fn test_project_root_glob() {
+        use std::path::Path;
+
+        let pattern = Pattern::new("cmake-clion", "cmake-build-*", "CMakeLists.txt");
+        assert_eq!(pattern.leaf_name(), "cmake-build-*");
+        assert_eq!(
+            pattern.project_root(Path::new("/code/app/cmake-build-debug")),
+            Some(Path::new("/code/app"))
+        );
+        assert_eq!(pattern.project_root(Path::new("/code/app/build-debug")), None);
+
+        let nested = Pattern::new("nested", "out/cmake-*", "CMakeLists.txt");
+        assert_eq!(
+            nested.project_root(Path::new("/code/app/out/cmake-release")),
+            Some(Path::new("/code/app"))
+        );
+        assert_eq!(nested.project_root(Path::new("/code/app/cmake-release")), None);
+    }
+
+    #[test]
+    fn test_project_root_multi_component() {
+        use std::path::Path;
+
+        let pattern = Pattern::new("phoenix-static", "priv/static", "mix.exs");
+        assert_eq!(pattern.leaf_name(), "static");
+        assert_eq!(
+            pattern.project_root(Path::new("/code/app/priv/static")),
+            Some(Path::new("/code/app"))
+        );
+        assert_eq!(pattern.project_root(Path::new("/code/app/public/static")), None);
+
+        let single = Pattern::new("node", "node_modules", "package.json");
+        assert_eq!(
+            single.project_root(Path::new("/code/app/node_modules")),
+            Some(Path::new("/code/app"))
+        );
+    }
+
     #[test]
     fn test_all_patterns_have_fields() {
         for p in builtin_patterns() {
             assert!(!p.name.is_empty(), "Pattern has empty name");
             assert!(!p.directory.is_empty(), "Pattern {} has empty directory", p.name);
-            assert!(!p.sentinel.is_empty(), "Pattern {} has empty sentinel", p.name);
+            assert!(p.expected_size_hint.is_some(), "Pattern {} has no size hint", p.name);
+            match p.kind {
+                PatternKind::Directory | PatternKind::Link => {
+                    assert!(!p.sentinel.is_empty(), "Pattern {} has empty sentinel", p.name)
+                }
+                PatternKind::File => {
+                    assert!(p.min_size > 0, "File pattern {} has no min_size", p.name)
+                }
+            }
         }
     }
 }
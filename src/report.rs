@@ -0,0 +1,351 @@
+use crate::state::ExcludedEntry;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// What a run did, written out by `tmignore run --report`.
+#[derive(Debug)]
+pub struct RunReport<'a> {
+    pub run_at: &'a str,
+    pub dry_run: bool,
+    /// The scan stopped early (time budget), so some matches may be missing.
+    pub partial: bool,
+    /// Matches per pattern name, including patterns that matched nothing.
+    pub pattern_counts: &'a BTreeMap<String, u64>,
+    pub excluded: &'a [ExcludedEntry],
+    pub bytes_saved: u64,
+    /// Display paths of matches that were already excluded.
+    pub already_excluded: &'a [String],
+    pub errors: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// The format a report path asks for, from its extension.
+    pub fn for_path(path: &Path) -> Result<Self> {
+        let ext = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("md" | "markdown") => Ok(ReportFormat::Markdown),
+            Some("html" | "htm") => Ok(ReportFormat::Html),
+            _ => anyhow::bail!(
+                "Can't tell the report format of {}: use a .md or .html file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Parse a `--report` path, rejecting extensions without a report format up
+/// front instead of after the run.
+pub fn parse_report_path(input: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(input);
+    ReportFormat::for_path(&path)?;
+    Ok(path)
+}
+
+/// Patterns with at least one match, most matches first.
+fn fired_patterns<'a>(report: &RunReport<'a>) -> Vec<(&'a str, u64)> {
+    let mut fired: Vec<(&str, u64)> = report
+        .pattern_counts
+        .iter()
+        .filter(|(_, count)| **count > 0)
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    fired.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    fired
+}
+
+/// Excluded entries grouped by the pattern that decided them, in name order.
+fn by_pattern<'a>(report: &RunReport<'a>) -> BTreeMap<&'a str, Vec<&'a ExcludedEntry>> {
+    let mut groups: BTreeMap<&str, Vec<&ExcludedEntry>> = BTreeMap::new();
+    for entry in report.excluded {
+        groups.entry(entry.pattern.as_str()).or_default().push(entry);
+    }
+    groups
+}
+
+fn title(report: &RunReport) -> String {
+    let kind = if report.dry_run { "dry run" } else { "run" };
+    format!("tmignore {} report", kind)
+}
+
+fn summary_line(report: &RunReport) -> String {
+    format!(
+        "{} {} ({}), {} already excluded, {} errors{}",
+        report.excluded.len(),
+        if report.dry_run { "would be excluded" } else { "newly excluded" },
+        crate::size::format_size(report.bytes_saved),
+        report.already_excluded.len(),
+        report.errors,
+        if report.partial { " (partial scan)" } else { "" }
+    )
+}
+
+/// Other patterns that matched too, e.g. " (also bundler)".
+fn also_matched(entry: &ExcludedEntry) -> String {
+    if entry.also_matched.is_empty() {
+        String::new()
+    } else {
+        format!(" (also {})", entry.also_matched.join(", "))
+    }
+}
+
+/// Escape characters Markdown would otherwise treat as formatting, which turn
+/// up in paths (`_site`, `__pycache__`).
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>|#".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn render_markdown(report: &RunReport) -> String {
+    let mut out = String::new();
+    let md = escape_markdown;
+    let _ = writeln!(out, "# {}\n", title(report));
+    let _ = writeln!(out, "- Run at: {}", report.run_at);
+    let _ = writeln!(out, "- Summary: {}", summary_line(report));
+    let _ = writeln!(out, "- Size saved: {}", crate::size::format_size(report.bytes_saved));
+
+    out.push_str("\n## Pattern matches\n\n");
+    let fired = fired_patterns(report);
+    if fired.is_empty() {
+        out.push_str("No pattern matched.\n");
+    } else {
+        out.push_str("| Pattern | Matches |\n| --- | --- |\n");
+        for (name, count) in fired {
+            let _ = writeln!(out, "| {} | {} |", md(name), count);
+        }
+    }
+
+    out.push_str(if report.dry_run { "\n## Would be excluded\n" } else { "\n## Excluded\n" });
+    if report.excluded.is_empty() {
+        out.push_str("\nNothing.\n");
+    }
+    for (pattern, entries) in by_pattern(report) {
+        let _ = writeln!(out, "\n### {}\n", md(pattern));
+        for entry in entries {
+            let _ = writeln!(
+                out,
+                "- {} ({}){}",
+                md(&entry.path),
+                crate::size::format_size(entry.size),
+                md(&also_matched(entry))
+            );
+        }
+    }
+
+    out.push_str("\n## Already excluded\n\n");
+    if report.already_excluded.is_empty() {
+        out.push_str("Nothing.\n");
+    }
+    for path in report.already_excluded {
+        let _ = writeln!(out, "- {}", md(path));
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A standalone page with a little inline CSS and no scripts.
+pub fn render_html(report: &RunReport) -> String {
+    let mut out = String::new();
+    let h = escape_html;
+    let title = title(report);
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>", h(&title));
+    out.push_str(
+        "<style>\nbody { font-family: -apple-system, sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.25em 0.75em; text-align: left; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    let _ = writeln!(out, "<h1>{}</h1>\n<ul>", h(&title));
+    let _ = writeln!(out, "<li>Run at: {}</li>", h(report.run_at));
+    let _ = writeln!(out, "<li>Summary: {}</li>", h(&summary_line(report)));
+    let _ = writeln!(
+        out,
+        "<li>Size saved: {}</li>\n</ul>",
+        crate::size::format_size(report.bytes_saved)
+    );
+
+    out.push_str("<h2>Pattern matches</h2>\n");
+    let fired = fired_patterns(report);
+    if fired.is_empty() {
+        out.push_str("<p>No pattern matched.</p>\n");
+    } else {
+        out.push_str("<table>\n<tr><th>Pattern</th><th>Matches</th></tr>\n");
+        for (name, count) in fired {
+            let _ = writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", h(name), count);
+        }
+        out.push_str("</table>\n");
+    }
+
+    let _ = writeln!(
+        out,
+        "<h2>{}</h2>",
+        if report.dry_run { "Would be excluded" } else { "Excluded" }
+    );
+    if report.excluded.is_empty() {
+        out.push_str("<p>Nothing.</p>\n");
+    }
+    for (pattern, entries) in by_pattern(report) {
+        let _ = writeln!(out, "<h3>{}</h3>\n<ul>", h(pattern));
+        for entry in entries {
+            let _ = writeln!(
+                out,
+                "<li>{} ({}){}</li>",
+                h(&entry.path),
+                h(&crate::size::format_size(entry.size)),
+                h(&also_matched(entry))
+            );
+        }
+        out.push_str("</ul>\n");
+    }
+
+    out.push_str("<h2>Already excluded</h2>\n");
+    if report.already_excluded.is_empty() {
+        out.push_str("<p>Nothing.</p>\n");
+    } else {
+        out.push_str("<ul>\n");
+        for path in report.already_excluded {
+            let _ = writeln!(out, "<li>{}</li>", h(path));
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Write the report to `path`, as Markdown or HTML depending on its extension.
+pub fn write(path: &Path, report: &RunReport) -> Result<()> {
+    let contents = match ReportFormat::for_path(path)? {
+        ReportFormat::Markdown => render_markdown(report),
+        ReportFormat::Html => render_html(report),
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, pattern: &str, also: &[&str], size: u64) -> ExcludedEntry {
+        ExcludedEntry {
+            path: path.to_string(),
+            pattern: pattern.to_string(),
+            also_matched: also.iter().map(|s| s.to_string()).collect(),
+            size,
+            files: None,
+        }
+    }
+
+    fn with_sample(check: impl FnOnce(&RunReport)) {
+        let counts = BTreeMap::from([
+            ("node".to_string(), 2),
+            ("composer".to_string(), 1),
+            ("cargo".to_string(), 0),
+        ]);
+        let excluded = vec![
+            entry("~/code/web/node_modules", "node", &[], 1288490189),
+            entry("~/code/api/vendor", "composer", &["bundler"], 48 << 20),
+        ];
+        let already = vec!["~/code/site/node_modules".to_string()];
+        check(&RunReport {
+            run_at: "2024-06-01T09:30:00Z",
+            dry_run: false,
+            partial: false,
+            pattern_counts: &counts,
+            excluded: &excluded,
+            bytes_saved: 1_300_000_000,
+            already_excluded: &already,
+            errors: 0,
+        });
+    }
+
+    #[test]
+    fn test_report_format_from_extension() {
+        assert_eq!(ReportFormat::for_path(Path::new("r.md")).unwrap(), ReportFormat::Markdown);
+        assert_eq!(ReportFormat::for_path(Path::new("r.HTML")).unwrap(), ReportFormat::Html);
+        assert!(parse_report_path("report.txt").is_err());
+        assert!(parse_report_path("report").is_err());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        with_sample(|report| {
+            let md = render_markdown(report);
+            assert!(md.starts_with("# tmignore run report\n"));
+            assert!(md.contains("- Run at: 2024-06-01T09:30:00Z\n"));
+            assert!(md.contains("| node | 2 |\n| composer | 1 |\n"));
+            // Patterns without matches are left out
+            assert!(!md.contains("cargo"));
+            assert!(md.contains("### composer\n\n- ~/code/api/vendor (48MB) (also bundler)\n"));
+            assert!(md.contains("### node\n\n- ~/code/web/node\\_modules (1.2GB)\n"));
+            assert!(md.contains("## Already excluded\n\n- ~/code/site/node\\_modules\n"));
+        });
+    }
+
+    #[test]
+    fn test_render_html_escapes_and_has_no_script() {
+        let counts = BTreeMap::new();
+        let excluded = vec![entry("~/a&b/<build>", "my-build", &[], 12 << 10)];
+        let html = render_html(&RunReport {
+            run_at: "2024-06-01T09:30:00Z",
+            dry_run: true,
+            partial: true,
+            pattern_counts: &counts,
+            excluded: &excluded,
+            bytes_saved: 1000,
+            already_excluded: &[],
+            errors: 1,
+        });
+        assert!(html.contains("<title>tmignore dry run report</title>"));
+        assert!(html.contains("<li>~/a&amp;b/&lt;build&gt; (12KB)</li>"));
+        assert!(html.contains("0 already excluded, 1 errors (partial scan)"));
+        assert!(html.contains("<p>No pattern matched.</p>"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn test_write_picks_format_by_extension() {
+        let dir = std::env::temp_dir().join("tmignore_test_report");
+        let _ = std::fs::remove_dir_all(&dir);
+        with_sample(|report| {
+            write(&dir.join("run.md"), report).unwrap();
+            write(&dir.join("run.html"), report).unwrap();
+            assert!(write(&dir.join("run.txt"), report).is_err());
+        });
+        let md = std::fs::read_to_string(dir.join("run.md")).unwrap();
+        assert!(md.starts_with("# "));
+        let html = std::fs::read_to_string(dir.join("run.html")).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
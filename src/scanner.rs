@@ -1,90 +1,1045 @@
-use crate::config::{expand_tilde, Config};
+use crate::config::{
+    expand_tilde, Config, MatchPolicy, PatternKind, SentinelLocation, SentinelType,
+};
 use crate::patterns::Pattern;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::ControlFlow;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::WalkDir;
 
-/// Check if a sentinel file exists in the given parent directory.
-/// Supports glob patterns (e.g., "*.csproj").
-fn sentinel_exists(parent: &Path, sentinel: &str) -> bool {
-    if sentinel.contains('*') || sentinel.contains('?') || sentinel.contains('[') {
+/// Check if a sentinel of the given type exists in the given parent directory.
+/// Supports glob patterns (e.g., "*.csproj"), which match if any matching entry
+/// has the right type. `compiled` is the sentinel's glob compiled ahead of time,
+/// if it has one.
+fn sentinel_exists(
+    parent: &Path,
+    sentinel: &str,
+    compiled: Option<&glob::Pattern>,
+    kind: SentinelType,
+) -> bool {
+    let accepts = |path: &Path| std::fs::metadata(path).is_ok_and(|m| kind.accepts(&m));
+    if let Some(glob) = compiled.filter(|_| !sentinel.contains('/')) {
+        // Single-component glob: match the directory's entries by name
+        let Ok(entries) = std::fs::read_dir(parent) else {
+            return false;
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_str().is_some_and(|name| glob.matches(name)))
+            .any(|e| accepts(&e.path()))
+    } else if crate::patterns::is_glob(sentinel) {
         // Glob pattern
         let pattern = parent.join(sentinel).to_string_lossy().to_string();
         if let Ok(entries) = glob::glob(&pattern) {
-            return entries.filter_map(|e| e.ok()).next().is_some();
+            return entries.filter_map(|e| e.ok()).any(|path| accepts(&path));
         }
         false
     } else {
-        // Exact file name
-        parent.join(sentinel).exists()
+        // Exact name
+        accepts(&parent.join(sentinel))
+    }
+}
+
+/// Sentinel lookups, remembered for one scan. Patterns that share a sentinel
+/// (`node_modules`, `.next` and `.turbo` all look for `package.json`) and
+/// workspace packages that share a root only hit the filesystem once per directory.
+#[derive(Debug, Default)]
+struct SentinelCache {
+    found: HashMap<(PathBuf, String, SentinelType), bool>,
+    /// Lookups that went to the filesystem.
+    checks: usize,
+}
+
+impl SentinelCache {
+    fn exists(
+        &mut self,
+        dir: &Path,
+        sentinel: &str,
+        compiled: Option<&glob::Pattern>,
+        kind: SentinelType,
+    ) -> bool {
+        let checks = &mut self.checks;
+        *self
+            .found
+            .entry((dir.to_path_buf(), sentinel.to_string(), kind))
+            .or_insert_with(|| {
+                *checks += 1;
+                sentinel_exists(dir, sentinel, compiled, kind)
+            })
+    }
+}
+
+/// Where a pattern looks for its sentinels first: the project root, the
+/// candidate itself for "inside" patterns, or the directory holding a matched file.
+fn sentinel_base<'a>(path: &'a Path, pattern: &Pattern) -> Option<&'a Path> {
+    match (pattern.kind, pattern.sentinel_location) {
+        (PatternKind::File | PatternKind::Link, _) => path.parent(),
+        (_, SentinelLocation::Parent) => pattern.project_root(path),
+        (_, SentinelLocation::Inside) => Some(path),
+    }
+}
+
+/// Check whether a candidate directory satisfies a pattern's sentinel requirement.
+/// All sentinels must be found in the same directory: the base or, with
+/// `sentinel_depth` above 1, one of its nearest ancestors.
+fn pattern_matches(path: &Path, pattern: &Pattern, sentinels: &mut SentinelCache) -> bool {
+    // File patterns, and custom patterns with match_anywhere, match on name (and
+    // size) alone
+    if pattern.sentinel.is_empty() {
+        return true;
+    }
+    let Some(base) = sentinel_base(path, pattern) else {
+        return false;
+    };
+    base.ancestors()
+        .take(pattern.sentinel_depth.max(1))
+        .any(|dir| {
+            (sentinels.exists(
+                dir,
+                &pattern.sentinel,
+                pattern.sentinel_glob.as_ref(),
+                pattern.sentinel_type,
+            ) || pattern
+                .any_sentinels
+                .iter()
+                .any(|s| sentinels.exists(dir, s, None, pattern.sentinel_type)))
+                && pattern
+                .all_sentinels
+                .iter()
+                .all(|s| sentinels.exists(dir, s, None, SentinelType::Any))
+        })
+}
+
+/// Most recent modification time of a directory: its own mtime or that of any
+/// immediate child, whichever is newer. Rebuilding a project touches these even
+/// when the directory entry itself is unchanged.
+pub fn last_modified(path: &Path) -> Option<SystemTime> {
+    let own = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    // A directory that can't be listed still has an age of its own
+    let newest_child = std::fs::read_dir(path).ok().and_then(|entries| {
+        entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().and_then(|m| m.modified()).ok())
+            .max()
+    });
+    own.max(newest_child)
+}
+
+/// Configured directories (skip or exclude paths) that also cover everything
+/// below them. Holds both the literal and the resolved form of each path, since
+/// roots are walked in resolved form. Lookups hash each ancestor of a path, so
+/// they cost O(depth) regardless of how many paths are in the set.
+#[derive(Debug, Default)]
+struct PathSet(HashSet<PathBuf>);
+
+impl PathSet {
+    /// Expand configured paths into both their literal and resolved forms.
+    fn resolve<'a>(paths: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut set = HashSet::new();
+        for p in paths {
+            // Unexpandable paths can't match anything on disk; the exclude-path
+            // pass reports them.
+            let Ok(path) = expand_tilde(p) else { continue };
+            if let Ok(resolved) = path.canonicalize() {
+                set.insert(resolved);
+            }
+            set.insert(path);
+        }
+        Self(set)
+    }
+
+    /// Whether `path` is in the set or below a path that is, looking no higher
+    /// than `root`: a root inside a skip path is walked anyway, and so is the
+    /// root itself.
+    fn covers(&self, path: &Path, root: &Path) -> bool {
+        path.ancestors()
+            .take_while(|a| *a != root)
+            .any(|a| self.0.contains(a))
+    }
+
+    /// The closest path in the set that contains (or is) `path`.
+    fn enclosing<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.ancestors().find(|a| self.0.contains(*a))
     }
 }
 
-/// Build the set of directories to skip during scanning.
-fn build_skip_set(config: &Config) -> HashSet<PathBuf> {
-    config.resolved_skip_paths().iter().map(|p| expand_tilde(p)).collect()
+fn build_skip_set(config: &Config, options: &ScanOptions) -> PathSet {
+    PathSet::resolve(config.resolved_skip_paths().iter().chain(&options.extra_skip_paths))
 }
 
-/// Build a lookup of directory name -> list of patterns for fast matching.
-fn build_directory_index(patterns: &[Pattern]) -> std::collections::HashMap<String, Vec<&Pattern>> {
-    let mut index: std::collections::HashMap<String, Vec<&Pattern>> = std::collections::HashMap::new();
-    for p in patterns {
-        index.entry(p.directory.clone()).or_default().push(p);
+/// Directory patterns by the directory name they match, most specific first.
+struct DirectoryIndex<'a> {
+    by_name: HashMap<String, Vec<&'a Pattern>>,
+    /// Patterns whose directory name is a glob (e.g. "cmake-build-*").
+    globbed: Vec<&'a Pattern>,
+}
+
+impl<'a> DirectoryIndex<'a> {
+    /// Patterns that may match a directory with this name.
+    fn candidates(&self, name: &str) -> Option<Cow<'_, [&'a Pattern]>> {
+        let exact = self.by_name.get(name).map_or(&[][..], Vec::as_slice);
+        let globbed: Vec<&Pattern> = self
+            .globbed
+            .iter()
+            .copied()
+            .filter(|p| p.directory_glob.as_ref().is_some_and(|g| g.matches(name)))
+            .collect();
+        if globbed.is_empty() {
+            return (!exact.is_empty()).then_some(Cow::Borrowed(exact));
+        }
+        let mut all = exact.to_vec();
+        all.extend(globbed);
+        all.sort_by_key(|p| std::cmp::Reverse(p.specificity()));
+        Some(Cow::Owned(all))
+    }
+}
+
+fn build_directory_index(patterns: &[Pattern]) -> DirectoryIndex<'_> {
+    let mut index = DirectoryIndex {
+        by_name: HashMap::new(),
+        globbed: Vec::new(),
+    };
+    for p in patterns.iter().filter(|p| p.kind == PatternKind::Directory) {
+        if p.directory_glob.is_some() {
+            index.globbed.push(p);
+        } else {
+            index.by_name.entry(p.leaf_name().to_string()).or_default().push(p);
+        }
+    }
+    // Most specific first (see `Pattern::specificity`); the sort is stable, so
+    // ties keep their declaration order
+    for candidates in index.by_name.values_mut() {
+        candidates.sort_by_key(|p| std::cmp::Reverse(p.specificity()));
     }
     index
 }
 
+/// Patterns indexed the ways the walk looks them up.
+struct PatternIndex<'a> {
+    directories: DirectoryIndex<'a>,
+    files: Vec<(glob::Pattern, &'a Pattern)>,
+    links: Vec<(glob::Pattern, &'a Pattern)>,
+    hidden_allowed: HiddenPatternDirs<'a>,
+}
+
+impl<'a> PatternIndex<'a> {
+    fn new(patterns: &'a [Pattern]) -> Self {
+        Self {
+            directories: build_directory_index(patterns),
+            files: build_file_patterns(patterns),
+            links: build_link_patterns(patterns),
+            hidden_allowed: hidden_pattern_dirs(patterns),
+        }
+    }
+}
+
+/// The patterns under each existing `root_patterns` path (see
+/// `patterns::scoped_patterns`), resolved like scan roots. Deepest paths come
+/// first, so the first one above a directory is the closest.
+fn resolve_scoped_patterns(config: &Config, patterns: &[Pattern]) -> Vec<(PathBuf, Vec<Pattern>)> {
+    let mut scoped: Vec<(PathBuf, Vec<Pattern>)> = config
+        .root_patterns
+        .iter()
+        .filter_map(|(root, scope)| {
+            let root = expand_tilde(root).ok()?.canonicalize().ok()?;
+            Some((root, crate::patterns::scoped_patterns(config, patterns, scope)))
+        })
+        .collect();
+    scoped.sort_by_key(|(root, _)| std::cmp::Reverse(root.components().count()));
+    scoped
+}
+
+/// File patterns with their compiled name globs. Invalid globs never match.
+fn build_file_patterns(patterns: &[Pattern]) -> Vec<(glob::Pattern, &Pattern)> {
+    patterns
+        .iter()
+        .filter(|p| p.kind == PatternKind::File)
+        .filter_map(|p| Some((glob::Pattern::new(&p.directory).ok()?, p)))
+        .collect()
+}
+
+/// The first file pattern a file satisfies: its name matches the glob, it takes
+/// up at least the pattern's `min_size` on disk, and any sentinel exists.
+fn match_file<'a>(
+    entry: &walkdir::DirEntry,
+    file_patterns: &[(glob::Pattern, &'a Pattern)],
+    sentinels: &mut SentinelCache,
+) -> Option<&'a Pattern> {
+    let name = entry.file_name().to_str()?;
+    let mut named = file_patterns
+        .iter()
+        .filter(|(glob, _)| glob.matches(name))
+        .peekable();
+    // Only stat files whose name matches
+    named.peek()?;
+    let bytes = entry.metadata().ok()?.blocks() * 512;
+    named
+        .find(|(_, p)| bytes >= p.min_size && pattern_matches(entry.path(), p, sentinels))
+        .map(|(_, p)| *p)
+}
+
+/// Link patterns with their compiled name globs. Invalid globs never match.
+fn build_link_patterns(patterns: &[Pattern]) -> Vec<(glob::Pattern, &Pattern)> {
+    patterns
+        .iter()
+        .filter(|p| p.kind == PatternKind::Link)
+        .filter_map(|p| Some((glob::Pattern::new(&p.directory).ok()?, p)))
+        .collect()
+}
+
+/// The directory a link pattern excludes for a symlink: the resolved target or,
+/// with `link_anchor`, the parent of the nearest anchor directory above it.
+fn link_target(link: &Path, pattern: &Pattern) -> Option<PathBuf> {
+    let target = link.canonicalize().ok().filter(|t| t.is_dir())?;
+    match &pattern.link_anchor {
+        Some(anchor) => target
+            .ancestors()
+            .find(|a| a.file_name().is_some_and(|name| name == anchor.as_str()))?
+            .parent()
+            .map(Path::to_path_buf),
+        None => Some(target),
+    }
+}
+
+/// The directory a symlink leads a link pattern to, with the names of every
+/// link pattern the symlink satisfies (the first one decides the directory).
+fn match_link(
+    entry: &walkdir::DirEntry,
+    link_patterns: &[(glob::Pattern, &Pattern)],
+    policy: MatchPolicy,
+    sentinels: &mut SentinelCache,
+) -> Option<(PathBuf, Vec<String>)> {
+    let name = entry.file_name().to_str()?;
+    let matched: Vec<&Pattern> = link_patterns
+        .iter()
+        .filter(|(glob, p)| glob.matches(name) && pattern_matches(entry.path(), p, sentinels))
+        .map(|(_, p)| *p)
+        .take(policy_limit(policy))
+        .collect();
+    let target = link_target(entry.path(), matched.first()?)?;
+    Some((target, matched.iter().map(|p| p.name.clone()).collect()))
+}
+
+/// How many matching patterns to look for under `policy`, most specific first.
+fn policy_limit(policy: MatchPolicy) -> usize {
+    match policy {
+        MatchPolicy::All => usize::MAX,
+        MatchPolicy::First => 1,
+    }
+}
+
+/// Names of the candidates that match `path`, most specific first. With
+/// `MatchPolicy::First` the rest aren't checked once one matches.
+fn matching_names(
+    path: &Path,
+    candidates: &[&Pattern],
+    policy: MatchPolicy,
+    sentinels: &mut SentinelCache,
+) -> Vec<String> {
+    candidates
+        .iter()
+        .filter(|p| pattern_matches(path, p, sentinels))
+        .take(policy_limit(policy))
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+/// Pattern name reported for directories a repository's `.gitignore` lists
+/// (`use_gitignore`).
+pub const GITIGNORE_PATTERN: &str = "gitignore";
+
+/// The name a `.gitignore` line ignores at the top of its repository, if it is a
+/// simple entry (`build/`, `/out`, `**/tmp`, `dist-*`). Comments, negations,
+/// entries with a path inside and bare wildcards are left out.
+fn gitignore_entry(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    if line.starts_with('#') || line.starts_with('!') {
+        return None;
+    }
+    let line = line.strip_prefix("**/").or_else(|| line.strip_prefix('/')).unwrap_or(line);
+    let name = line.strip_suffix('/').unwrap_or(line);
+    let wildcard_only = name.chars().all(|c| c == '*' || c == '?');
+    (!wildcard_only && !name.contains('/')).then_some(name)
+}
+
+/// The top-level entries of `repo`'s `.gitignore`, if `repo` is the root of a
+/// git repository (`.git` is a directory, or a file in worktrees and submodules).
+/// Each is paired with whether it is a `!` line re-including what it matches.
+fn read_gitignore(repo: &Path) -> Vec<(glob::Pattern, bool)> {
+    if !repo.join(".git").exists() {
+        return Vec::new();
+    }
+    let Ok(contents) = std::fs::read_to_string(repo.join(".gitignore")) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (negated, line) = line.strip_prefix('!').map_or((false, line), |l| (true, l));
+            Some((glob::Pattern::new(gitignore_entry(line)?).ok()?, negated))
+        })
+        .collect()
+}
+
+/// Parsed `.gitignore` files by the directory holding them, remembered for one
+/// scan.
+#[derive(Debug, Default)]
+struct GitignoreCache {
+    by_dir: HashMap<PathBuf, Vec<(glob::Pattern, bool)>>,
+}
+
+impl GitignoreCache {
+    /// Whether `dir` sits at the top of a git repository whose `.gitignore`
+    /// lists it. As in git, the last line matching its name decides, so `.*`
+    /// followed by `!.github/` leaves `.github` alone.
+    fn ignores(&mut self, dir: &Path) -> bool {
+        let (Some(repo), Some(name)) = (dir.parent(), dir.file_name().and_then(|n| n.to_str()))
+        else {
+            return false;
+        };
+        name != ".git"
+            && self
+                .by_dir
+                .entry(repo.to_path_buf())
+                .or_insert_with(|| read_gitignore(repo))
+                .iter()
+                .rev()
+                .find(|(entry, _)| entry.matches(name))
+                .is_some_and(|(_, negated)| !negated)
+    }
+}
+
 /// Result of a scan: path to exclude, matched pattern name, and whether it came from a pattern or exclude_paths.
 #[derive(Debug)]
 pub struct ScanMatch {
     pub path: PathBuf,
+    /// The most specific pattern that matched.
+    pub pattern_name: String,
+    /// Other patterns that matched the same directory, most specific first (e.g.
+    /// `bundler` for a `vendor` directory next to both `composer.json` and a
+    /// `Gemfile`). Always empty with `match_policy = "first"`.
+    pub also_matched: Vec<String>,
+    /// Reused from an earlier scan that found the directory already excluded, and
+    /// nothing relevant has changed since, so it needs no re-check.
+    pub known_excluded: bool,
+}
+
+/// Category of a problem encountered while scanning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanWarningKind {
+    RootMissing,
+    /// A configured path could not be expanded (e.g. `~name` for an unknown user).
+    InvalidPath,
+    /// The root is (or sits behind) a symlink and was resolved before scanning.
+    RootResolved,
+    /// The root is a file, or a symlink to one, so there is nothing to walk.
+    RootNotDirectory,
+    /// The same root is listed more than once (perhaps spelled differently, e.g.
+    /// with a trailing slash); it is walked once.
+    DuplicateRoot,
+    /// The root lies inside a skipped path. It is still scanned, since it was
+    /// asked for explicitly.
+    RootInSkipPath,
+    /// The root lies inside an exclude path, so its contents are already
+    /// excluded wholesale.
+    RootInExcludePath,
+    PermissionDenied,
+    Io,
+}
+
+impl ScanWarningKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScanWarningKind::RootMissing => "root_missing",
+            ScanWarningKind::InvalidPath => "invalid_path",
+            ScanWarningKind::RootResolved => "root_resolved",
+            ScanWarningKind::RootNotDirectory => "root_not_directory",
+            ScanWarningKind::DuplicateRoot => "duplicate_root",
+            ScanWarningKind::RootInSkipPath => "root_in_skip_path",
+            ScanWarningKind::RootInExcludePath => "root_in_exclude_path",
+            ScanWarningKind::PermissionDenied => "permission_denied",
+            ScanWarningKind::Io => "io",
+        }
+    }
+}
+
+/// Number of warnings of each kind, keyed by `ScanWarningKind::as_str`. Lets
+/// callers tell routine permission errors apart from broken roots or mounts.
+pub fn count_by_kind(warnings: &[ScanWarning]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for w in warnings {
+        *counts.entry(w.kind.as_str().to_string()).or_default() += 1;
+    }
+    counts
+}
+
+/// A non-fatal problem encountered while scanning. Collected rather than printed
+/// so callers decide how (and whether) to render them.
+#[derive(Debug, Clone)]
+pub struct ScanWarning {
+    pub path: PathBuf,
+    pub kind: ScanWarningKind,
+    pub message: String,
+}
+
+impl fmt::Display for ScanWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ScanWarningKind::RootMissing => {
+                write!(f, "scan root does not exist: {}", self.path.display())
+            }
+            ScanWarningKind::InvalidPath => write!(f, "{}", self.message),
+            ScanWarningKind::RootResolved => {
+                write!(f, "scan root {} {}", self.path.display(), self.message)
+            }
+            ScanWarningKind::RootNotDirectory if self.message.is_empty() => {
+                write!(f, "scan root is not a directory: {}", self.path.display())
+            }
+            ScanWarningKind::RootNotDirectory => {
+                write!(
+                    f,
+                    "scan root is not a directory: {} ({})",
+                    self.path.display(),
+                    self.message
+                )
+            }
+            ScanWarningKind::DuplicateRoot => write!(
+                f,
+                "scan root {} is listed more than once; scanning it once",
+                self.path.display()
+            ),
+            ScanWarningKind::RootInSkipPath => write!(
+                f,
+                "scan root {} is inside skipped path {}; scanning it anyway",
+                self.path.display(),
+                self.message
+            ),
+            ScanWarningKind::RootInExcludePath => write!(
+                f,
+                "scan root {} is inside exclude path {}; its contents are already excluded",
+                self.path.display(),
+                self.message
+            ),
+            _ => write!(f, "cannot access {}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Counters describing how a scan went.
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    /// Directories visited by the walker.
+    pub dirs_visited: usize,
+    pub elapsed: Duration,
+    /// The time budget ran out before every root was fully walked.
+    pub truncated: bool,
+    /// Roots not walked because they are the same as or inside another root,
+    /// paired with the root that covers them.
+    pub nested_roots: Vec<(PathBuf, PathBuf)>,
+    /// Hidden directories not descended into because of `skip_hidden`.
+    pub hidden_pruned: usize,
+    /// Candidate directories whose result was reused from the prior scan.
+    pub reused: usize,
+    /// Sentinel lookups that had to go to the filesystem.
+    pub sentinel_checks: usize,
+    /// The scan was cancelled through `ScanOptions::cancel`.
+    pub interrupted: bool,
+    /// Directories the walk descended into, with `ScanOptions::record_walked`:
+    /// everything it visited except skipped and pruned hidden directories and
+    /// what lies inside a match.
+    pub walked: Vec<PathBuf>,
+}
+
+/// Everything a scan produced: matches to exclude and warnings to report.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub matches: Vec<ScanMatch>,
+    pub warnings: Vec<ScanWarning>,
+    pub stats: ScanStats,
+}
+
+/// What a streaming scan produced besides the matches handed to its callback.
+#[derive(Debug, Default)]
+pub struct ScanSummary {
+    pub warnings: Vec<ScanWarning>,
+    pub stats: ScanStats,
+}
+
+fn invalid_path_warning(path: &str, err: anyhow::Error) -> ScanWarning {
+    ScanWarning {
+        path: PathBuf::from(path),
+        kind: ScanWarningKind::InvalidPath,
+        message: err.to_string(),
+    }
+}
+
+fn walk_warning(err: &walkdir::Error, root: &Path) -> ScanWarning {
+    let kind = match err.io_error().map(|e| e.kind()) {
+        Some(std::io::ErrorKind::PermissionDenied) => ScanWarningKind::PermissionDenied,
+        _ => ScanWarningKind::Io,
+    };
+    ScanWarning {
+        path: err.path().unwrap_or(root).to_path_buf(),
+        kind,
+        message: err.to_string(),
+    }
+}
+
+/// Hidden directories that active patterns need to reach, so `skip_hidden`
+/// must not prune them.
+#[derive(Default)]
+struct HiddenPatternDirs<'a> {
+    /// Hidden names a pattern matches (e.g. `.venv`), or that lead to more
+    /// than one component of a pattern's directory.
+    names: HashSet<&'a str>,
+    /// Hidden names that only lead to a pattern's directory (e.g. `.cache` for
+    /// `.cache/clangd`), with the component that has to come next.
+    leading_to: HashMap<&'a str, Vec<&'a str>>,
+}
+
+impl HiddenPatternDirs<'_> {
+    /// Whether `skip_hidden` should still descend into the hidden directory `dir`.
+    fn allows(&self, dir: &Path, name: &str) -> bool {
+        self.names.contains(name)
+            || self.leading_to.get(name).is_some_and(|next| {
+                next.iter().any(|n| crate::patterns::is_glob(n) || dir.join(n).is_dir())
+            })
+    }
+}
+
+fn hidden_pattern_dirs(patterns: &[Pattern]) -> HiddenPatternDirs<'_> {
+    let mut hidden = HiddenPatternDirs::default();
+    for p in patterns.iter().filter(|p| p.kind == PatternKind::Directory) {
+        let components: Vec<&str> = p.directory.split('/').collect();
+        for (i, component) in components.iter().enumerate() {
+            if !component.starts_with('.') {
+                continue;
+            }
+            match components.get(i + 1) {
+                Some(next) if i + 2 == components.len() => {
+                    hidden.leading_to.entry(component).or_default().push(next)
+                }
+                _ => {
+                    hidden.names.insert(component);
+                }
+            }
+        }
+    }
+    hidden
+}
+
+/// Where macOS mounts the writable data volume. Firmlinks make the same
+/// directories reachable under it and at their usual paths (e.g. `/Users`).
+const DATA_VOLUME: &str = "/System/Volumes/Data";
+
+/// The user-facing spelling of a path reached through the data volume, e.g.
+/// `/Users/me/app` for `/System/Volumes/Data/Users/me/app`.
+fn user_facing(path: PathBuf) -> PathBuf {
+    user_facing_in(path, Path::new(DATA_VOLUME), Path::new("/"))
+}
+
+/// `user_facing` with the data volume assumed to be firmlinked into `system`.
+fn user_facing_in(path: PathBuf, data_volume: &Path, system: &Path) -> PathBuf {
+    let Ok(rest) = path.strip_prefix(data_volume) else {
+        return path;
+    };
+    let candidate = system.join(rest);
+    if physical_id(&candidate).is_some() && physical_id(&candidate) == physical_id(&path) {
+        candidate
+    } else {
+        path
+    }
+}
+
+/// Device and inode of a path, identifying it regardless of how it is spelled.
+fn physical_id(path: &Path) -> Option<(u64, u64)> {
+    std::fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+/// Sort roots and drop any that equal or sit inside an earlier root, so no
+/// directory is walked twice. A nested root inside one of `apart` (skip or
+/// exclude paths) below its covering root is kept, since the walk from the
+/// covering root would prune it or it deserves its own warning. Returns the
+/// kept roots and each dropped root paired with the root that covers it.
+fn collapse_nested_roots(
+    mut roots: Vec<PathBuf>,
+    apart: &[&PathSet],
+) -> (Vec<PathBuf>, Vec<(PathBuf, PathBuf)>) {
+    roots.sort();
+    let mut kept: Vec<PathBuf> = Vec::new();
+    let mut dropped = Vec::new();
+    for root in roots {
+        let covering = kept
+            .iter()
+            .find(|k| root.starts_with(k) && !apart.iter().any(|set| set.covers(&root, k)));
+        match covering {
+            Some(covering) => dropped.push((root, covering.clone())),
+            None => kept.push(root),
+        }
+    }
+    (kept, dropped)
+}
+
+/// A remembered match from an earlier scan.
+#[derive(Debug, Clone)]
+pub struct PriorMatch {
     pub pattern_name: String,
+    /// The run that found it left it excluded.
+    pub excluded: bool,
+}
+
+/// Results of an earlier complete scan, for incremental runs.
+#[derive(Debug)]
+pub struct PriorScan {
+    /// When the earlier scan started. Directories modified before this are
+    /// assumed to match (or not) exactly as they did then.
+    pub scanned_at: SystemTime,
+    pub matches: HashMap<PathBuf, PriorMatch>,
+}
+
+/// Per-invocation scan settings that don't come from the config file.
+#[derive(Debug, Default)]
+pub struct ScanOptions {
+    /// Additional directories to skip for this scan only (supports ~ expansion).
+    pub extra_skip_paths: Vec<String>,
+    /// Stop walking once this much time has passed, keeping matches found so far.
+    pub time_budget: Option<Duration>,
+    /// Reuse these results for candidates unchanged since the earlier scan instead
+    /// of checking sentinels again.
+    pub prior: Option<PriorScan>,
+    /// Stop walking as soon as this is set (e.g. by a signal handler).
+    pub cancel: Option<&'static AtomicBool>,
+    /// Check only these directories against the directory patterns instead of
+    /// walking the roots (`run --changed-since`). Exclude paths still apply.
+    pub only_dirs: Option<Vec<PathBuf>>,
+    /// Collect the directories the walk descends into in `ScanStats::walked`,
+    /// for `run --watch` to watch.
+    pub record_walked: bool,
+}
+
+/// Identifies everything that decides what a scan matches (roots, skip paths,
+/// patterns and walk settings), so an earlier scan's results are only reused by a
+/// scan that would have produced the same ones.
+pub fn fingerprint(config: &Config, patterns: &[Pattern], options: &ScanOptions) -> String {
+    let mut hasher = DefaultHasher::new();
+    let inputs = (
+        env!("CARGO_PKG_VERSION"),
+        &config.scan_roots,
+        config.resolved_skip_paths(),
+        &options.extra_skip_paths,
+        patterns,
+        config.follow_symlinks,
+        config.skip_hidden,
+        &config.root_patterns,
+        config.use_gitignore,
+    );
+    format!("{:?}", inputs).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Whether a candidate's match result can't have changed since `since`: the
+/// candidate and every directory between it and the highest place its sentinels
+/// are looked up must be unmodified, as adding or removing an entry updates a
+/// directory's mtime.
+fn unchanged_since(path: &Path, pattern: &Pattern, since: SystemTime) -> bool {
+    let Some(base) = sentinel_base(path, pattern) else {
+        return false;
+    };
+    let top = base.ancestors().nth(pattern.sentinel_depth.max(1) - 1).unwrap_or(base);
+    path.ancestors()
+        .take_while(|dir| dir.starts_with(top))
+        .all(|dir| {
+            std::fs::metadata(dir)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified < since)
+        })
+}
+
+/// Scan all configured roots and collect the matches. `run` and `reset` use
+/// `scan_streaming` to act on matches as they are found.
+#[cfg(test)]
+pub fn scan_optimized(config: &Config, patterns: &[Pattern]) -> ScanResult {
+    scan_with_options(config, patterns, &ScanOptions::default())
 }
 
-/// Scan all configured roots for dependency directories matching the given patterns.
-/// Skips descending into matched dependency directories for performance.
-pub fn scan_optimized(config: &Config, patterns: &[Pattern]) -> Vec<ScanMatch> {
-    let skip_set = build_skip_set(config);
-    let dir_index = build_directory_index(patterns);
+/// Like `scan_optimized`, with one-off options such as extra directories to skip.
+#[cfg(test)]
+pub fn scan_with_options(config: &Config, patterns: &[Pattern], options: &ScanOptions) -> ScanResult {
     let mut matches = Vec::new();
-    let mut excluded_dirs: HashSet<PathBuf> = HashSet::new();
+    let ScanSummary { warnings, stats } = scan_streaming(config, patterns, options, |m| {
+        matches.push(m);
+        ControlFlow::Continue(())
+    });
+    ScanResult {
+        matches,
+        warnings,
+        stats,
+    }
+}
 
+/// Scan all configured roots for dependency directories matching the given
+/// patterns, handing each match to `on_match` as soon as it is found so the caller
+/// can act on it while the walk continues. Returning `ControlFlow::Break` from the
+/// callback ends the scan early. Skips descending into matched dependency
+/// directories for performance.
+pub fn scan_streaming(
+    config: &Config,
+    patterns: &[Pattern],
+    options: &ScanOptions,
+    mut on_match: impl FnMut(ScanMatch) -> ControlFlow<()>,
+) -> ScanSummary {
+    let started = Instant::now();
+    let skip_set = build_skip_set(config, options);
+    let default_index = PatternIndex::new(patterns);
+    let scoped_patterns = resolve_scoped_patterns(config, patterns);
+    let scoped_indexes: Vec<(&Path, PatternIndex)> = scoped_patterns
+        .iter()
+        .map(|(root, patterns)| (root.as_path(), PatternIndex::new(patterns)))
+        .collect();
+    let mut sentinels = SentinelCache::default();
+    let mut gitignores = GitignoreCache::default();
+    let mut stopped = false;
+    let mut warnings = Vec::new();
+    let mut stats = ScanStats::default();
+    // Every path already reported as a match. Also stops the walker from
+    // descending into matched dependency directories.
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+
+    let mut roots = Vec::new();
     for root_str in &config.scan_roots {
-        let root = expand_tilde(root_str);
+        let expanded = match expand_tilde(root_str) {
+            Ok(p) => p,
+            Err(e) => {
+                warnings.push(invalid_path_warning(root_str, e));
+                continue;
+            }
+        };
+
+        if !expanded.exists() {
+            warnings.push(ScanWarning {
+                path: expanded,
+                kind: ScanWarningKind::RootMissing,
+                message: "scan root does not exist".to_string(),
+            });
+            continue;
+        }
+
+        // Walk the resolved path so matches line up with the skip set and with
+        // the canonical paths `add`/`remove` work with.
+        match expanded.canonicalize() {
+            Ok(resolved) if !resolved.is_dir() => {
+                let message = if resolved != expanded {
+                    format!("resolves to {}", resolved.display())
+                } else {
+                    String::new()
+                };
+                warnings.push(ScanWarning {
+                    path: expanded,
+                    kind: ScanWarningKind::RootNotDirectory,
+                    message,
+                });
+            }
+            Ok(resolved) => {
+                if resolved != expanded {
+                    warnings.push(ScanWarning {
+                        path: expanded,
+                        kind: ScanWarningKind::RootResolved,
+                        message: format!("resolves to {}", resolved.display()),
+                    });
+                }
+                roots.push(resolved);
+            }
+            Err(e) => {
+                warnings.push(ScanWarning {
+                    path: expanded,
+                    kind: ScanWarningKind::Io,
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    let exclude_set = PathSet::resolve(&config.resolved_exclude_paths());
+    let (mut roots, nested_roots) = collapse_nested_roots(roots, &[&skip_set, &exclude_set]);
+    for (dropped, covering) in &nested_roots {
+        if dropped == covering {
+            warnings.push(ScanWarning {
+                path: dropped.clone(),
+                kind: ScanWarningKind::DuplicateRoot,
+                message: String::new(),
+            });
+        }
+    }
+    stats.nested_roots = nested_roots;
+    // Without patterns nothing can match, so don't walk at all
+    if patterns.is_empty() {
+        roots.clear();
+    }
 
-        if !root.exists() {
-            eprintln!("Warning: scan root does not exist: {}", root.display());
+    // A root under an excluded or skipped path is scanned anyway, but it's
+    // almost always a config mistake worth pointing out.
+    for root in &roots {
+        let (kind, enclosing) = if let Some(p) = exclude_set.enclosing(root) {
+            (ScanWarningKind::RootInExcludePath, p)
+        } else if let Some(p) = skip_set.enclosing(root) {
+            (ScanWarningKind::RootInSkipPath, p)
+        } else {
             continue;
+        };
+        warnings.push(ScanWarning {
+            path: root.clone(),
+            kind,
+            message: enclosing.display().to_string(),
+        });
+    }
+    // Walk data-volume roots last, so directories also reachable at their
+    // user-facing path are reported under that spelling.
+    roots.sort_by_key(|r| r.starts_with(DATA_VOLUME));
+    // Physical identity of every directory walked and every match reported.
+    // Firmlinks, symlinks and hard links can reach the same one by several paths.
+    let mut visited_inodes: HashSet<(u64, u64)> = HashSet::new();
+    let mut matched_ids: HashSet<(u64, u64)> = HashSet::new();
+
+    // A list of candidates replaces the walk. Parents sort before their
+    // children, so nothing inside a match is matched again.
+    if let Some(dirs) = &options.only_dirs {
+        let mut matcher = DirectoryMatcher::new(config, patterns, options, []);
+        let mut dirs = dirs.clone();
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            if options.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                stats.interrupted = true;
+                break;
+            }
+            stats.dirs_visited += 1;
+            let Some(found) = matcher.check(&dir) else { continue };
+            if let Some(id) = physical_id(&dir) {
+                matched_ids.insert(id);
+            }
+            seen.insert(dir);
+            if on_match(found).is_break() {
+                stopped = true;
+                break;
+            }
         }
+        roots.clear();
+    }
 
-        let mut walker = WalkDir::new(&root).follow_links(false).into_iter();
+    'roots: for root in roots {
+        let mut walker = WalkDir::new(&root)
+            .follow_links(config.follow_symlinks)
+            .into_iter();
 
         loop {
             let entry = match walker.next() {
                 Some(Ok(e)) => e,
+                // Symlink cycles are expected when following links; the inode
+                // tracking below already keeps us from walking anything twice.
+                Some(Err(err)) if err.loop_ancestor().is_some() => continue,
                 Some(Err(err)) => {
-                    if let Some(path) = err.path() {
-                        eprintln!("Warning: cannot access {}: {}", path.display(), err);
-                    }
+                    warnings.push(walk_warning(&err, &root));
                     continue;
                 }
                 None => break,
             };
+            // Paths under a `root_patterns` entry use that entry's patterns
+            let index = scoped_indexes
+                .iter()
+                .find(|(root, _)| entry.path().starts_with(root))
+                .map_or(&default_index, |(_, index)| index);
+
+            // Build tools such as Bazel leave symlinks into output trees kept
+            // outside the project; exclude the tree instead of the link
+            if entry.path_is_symlink()
+                && !index.links.is_empty()
+                && let Some((target, mut names)) = match_link(&entry, &index.links, config.match_policy, &mut sentinels)
+            {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                let id = physical_id(&target);
+                if seen.insert(target.clone()) && id.is_none_or(|id| matched_ids.insert(id)) {
+                    let found = ScanMatch {
+                        path: user_facing(target),
+                        pattern_name: names.remove(0),
+                        also_matched: names,
+                        known_excluded: false,
+                    };
+                    if on_match(found).is_break() {
+                        stopped = true;
+                        break 'roots;
+                    }
+                }
+                continue;
+            }
 
             if !entry.file_type().is_dir() {
+                // Most scans have no file patterns; don't look at files at all then
+                if !index.files.is_empty()
+                    && entry.file_type().is_file()
+                    && let Some(pattern) = match_file(&entry, &index.files, &mut sentinels)
+                {
+                    let path = if config.follow_symlinks {
+                        entry.path().canonicalize().unwrap_or_else(|_| entry.path().to_path_buf())
+                    } else {
+                        entry.path().to_path_buf()
+                    };
+                    let id = entry.metadata().ok().map(|m| (m.dev(), m.ino()));
+                    if seen.insert(path.clone()) && id.is_none_or(|id| matched_ids.insert(id)) {
+                        let found = ScanMatch {
+                            path: user_facing(path),
+                            pattern_name: pattern.name.clone(),
+                            also_matched: Vec::new(),
+                            known_excluded: false,
+                        };
+                        if on_match(found).is_break() {
+                            stopped = true;
+                            break 'roots;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // The same physical directory can be reached by several paths
+            // (firmlinked roots, or symlinks when followed); walk it only the
+            // first time.
+            if let Ok(meta) = entry.metadata()
+                && !visited_inodes.insert((meta.dev(), meta.ino()))
+            {
+                walker.skip_current_dir();
                 continue;
             }
 
+            if options.time_budget.is_some_and(|budget| started.elapsed() >= budget) {
+                stats.truncated = true;
+                break 'roots;
+            }
+            if options.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                stats.interrupted = true;
+                break 'roots;
+            }
+            stats.dirs_visited += 1;
+
             let path = entry.path().to_path_buf();
 
-            // Skip paths in skip set
-            if skip_set.contains(&path) {
+            // Skip paths in skip set. Checking ancestors too catches a path the
+            // walker reached below a skip path without passing through it.
+            if skip_set.covers(&path, &root) {
                 walker.skip_current_dir();
                 continue;
             }
 
             // Skip already-matched dependency directories (no point descending into node_modules)
-            if excluded_dirs.contains(&path) {
+            if seen.contains(&path) {
                 walker.skip_current_dir();
                 continue;
             }
@@ -94,74 +1049,407 @@ pub fn scan_optimized(config: &Config, patterns: &[Pattern]) -> Vec<ScanMatch> {
                 None => continue,
             };
 
-            if let Some(candidates) = dir_index.get(&dir_name) {
-                if let Some(parent) = path.parent() {
-                    for pattern in candidates {
-                        if sentinel_exists(parent, &pattern.sentinel) {
-                            excluded_dirs.insert(path.clone());
-                            matches.push(ScanMatch {
-                                path: path.clone(),
-                                pattern_name: pattern.name.clone(),
-                            });
-                            walker.skip_current_dir();
-                            break;
-                        }
+            if config.skip_hidden
+                && entry.depth() > 0
+                && dir_name.starts_with('.')
+                && !index.hidden_allowed.allows(entry.path(), &dir_name)
+            {
+                stats.hidden_pruned += 1;
+                walker.skip_current_dir();
+                continue;
+            }
+
+            if let Some(candidates) = index.directories.candidates(&dir_name) {
+                // The exclusion xattr lives on the physical directory, so report
+                // matches reached through a symlink at their target.
+                let physical = |path: PathBuf| {
+                    if config.follow_symlinks {
+                        path.canonicalize().unwrap_or(path)
+                    } else {
+                        path
+                    }
+                };
+
+                let reusable = options.prior.as_ref().filter(|prior| {
+                    candidates
+                        .iter()
+                        .all(|p| unchanged_since(&path, p, prior.scanned_at))
+                });
+                let matched = match reusable {
+                    Some(prior) => {
+                        stats.reused += 1;
+                        let path = physical(path);
+                        prior
+                            .matches
+                            .get(&path)
+                            .filter(|pm| candidates.iter().any(|p| p.name == pm.pattern_name))
+                            .map(|pm| (path, pm.pattern_name.clone(), Vec::new(), pm.excluded))
+                    }
+                    None => {
+                        // Unless told to stop at the first, check every candidate
+                        // so the match lists all patterns that claim the directory
+                        let mut names =
+                            matching_names(&path, &candidates, config.match_policy, &mut sentinels);
+                        (!names.is_empty()).then(|| {
+                            let first = names.remove(0);
+                            (physical(path), first, names, false)
+                        })
+                    }
+                };
+
+                if let Some((path, pattern_name, also_matched, known_excluded)) = matched {
+                    // Matches of descending patterns are walked like any other
+                    // directory, so nested matches are reported as well
+                    let descend = candidates
+                        .iter()
+                        .any(|p| p.name == pattern_name && p.descend);
+                    seen.insert(path.clone());
+                    if let Some(id) = physical_id(&path) {
+                        matched_ids.insert(id);
+                    }
+                    let found = ScanMatch {
+                        path: user_facing(path),
+                        pattern_name,
+                        also_matched,
+                        known_excluded,
+                    };
+                    if on_match(found).is_break() {
+                        stopped = true;
+                        break 'roots;
+                    }
+                    if !descend {
+                        walker.skip_current_dir();
+                    } else if options.record_walked {
+                        stats.walked.push(entry.path().to_path_buf());
+                    }
+                    continue;
+                }
+            }
+
+            // Directories a repository ignores that no pattern claimed
+            if config.use_gitignore && entry.depth() > 0 && gitignores.ignores(entry.path()) {
+                walker.skip_current_dir();
+                let path = if config.follow_symlinks {
+                    entry.path().canonicalize().unwrap_or_else(|_| entry.path().to_path_buf())
+                } else {
+                    entry.path().to_path_buf()
+                };
+                let id = physical_id(&path);
+                if seen.insert(path.clone()) && id.is_none_or(|id| matched_ids.insert(id)) {
+                    let found = ScanMatch {
+                        path: user_facing(path),
+                        pattern_name: GITIGNORE_PATTERN.to_string(),
+                        also_matched: Vec::new(),
+                        known_excluded: false,
+                    };
+                    if on_match(found).is_break() {
+                        stopped = true;
+                        break 'roots;
                     }
                 }
+                continue;
+            }
+
+            if options.record_walked {
+                stats.walked.push(entry.path().to_path_buf());
             }
         }
     }
 
-    // Add resolved exclude_paths (built-ins + extras - disabled)
-    for path_str in config.resolved_exclude_paths() {
-        let path = expand_tilde(&path_str);
-        if path.exists() {
-            matches.push(ScanMatch {
-                path,
+    // Add resolved exclude_paths (built-ins + extras - disabled), skipping any
+    // path the walk already matched or that is spelled twice in the config
+    let exclude_paths = if stopped || stats.interrupted {
+        Vec::new()
+    } else {
+        config.resolved_exclude_paths()
+    };
+    for path_str in exclude_paths {
+        let path = match expand_tilde(&path_str) {
+            Ok(p) => p,
+            Err(e) => {
+                warnings.push(invalid_path_warning(&path_str, e));
+                continue;
+            }
+        };
+        if !path.exists() {
+            continue;
+        }
+        let path = path.canonicalize().unwrap_or(path);
+        let id = physical_id(&path);
+        if seen.insert(path.clone()) && id.is_none_or(|id| matched_ids.insert(id)) {
+            let found = ScanMatch {
+                path: user_facing(path),
                 pattern_name: "exclude_path".to_string(),
-            });
+                also_matched: Vec::new(),
+                known_excluded: false,
+            };
+            if on_match(found).is_break() {
+                break;
+            }
         }
     }
 
-    matches
+    stats.sentinel_checks = sentinels.checks;
+    stats.elapsed = started.elapsed();
+    ScanSummary { warnings, stats }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-
-    #[test]
-    fn test_sentinel_exists_exact() {
-        let dir = std::env::temp_dir().join("tmignore_test_sentinel");
-        let _ = fs::remove_dir_all(&dir);
-        fs::create_dir_all(&dir).unwrap();
-        fs::write(dir.join("package.json"), "{}").unwrap();
+/// `mdfind` arguments listing everything under `root` modified at or after
+/// `since` (ISO 8601), for `run --changed-since`.
+pub fn changed_since_query(root: &Path, since: &str) -> Vec<String> {
+    vec![
+        "-onlyin".to_string(),
+        root.to_string_lossy().to_string(),
+        format!("kMDItemContentModificationDate >= $time.iso({since})"),
+    ]
+}
 
-        assert!(sentinel_exists(&dir, "package.json"));
-        assert!(!sentinel_exists(&dir, "Cargo.toml"));
+/// Directories under `root` that Spotlight saw change since `since`.
+pub fn find_changed_dirs(root: &Path, since: &str) -> anyhow::Result<Vec<PathBuf>> {
+    use anyhow::Context;
+    let output = std::process::Command::new("mdfind")
+        .args(changed_since_query(root, since))
+        .output()
+        .context("Failed to run mdfind")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("mdfind failed: {}", stderr.trim());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(changed_dirs(root, stdout.lines().map(Path::new)))
+}
 
-        let _ = fs::remove_dir_all(&dir);
+/// Every directory holding a changed item, and the directories above it up to
+/// `root`: a dependency directory has changed whenever anything inside it has.
+fn changed_dirs<'a>(root: &Path, changed: impl IntoIterator<Item = &'a Path>) -> Vec<PathBuf> {
+    let mut dirs = std::collections::BTreeSet::new();
+    for item in changed {
+        for dir in item.ancestors().take_while(|d| d.starts_with(root) && *d != root) {
+            // Its ancestors went in along with it
+            if !dirs.insert(dir.to_path_buf()) {
+                break;
+            }
+        }
     }
+    dirs.into_iter().filter(|d| d.is_dir()).collect()
+}
 
-    #[test]
-    fn test_sentinel_exists_glob() {
-        let dir = std::env::temp_dir().join("tmignore_test_glob");
-        let _ = fs::remove_dir_all(&dir);
-        fs::create_dir_all(&dir).unwrap();
-        fs::write(dir.join("MyApp.csproj"), "<Project/>").unwrap();
+/// Checks directories one at a time against the directory patterns, for ones
+/// that appear after a scan (`run --watch`). Follows the walker's rules: skipped
+/// paths, hidden directories with `skip_hidden` and anything inside an earlier
+/// match are left alone.
+pub struct DirectoryMatcher<'a> {
+    roots: Vec<PathBuf>,
+    skip_set: PathSet,
+    dir_index: DirectoryIndex<'a>,
+    hidden_allowed: HiddenPatternDirs<'a>,
+    /// Patterns under `root_patterns` paths, indexed per check as they are rare.
+    scoped: Vec<(PathBuf, Vec<Pattern>)>,
+    skip_hidden: bool,
+    use_gitignore: bool,
+    match_policy: MatchPolicy,
+    /// Matches so far; nothing inside them is matched again.
+    matched: PathSet,
+}
 
-        assert!(sentinel_exists(&dir, "*.csproj"));
-        assert!(!sentinel_exists(&dir, "*.fsproj"));
+impl<'a> DirectoryMatcher<'a> {
+    /// `matched` holds the matches of the scan that came before.
+    pub fn new(
+        config: &Config,
+        patterns: &'a [Pattern],
+        options: &ScanOptions,
+        matched: impl IntoIterator<Item = PathBuf>,
+    ) -> Self {
+        let roots = config
+            .scan_roots
+            .iter()
+            .filter_map(|r| expand_tilde(r).ok()?.canonicalize().ok())
+            .filter(|r| r.is_dir())
+            .collect();
+        let skip_set = build_skip_set(config, options);
+        Self {
+            roots: collapse_nested_roots(roots, &[&skip_set]).0,
+            skip_set,
+            dir_index: build_directory_index(patterns),
+            hidden_allowed: hidden_pattern_dirs(patterns),
+            scoped: resolve_scoped_patterns(config, patterns),
+            skip_hidden: config.skip_hidden,
+            use_gitignore: config.use_gitignore,
+            match_policy: config.match_policy,
+            matched: PathSet(matched.into_iter().collect()),
+        }
+    }
 
-        let _ = fs::remove_dir_all(&dir);
+    /// The existing scan roots, resolved, without any nested in another.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
     }
 
-    #[test]
-    fn test_scan_finds_node_modules() {
-        let dir = std::env::temp_dir().join("tmignore_test_scan");
-        let _ = fs::remove_dir_all(&dir);
-        let project_dir = dir.join("myproject");
+    /// Whether the walker would reach `path` and descend into it: it lies under
+    /// a root, and not in a skipped path, a pruned hidden directory or a match
+    /// (other than one of a descending pattern).
+    pub fn walks(&self, path: &Path) -> bool {
+        // The innermost root, since a root kept inside a skip path is walked
+        let Some(root) = self.roots.iter().rfind(|r| path.starts_with(r)) else {
+            return false;
+        };
+        if self.matched.enclosing(path).is_some() || self.skip_set.covers(path, root) {
+            return false;
+        }
+        if !self.skip_hidden {
+            return true;
+        }
+        let scoped = self
+            .scoped
+            .iter()
+            .find(|(root, _)| path.starts_with(root))
+            .map(|(_, patterns)| hidden_pattern_dirs(patterns));
+        let hidden_allowed = scoped.as_ref().unwrap_or(&self.hidden_allowed);
+        !path
+            .ancestors()
+            .take_while(|dir| dir != root)
+            .any(|dir| {
+                dir.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with('.') && !hidden_allowed.allows(dir, n))
+            })
+    }
+
+    /// The match for a directory, if it is one. Sentinels and `.gitignore` files
+    /// are read afresh each time, since they may have changed since the last check.
+    pub fn check(&mut self, path: &Path) -> Option<ScanMatch> {
+        if !self.walks(path) {
+            return None;
+        }
+        let scoped = self
+            .scoped
+            .iter()
+            .find(|(root, _)| path.starts_with(root))
+            .map(|(_, patterns)| build_directory_index(patterns));
+        let dir_index = scoped.as_ref().unwrap_or(&self.dir_index);
+
+        let candidates = dir_index.candidates(path.file_name()?.to_str()?).unwrap_or_default();
+        let mut sentinels = SentinelCache::default();
+        let mut names = matching_names(path, &candidates, self.match_policy, &mut sentinels);
+        if names.is_empty() && self.use_gitignore && GitignoreCache::default().ignores(path) {
+            names.push(GITIGNORE_PATTERN.to_string());
+        }
+        if names.is_empty() {
+            return None;
+        }
+        let pattern_name = names.remove(0);
+        let descend = candidates
+            .iter()
+            .any(|p| p.name == pattern_name && p.descend);
+        if !descend {
+            self.matched.0.insert(path.to_path_buf());
+        }
+        Some(ScanMatch {
+            path: user_facing(path.to_path_buf()),
+            pattern_name,
+            also_matched: names,
+            known_excluded: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Fresh temp directory for a test, canonicalized since temp dirs sit behind
+    /// a symlink on macOS and scan results use resolved paths.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    /// Config that scans only `root`, with all built-in exclude paths disabled.
+    fn test_config(root: &Path) -> Config {
+        Config {
+            scan_roots: vec![root.to_string_lossy().to_string()],
+            disable_exclude_paths: crate::config::builtin_exclude_paths()
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect(),
+            ..Config::default()
+        }
+    }
+
+    /// Path and pattern name of every match a scan finds, sorted.
+    fn found(config: &Config, patterns: &[Pattern]) -> Vec<(PathBuf, String)> {
+        let mut found: Vec<(PathBuf, String)> = scan_optimized(config, patterns)
+            .matches
+            .into_iter()
+            .map(|m| (m.path, m.pattern_name))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_sentinel_exists_exact() {
+        let dir = std::env::temp_dir().join("tmignore_test_sentinel");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("package.json"), "{}").unwrap();
+
+        assert!(sentinel_exists(&dir, "package.json", None, SentinelType::Any));
+        assert!(!sentinel_exists(&dir, "Cargo.toml", None, SentinelType::Any));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sentinel_exists_glob() {
+        let dir = std::env::temp_dir().join("tmignore_test_glob");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("MyApp.csproj"), "<Project/>").unwrap();
+
+        assert!(sentinel_exists(&dir, "*.csproj", None, SentinelType::Any));
+        assert!(!sentinel_exists(&dir, "*.fsproj", None, SentinelType::Any));
+
+        // A precompiled glob gives the same answers
+        let csproj = glob::Pattern::new("*.csproj").unwrap();
+        let fsproj = glob::Pattern::new("*.fsproj").unwrap();
+        assert!(sentinel_exists(&dir, "*.csproj", Some(&csproj), SentinelType::Any));
+        assert!(!sentinel_exists(&dir, "*.fsproj", Some(&fsproj), SentinelType::Any));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sentinel_exists_checks_type() {
+        let dir = test_dir("tmignore_test_sentinel_type");
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".idea"), "").unwrap();
+        fs::create_dir_all(dir.join("App.xcodeproj")).unwrap();
+        fs::write(dir.join("Stray.xcodeproj"), "").unwrap();
+
+        assert!(sentinel_exists(&dir, ".git", None, SentinelType::Dir));
+        assert!(!sentinel_exists(&dir, ".git", None, SentinelType::File));
+        assert!(sentinel_exists(&dir, ".idea", None, SentinelType::File));
+        assert!(!sentinel_exists(&dir, ".idea", None, SentinelType::Dir));
+        assert!(sentinel_exists(&dir, ".idea", None, SentinelType::Any));
+
+        // A glob matches if any matching entry has the right type
+        assert!(sentinel_exists(&dir, "*.xcodeproj", None, SentinelType::Dir));
+        fs::remove_dir_all(dir.join("App.xcodeproj")).unwrap();
+        assert!(!sentinel_exists(&dir, "*.xcodeproj", None, SentinelType::Dir));
+        assert!(sentinel_exists(&dir, "*.xcodeproj", None, SentinelType::File));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_node_modules() {
+        let dir = std::env::temp_dir().join("tmignore_test_scan");
+        let _ = fs::remove_dir_all(&dir);
+        let project_dir = dir.join("myproject");
         fs::create_dir_all(project_dir.join("node_modules/.package-lock.json")).unwrap();
         fs::write(project_dir.join("package.json"), "{}").unwrap();
 
@@ -177,17 +1465,1394 @@ mod tests {
             disable_exclude_paths: disable_all_excludes,
             disable_patterns: vec![],
             custom_patterns: vec![],
+            ..Config::default()
+        };
+
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+
+        let result = scan_optimized(&config, &patterns);
+        assert!(result.matches.iter().any(|m| m.pattern_name == "node" && m.path.ends_with("node_modules")));
+        assert!(result.warnings.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pattern_matches_sentinel_inside() {
+        let dir = test_dir("tmignore_test_inside");
+        let with_cache = dir.join("app/build");
+        let without_cache = dir.join("docs/build");
+        fs::create_dir_all(&with_cache).unwrap();
+        fs::create_dir_all(&without_cache).unwrap();
+        fs::write(with_cache.join("CMakeCache.txt"), "").unwrap();
+        // A sentinel next to the directory must not count for an "inside" pattern
+        fs::write(dir.join("docs/CMakeCache.txt"), "").unwrap();
+
+        let pattern = Pattern::new("cmake", "build", "CMakeCache.txt").sentinel_inside();
+
+        assert!(pattern_matches(&with_cache, &pattern, &mut SentinelCache::default()));
+        assert!(!pattern_matches(&without_cache, &pattern, &mut SentinelCache::default()));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pattern_matches_sentinel_depth() {
+        let dir = test_dir("tmignore_test_sentinel_depth");
+        let turbo = dir.join("packages/foo/.turbo");
+        fs::create_dir_all(&turbo).unwrap();
+        fs::write(dir.join("turbo.json"), "{}").unwrap();
+
+        let mut pattern = Pattern::new("turbo-ws", ".turbo", "turbo.json");
+        let mut ancestors = SentinelCache::default();
+        assert!(!pattern_matches(&turbo, &pattern, &mut ancestors));
+
+        pattern.sentinel_depth = 2;
+        assert!(!pattern_matches(&turbo, &pattern, &mut ancestors));
+
+        pattern.sentinel_depth = 3;
+        assert!(pattern_matches(&turbo, &pattern, &mut ancestors));
+        // The workspace root lookup is remembered for sibling packages
+        let key = (dir.clone(), "turbo.json".to_string(), SentinelType::Any);
+        assert_eq!(ancestors.found.get(&key), Some(&true));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_checks_each_sentinel_once_per_directory() {
+        let dir = test_dir("tmignore_test_sentinel_cache");
+        let packages = 20;
+        for i in 0..packages {
+            let package = dir.join(format!("packages/p{i}"));
+            for build_dir in ["node_modules", ".next", ".turbo"] {
+                fs::create_dir_all(package.join(build_dir)).unwrap();
+            }
+            fs::write(package.join("package.json"), "{}").unwrap();
+            fs::write(package.join("App.csproj"), "").unwrap();
+            fs::create_dir_all(package.join("bin")).unwrap();
+            fs::create_dir_all(package.join("obj")).unwrap();
+        }
+
+        let patterns = vec![
+            Pattern::new("node", "node_modules", "package.json"),
+            Pattern::new("next", ".next", "package.json"),
+            Pattern::new("turbo", ".turbo", "package.json"),
+            Pattern::new("dotnet-bin", "bin", "*.csproj"),
+            Pattern::new("dotnet-obj", "obj", "*.csproj"),
+        ];
+        let result = scan_optimized(&test_config(&dir), &patterns);
+
+        assert_eq!(result.matches.len(), packages * 5);
+        // Without the cache this would be one lookup per match (100)
+        assert_eq!(result.stats.sentinel_checks, packages * 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_reports_every_pattern_sharing_a_directory() {
+        let dir = test_dir("tmignore_test_shared_names");
+        fs::create_dir_all(dir.join("app/vendor")).unwrap();
+        fs::write(dir.join("app/composer.json"), "{}").unwrap();
+        fs::write(dir.join("app/Gemfile"), "").unwrap();
+        let config = test_config(&dir);
+
+        let found = |disabled: &[&str]| {
+            let disabled: Vec<String> = disabled.iter().map(|s| s.to_string()).collect();
+            let patterns = crate::patterns::resolve_patterns(&disabled, &[], &[], &[]);
+            scan_optimized(&config, &patterns)
+                .matches
+                .into_iter()
+                .map(|m| (m.pattern_name, m.also_matched))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            found(&[]),
+            vec![("composer".to_string(), vec!["bundler".to_string()])]
+        );
+        // Still excluded while any enabled pattern matches
+        assert_eq!(found(&["composer"]), vec![("bundler".to_string(), vec![])]);
+        assert!(found(&["composer", "bundler"]).is_empty());
+
+        // The first policy stops at the most specific match
+        let mut config = config;
+        config.match_policy = MatchPolicy::First;
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let result = scan_optimized(&config, &patterns);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].pattern_name, "composer");
+        assert!(result.matches[0].also_matched.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_prefers_the_most_specific_pattern() {
+        let dir = test_dir("tmignore_test_specificity");
+        fs::create_dir_all(dir.join("site/dist")).unwrap();
+        fs::write(dir.join("site/astro.config.mjs"), "").unwrap();
+        fs::write(dir.join("site/vite.config.ts"), "").unwrap();
+        fs::write(dir.join("site/package.json"), "{}").unwrap();
+
+        // astro is declared first, but vite also requires package.json
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let result = scan_optimized(&test_config(&dir), &patterns);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].pattern_name, "vite");
+        assert_eq!(result.matches[0].also_matched, vec!["astro"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_large_files() {
+        let dir = test_dir("tmignore_test_file_patterns");
+        fs::create_dir_all(dir.join("vms/disk.img")).unwrap();
+        fs::write(dir.join("vms/big.img"), vec![1u8; 256 * 1024]).unwrap();
+        fs::write(dir.join("vms/small.img"), b"tiny").unwrap();
+        fs::create_dir_all(dir.join("models")).unwrap();
+        fs::write(dir.join("models/weights.bin"), vec![1u8; 256 * 1024]).unwrap();
+        fs::create_dir_all(dir.join("project")).unwrap();
+        fs::write(dir.join("project/weights.bin"), vec![1u8; 256 * 1024]).unwrap();
+        fs::write(dir.join("project/train.py"), "").unwrap();
+
+        let mut weights = Pattern::file("weights", "*.bin", 64 * 1024);
+        weights.sentinel = "train.py".to_string();
+        let patterns = vec![Pattern::file("images", "*.img", 64 * 1024), weights];
+        let result = scan_optimized(&test_config(&dir), &patterns);
+
+        let mut found: Vec<(PathBuf, &str)> = result
+            .matches
+            .iter()
+            .map(|m| (m.path.clone(), m.pattern_name.as_str()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (dir.join("project/weights.bin"), "weights"),
+                (dir.join("vms/big.img"), "images"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_reports_hard_linked_files_once() {
+        let dir = test_dir("tmignore_test_hard_links");
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a/disk.img"), vec![1u8; 256 * 1024]).unwrap();
+        fs::hard_link(dir.join("a/disk.img"), dir.join("b/disk.img")).unwrap();
+
+        let patterns = vec![Pattern::file("images", "*.img", 64 * 1024)];
+        let result = scan_optimized(&test_config(&dir), &patterns);
+        assert_eq!(result.matches.len(), 1, "{:?}", result.matches);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_user_facing_strips_data_volume() {
+        // Stand-in for the firmlink: `dir/Users` reaches `dir/Data/Users`
+        let dir = test_dir("tmignore_test_data_volume");
+        let data = dir.join("Data");
+        fs::create_dir_all(data.join("Users/me/app")).unwrap();
+        fs::create_dir_all(data.join("private/var")).unwrap();
+        std::os::unix::fs::symlink(data.join("Users"), dir.join("Users")).unwrap();
+
+        assert_eq!(
+            user_facing_in(data.join("Users/me/app"), &data, &dir),
+            dir.join("Users/me/app")
+        );
+        // No user-facing counterpart, so the data-volume spelling is kept
+        assert_eq!(
+            user_facing_in(data.join("private/var"), &data, &dir),
+            data.join("private/var")
+        );
+        assert_eq!(
+            user_facing_in(dir.join("Users/me"), &data, &dir),
+            dir.join("Users/me")
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_descends_into_matches_when_asked() {
+        let dir = test_dir("tmignore_test_descend");
+        let build = dir.join("app/build");
+        fs::create_dir_all(build.join("vendor/lib/node_modules")).unwrap();
+        fs::write(dir.join("app/build.sh"), "").unwrap();
+        fs::write(build.join("vendor/lib/package.json"), "{}").unwrap();
+
+        let mut outer = Pattern::new("scripted-build", "build", "build.sh");
+        let node = Pattern::new("node", "node_modules", "package.json");
+        let nested = build.join("vendor/lib/node_modules");
+
+        let config = test_config(&dir);
+        let result = scan_optimized(&config, &[outer.clone(), node.clone()]);
+        let paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        assert_eq!(paths, vec![&build]);
+
+        outer.descend = true;
+        let result = scan_optimized(&config, &[outer, node]);
+        let mut paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        paths.sort();
+        assert_eq!(paths, vec![&build, &nested]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_matches_sentinel_less_custom_patterns_anywhere() {
+        let dir = test_dir("tmignore_test_match_anywhere");
+        fs::create_dir_all(dir.join("app/.cache")).unwrap();
+        fs::create_dir_all(dir.join("notes/deep/.cache")).unwrap();
+
+        let mut custom = crate::config::CustomPattern::new("company-cache", ".cache", "");
+        custom.match_anywhere = true;
+        let result = scan_optimized(&test_config(&dir), &[Pattern::from(&custom)]);
+        let mut paths: Vec<PathBuf> = result.matches.into_iter().map(|m| m.path).collect();
+        paths.sort();
+        assert_eq!(paths, vec![dir.join("app/.cache"), dir.join("notes/deep/.cache")]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_uses_root_patterns_under_their_path() {
+        let dir = test_dir("tmignore_test_root_patterns");
+        for project in ["home/web", "archive/web", "archive/tool"] {
+            fs::create_dir_all(dir.join(project).join("node_modules")).unwrap();
+            fs::write(dir.join(project).join("package.json"), "{}").unwrap();
+        }
+        fs::create_dir_all(dir.join("home/tool/out")).unwrap();
+        fs::create_dir_all(dir.join("archive/tool/out")).unwrap();
+        for project in ["home/tool", "archive/tool"] {
+            fs::write(dir.join(project).join("build.ninja"), "").unwrap();
+        }
+
+        let mut config = test_config(&dir.join("home"));
+        config.scan_roots.push(dir.join("archive").to_string_lossy().to_string());
+        let scope = crate::config::RootPatterns {
+            disable_patterns: vec!["node".to_string()],
+            custom_patterns: vec![crate::config::CustomPattern::new("ninja", "out", "build.ninja")],
+        };
+        config.root_patterns.insert(dir.join("archive").to_string_lossy().to_string(), scope);
+
+        let node = Pattern::new("node", "node_modules", "package.json");
+        assert_eq!(
+            found(&config, &[node]),
+            vec![
+                (dir.join("archive/tool/out"), "ninja".to_string()),
+                (dir.join("home/web/node_modules"), "node".to_string()),
+            ]
+        );
+
+        // Directories found one at a time (watch, --changed-since) follow them too
+        let patterns = [Pattern::new("node", "node_modules", "package.json")];
+        let mut matcher = DirectoryMatcher::new(&config, &patterns, &ScanOptions::default(), []);
+        assert!(matcher.check(&dir.join("archive/web/node_modules")).is_none());
+        assert!(matcher.check(&dir.join("archive/tool/out")).is_some());
+        assert!(matcher.check(&dir.join("home/tool/out")).is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_without_patterns_only_checks_exclude_paths() {
+        let dir = test_dir("tmignore_test_no_patterns");
+        fs::create_dir_all(dir.join("media")).unwrap();
+        fs::create_dir_all(dir.join("app/node_modules")).unwrap();
+        fs::write(dir.join("app/package.json"), "{}").unwrap();
+
+        let mut config = test_config(&dir);
+        config.extra_exclude_paths = vec![dir.join("media").to_string_lossy().to_string()];
+        let result = scan_optimized(&config, &[]);
+
+        let paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        assert_eq!(paths, vec![&dir.join("media")]);
+        assert_eq!(result.stats.dirs_visited, 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_path_set_covers_descendants() {
+        let set = PathSet(HashSet::from([
+            PathBuf::from("/Users/me/Library"),
+            PathBuf::from("/Users/me/Library/Caches/skipped"),
+        ]));
+        let root = Path::new("/Users/me");
+        assert!(set.covers(Path::new("/Users/me/Library"), root));
+        assert!(set.covers(Path::new("/Users/me/Library/Caches/foo"), root));
+        assert!(!set.covers(Path::new("/Users/me/Libraryish"), root));
+        assert!(!set.covers(root, root));
+
+        // A root inside a skip path is walked, but skip paths below it still apply
+        let inner = Path::new("/Users/me/Library/Caches");
+        assert!(!set.covers(inner, inner));
+        assert!(!set.covers(&inner.join("foo"), inner));
+        assert!(set.covers(&inner.join("skipped/foo"), inner));
+        assert_eq!(set.enclosing(&inner.join("foo")), Some(Path::new("/Users/me/Library")));
+    }
+
+    #[test]
+    fn test_scan_skips_extra_skip_paths() {
+        let dir = test_dir("tmignore_test_extra_skip");
+        for project in ["kept", "skipped"] {
+            fs::create_dir_all(dir.join(project).join("node_modules")).unwrap();
+            fs::write(dir.join(project).join("package.json"), "{}").unwrap();
+        }
+
+        let config = test_config(&dir);
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let options = ScanOptions {
+            extra_skip_paths: vec![dir.join("skipped").to_string_lossy().to_string()],
+            ..ScanOptions::default()
+        };
+
+        let result = scan_with_options(&config, &patterns, &options);
+        assert!(result.matches.iter().any(|m| m.path == dir.join("kept/node_modules")));
+        assert!(!result.matches.iter().any(|m| m.path.starts_with(dir.join("skipped"))));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_skip_hidden_keeps_pattern_dirs() {
+        let dir = test_dir("tmignore_test_skip_hidden");
+        // A project hidden inside a dot-directory is pruned...
+        fs::create_dir_all(dir.join(".cache/tool/node_modules")).unwrap();
+        fs::write(dir.join(".cache/tool/package.json"), "{}").unwrap();
+        // ...but hidden directories a pattern targets are still found
+        fs::create_dir_all(dir.join("app/.venv")).unwrap();
+        fs::write(dir.join("app/.venv/pyvenv.cfg"), "").unwrap();
+
+        let mut config = test_config(&dir);
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+
+        let result = scan_optimized(&config, &patterns);
+        assert_eq!(result.matches.len(), 2);
+        assert_eq!(result.stats.hidden_pruned, 0);
+
+        config.skip_hidden = true;
+        let result = scan_optimized(&config, &patterns);
+        let paths: Vec<_> = result.matches.iter().map(|m| m.path.clone()).collect();
+        assert_eq!(paths, vec![dir.join("app/.venv")]);
+        assert_eq!(result.stats.hidden_pruned, 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_reuses_prior_results_for_unchanged_dirs() {
+        let dir = test_dir("tmignore_test_prior_scan");
+        for project in ["cached", "uncached"] {
+            fs::create_dir_all(dir.join(project).join("node_modules")).unwrap();
+            fs::write(dir.join(project).join("package.json"), "{}").unwrap();
+        }
+        let config = test_config(&dir);
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let prior_matches = HashMap::from([(
+            dir.join("cached/node_modules"),
+            PriorMatch {
+                pattern_name: "node".to_string(),
+                excluded: true,
+            },
+        )]);
+
+        // Nothing changed since a scan "in the future": only cached results count,
+        // so the project the earlier scan didn't know about is not rediscovered
+        let options = ScanOptions {
+            prior: Some(PriorScan {
+                scanned_at: SystemTime::now() + Duration::from_secs(60),
+                matches: prior_matches.clone(),
+            }),
+            ..ScanOptions::default()
+        };
+        let result = scan_with_options(&config, &patterns, &options);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].path, dir.join("cached/node_modules"));
+        assert!(result.matches[0].known_excluded);
+        assert_eq!(result.stats.reused, 2);
+
+        // Everything changed since a scan at the epoch: all candidates are re-checked
+        let options = ScanOptions {
+            prior: Some(PriorScan {
+                scanned_at: SystemTime::UNIX_EPOCH,
+                matches: prior_matches,
+            }),
+            ..ScanOptions::default()
+        };
+        let result = scan_with_options(&config, &patterns, &options);
+        assert_eq!(result.matches.len(), 2);
+        assert!(result.matches.iter().all(|m| !m.known_excluded));
+        assert_eq!(result.stats.reused, 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_patterns_and_roots() {
+        let config = test_config(Path::new("/tmp/tmignore_fp"));
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let options = ScanOptions::default();
+        let base = fingerprint(&config, &patterns, &options);
+
+        assert_eq!(fingerprint(&config, &patterns, &options), base);
+        assert_ne!(fingerprint(&config, &patterns[1..], &options), base);
+
+        let mut other_root = config.clone();
+        other_root.scan_roots.push("/tmp/elsewhere".to_string());
+        assert_ne!(fingerprint(&other_root, &patterns, &options), base);
+    }
+
+    #[test]
+    fn test_scan_finds_phoenix_static_only_with_assets() {
+        let dir = test_dir("tmignore_test_phoenix");
+        for app in ["web", "api"] {
+            fs::create_dir_all(dir.join(app).join("priv/static")).unwrap();
+            fs::write(dir.join(app).join("mix.exs"), "").unwrap();
+        }
+        // Only the web app has an assets/ directory
+        fs::create_dir_all(dir.join("web/assets")).unwrap();
+
+        let config = test_config(&dir);
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+
+        let result = scan_optimized(&config, &patterns);
+        assert!(
+            result
+                .matches
+                .iter()
+                .any(|m| m.pattern_name == "phoenix-static" && m.path == dir.join("web/priv/static"))
+        );
+        assert!(!result.matches.iter().any(|m| m.path == dir.join("api/priv/static")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_unity_folders_only_with_both_sentinels() {
+        let dir = test_dir("tmignore_test_unity");
+        for folder in ["Library", "Temp", "obj", "Logs", "Assets", "ProjectSettings"] {
+            fs::create_dir_all(dir.join("game").join(folder)).unwrap();
+        }
+        fs::write(dir.join("game/Assembly-CSharp.csproj"), "").unwrap();
+        // A Library folder next to Assets alone isn't a Unity project
+        fs::create_dir_all(dir.join("docs/Library")).unwrap();
+        fs::create_dir_all(dir.join("docs/Assets")).unwrap();
+        // Nor is a ProjectSettings file standing in for the directory
+        fs::create_dir_all(dir.join("notes/Library")).unwrap();
+        fs::create_dir_all(dir.join("notes/Assets")).unwrap();
+        fs::write(dir.join("notes/ProjectSettings"), "").unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        assert_eq!(
+            found(&test_config(&dir), &patterns),
+            vec![
+                (dir.join("game/Library"), "unity".to_string()),
+                (dir.join("game/Logs"), "unity-logs".to_string()),
+                (dir.join("game/Temp"), "unity-temp".to_string()),
+                // Unity's generated .csproj files make this dotnet-obj too
+                (dir.join("game/obj"), "unity-obj".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_excludes_bazel_output_base_behind_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = test_dir("tmignore_test_bazel");
+        // Bazel keeps build output in an output base outside the workspace
+        let output_base = dir.join("cache/_bazel_me/0123abcd");
+        let execroot = output_base.join("execroot/_main");
+        fs::create_dir_all(execroot.join("bazel-out/darwin-fastbuild/bin")).unwrap();
+        fs::create_dir_all(output_base.join("external")).unwrap();
+
+        let workspace = dir.join("code/app");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::write(workspace.join("MODULE.bazel"), "").unwrap();
+        symlink(execroot.join("bazel-out"), workspace.join("bazel-out")).unwrap();
+        symlink(execroot.join("bazel-out/darwin-fastbuild/bin"), workspace.join("bazel-bin")).unwrap();
+        symlink(&execroot, workspace.join("bazel-app")).unwrap();
+        // Not a workspace, and a link whose target has no execroot above it
+        fs::create_dir_all(dir.join("code/other")).unwrap();
+        symlink(execroot.join("bazel-out"), dir.join("code/other/bazel-out")).unwrap();
+        let legacy = dir.join("code/legacy");
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("WORKSPACE"), "").unwrap();
+        symlink(dir.join("cache"), legacy.join("bazel-cache")).unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let mut config = test_config(&dir);
+        config.scan_roots = vec![dir.join("code").to_string_lossy().to_string()];
+        let result = scan_optimized(&config, &patterns);
+        let found: Vec<(&Path, &str)> = result
+            .matches
+            .iter()
+            .map(|m| (m.path.as_path(), m.pattern_name.as_str()))
+            .collect();
+        assert_eq!(found, vec![(output_base.as_path(), "bazel-module")]);
+
+        // Following symlinks reports the output base once instead of walking it
+        config.follow_symlinks = true;
+        let result = scan_optimized(&config, &patterns);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].path, output_base);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_directory_matcher_checks_new_directories() {
+        let dir = test_dir("tmignore_test_directory_matcher");
+        for project in ["old", "new", ".hidden/app", "skipped/app"] {
+            fs::create_dir_all(dir.join(project)).unwrap();
+            fs::write(dir.join(project).join("package.json"), "{}").unwrap();
+        }
+        fs::create_dir_all(dir.join("old/node_modules")).unwrap();
+
+        let mut config = test_config(&dir);
+        config.skip_hidden = true;
+        let options = ScanOptions {
+            extra_skip_paths: vec![dir.join("skipped").to_string_lossy().to_string()],
+            ..ScanOptions::default()
         };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let previous = scan_with_options(&config, &patterns, &options);
+        let mut matcher = DirectoryMatcher::new(
+            &config,
+            &patterns,
+            &options,
+            previous.matches.into_iter().map(|m| m.path),
+        );
+        assert_eq!(matcher.roots(), std::slice::from_ref(&dir));
 
-        let patterns = vec![Pattern {
-            name: "node".to_string(),
-            directory: "node_modules".to_string(),
-            sentinel: "package.json".to_string(),
-        }];
+        for created in [
+            "new/node_modules",
+            "old/node_modules/dep/node_modules",
+            ".hidden/app/node_modules",
+            "skipped/app/node_modules",
+        ] {
+            fs::create_dir_all(dir.join(created)).unwrap();
+        }
+        let found = matcher.check(&dir.join("new/node_modules")).unwrap();
+        assert_eq!(found.pattern_name, "node");
+        // Nested in a match, hidden, skipped, outside the roots, or no pattern
+        assert!(matcher.check(&dir.join("new/node_modules")).is_none());
+        assert!(matcher.check(&dir.join("old/node_modules/dep/node_modules")).is_none());
+        assert!(matcher.check(&dir.join(".hidden/app/node_modules")).is_none());
+        assert!(matcher.check(&dir.join("skipped/app/node_modules")).is_none());
+        assert!(matcher.check(Path::new("/elsewhere/node_modules")).is_none());
+        assert!(matcher.check(&dir.join("new")).is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_records_walked_directories() {
+        let dir = test_dir("tmignore_test_record_walked");
+        for project in ["app/src", ".hidden/app", "skipped/app"] {
+            fs::create_dir_all(dir.join(project)).unwrap();
+        }
+        fs::write(dir.join("app/package.json"), "{}").unwrap();
+        fs::create_dir_all(dir.join("app/node_modules/dep")).unwrap();
+
+        let mut config = test_config(&dir);
+        config.skip_hidden = true;
+        let options = ScanOptions {
+            extra_skip_paths: vec![dir.join("skipped").to_string_lossy().to_string()],
+            record_walked: true,
+            ..ScanOptions::default()
+        };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let result = scan_with_options(&config, &patterns, &options);
+        let mut walked = result.stats.walked.clone();
+        walked.sort();
+        // Skipped, hidden and matched directories are left out
+        assert_eq!(walked, vec![dir.clone(), dir.join("app"), dir.join("app/src")]);
+
+        let matcher = DirectoryMatcher::new(
+            &config,
+            &patterns,
+            &options,
+            result.matches.into_iter().map(|m| m.path),
+        );
+        for path in &walked {
+            assert!(matcher.walks(path), "{}", path.display());
+        }
+        for path in ["skipped/app", ".hidden/app", "app/node_modules", "app/node_modules/dep"] {
+            assert!(!matcher.walks(&dir.join(path)), "{path}");
+        }
+        assert!(!matcher.walks(Path::new("/elsewhere")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_nested_python_caches() {
+        let dir = test_dir("tmignore_test_python_caches");
+        // A setup.cfg project stands in for pyproject.toml
+        fs::write(dir.join("setup.cfg"), "").unwrap();
+        let caches = [".mypy_cache", "__pycache__", "src/pkg/__pycache__", "src/pkg/sub/__pycache__"];
+        for cache in caches {
+            fs::create_dir_all(dir.join(cache)).unwrap();
+        }
+        // Scripts outside any Python project are left alone
+        fs::create_dir_all(dir.join("../tmignore_test_python_loose/__pycache__")).unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        assert_eq!(
+            found(&test_config(&dir), &patterns),
+            vec![
+                (dir.join(".mypy_cache"), "python-mypy".to_string()),
+                (dir.join("__pycache__"), "python-pycache".to_string()),
+                (dir.join("src/pkg/__pycache__"), "python-pycache".to_string()),
+                (dir.join("src/pkg/sub/__pycache__"), "python-pycache".to_string()),
+            ]
+        );
+
+        let loose = dir.with_file_name("tmignore_test_python_loose");
+        assert!(scan_optimized(&test_config(&loose), &patterns).matches.is_empty());
+
+        // The whole family can be turned off at once
+        let disabled = ["python-caches".to_string()];
+        let patterns = crate::patterns::resolve_patterns(&disabled, &[], &[], &[]);
+        assert!(scan_optimized(&test_config(&dir), &patterns).matches.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(&loose);
+    }
+
+    #[test]
+    fn test_scan_finds_ml_caches() {
+        let dir = test_dir("tmignore_test_ml_caches");
+        fs::create_dir_all(dir.join("project/notebooks/.ipynb_checkpoints")).unwrap();
+        fs::write(dir.join("project/notebooks/train.ipynb"), "").unwrap();
+        fs::write(dir.join("project/environment.yml"), "").unwrap();
+        fs::create_dir_all(dir.join("project/wandb")).unwrap();
+        fs::create_dir_all(dir.join("project/mlruns")).unwrap();
+        fs::create_dir_all(dir.join("miniconda3/conda-meta")).unwrap();
+        fs::create_dir_all(dir.join("miniconda3/envs/torch/conda-meta")).unwrap();
+        // Checkpoints without notebooks and envs outside a conda install stay
+        fs::create_dir_all(dir.join("empty/.ipynb_checkpoints")).unwrap();
+        fs::create_dir_all(dir.join("app/envs")).unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        assert_eq!(
+            found(&test_config(&dir), &patterns),
+            vec![
+                (dir.join("miniconda3/envs"), "conda-envs".to_string()),
+                (dir.join("project/mlruns"), "mlflow".to_string()),
+                (dir.join("project/notebooks/.ipynb_checkpoints"), "jupyter".to_string()),
+                (dir.join("project/wandb"), "wandb".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_static_site_output() {
+        let dir = test_dir("tmignore_test_static_sites");
+        let hugo = dir.join("blog");
+        fs::create_dir_all(hugo.join("content/posts")).unwrap();
+        fs::create_dir_all(hugo.join("public")).unwrap();
+        fs::create_dir_all(hugo.join("resources/_gen/images")).unwrap();
+        fs::write(hugo.join("hugo.toml"), "").unwrap();
+        let zola = dir.join("docs");
+        for sub in ["content", "templates", "public"] {
+            fs::create_dir_all(zola.join(sub)).unwrap();
+        }
+        fs::write(zola.join("config.toml"), "").unwrap();
+        let jekyll = dir.join("pages");
+        fs::create_dir_all(jekyll.join("_site")).unwrap();
+        fs::create_dir_all(jekyll.join(".jekyll-cache")).unwrap();
+        fs::write(jekyll.join("_config.yml"), "").unwrap();
+        let eleventy = dir.join("notes");
+        fs::create_dir_all(eleventy.join("_site")).unwrap();
+        fs::write(eleventy.join("eleventy.config.mjs"), "").unwrap();
+        // A public folder next to an unrelated config.toml is kept
+        fs::create_dir_all(dir.join("app/public")).unwrap();
+        fs::write(dir.join("app/config.toml"), "").unwrap();
+        fs::create_dir_all(dir.join("web/public")).unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        assert_eq!(
+            found(&test_config(&dir), &patterns),
+            vec![
+                (hugo.join("public"), "hugo".to_string()),
+                (hugo.join("resources/_gen"), "hugo-resources".to_string()),
+                (zola.join("public"), "zola".to_string()),
+                (eleventy.join("_site"), "eleventy".to_string()),
+                (jekyll.join(".jekyll-cache"), "jekyll-cache".to_string()),
+                (jekyll.join("_site"), "jekyll".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_android_and_react_native_builds() {
+        let dir = test_dir("tmignore_test_android");
+        let app = dir.join("rn-app");
+        fs::create_dir_all(app.join(".expo")).unwrap();
+        fs::write(app.join("package.json"), "{}").unwrap();
+        fs::create_dir_all(app.join("android/.gradle")).unwrap();
+        fs::write(app.join("android/build.gradle"), "").unwrap();
+        fs::write(app.join("android/settings.gradle"), "").unwrap();
+        fs::create_dir_all(app.join("android/build")).unwrap();
+        fs::create_dir_all(app.join("android/app/build/intermediates")).unwrap();
+        fs::create_dir_all(app.join("android/app/.cxx")).unwrap();
+        fs::write(app.join("android/app/build.gradle.kts"), "").unwrap();
+        // A `build` without Gradle intermediates isn't Android's
+        fs::create_dir_all(dir.join("site/build")).unwrap();
+        fs::write(dir.join("site/build.gradle"), "").unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        assert_eq!(
+            found(&test_config(&dir), &patterns),
+            vec![
+                (app.join(".expo"), "expo".to_string()),
+                (app.join("android/.gradle"), "gradle".to_string()),
+                (app.join("android/app/.cxx"), "android-cxx".to_string()),
+                (app.join("android/app/build"), "android-module-build".to_string()),
+                (app.join("android/build"), "android-build".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_js_tool_caches_and_extras() {
+        let dir = test_dir("tmignore_test_js_extras");
+        fs::write(dir.join("package.json"), "{}").unwrap();
+        for cache in [".vite", ".wrangler", ".cache", "coverage"] {
+            fs::create_dir_all(dir.join(cache)).unwrap();
+        }
+        let scan = |enable: &[String]| {
+            let patterns = crate::patterns::resolve_patterns(&[], enable, &[], &[]);
+            found(&test_config(&dir), &patterns)
+        };
+
+        // .cache and coverage wait for the js-extras group to be enabled
+        let defaults = vec![
+            (dir.join(".vite"), "vite-cache".to_string()),
+            (dir.join(".wrangler"), "wrangler".to_string()),
+        ];
+        assert_eq!(scan(&[]), defaults);
+
+        let mut extras = defaults.clone();
+        extras.insert(0, (dir.join(".cache"), "js-cache".to_string()));
+        extras.push((dir.join("coverage"), "js-coverage".to_string()));
+        extras.sort();
+        assert_eq!(scan(&["js-extras".to_string()]), extras);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_cmake_and_meson_build_trees() {
+        let dir = test_dir("tmignore_test_cmake");
+        let cpp = dir.join("engine");
+        fs::create_dir_all(cpp.join("build")).unwrap();
+        fs::write(cpp.join("build/CMakeCache.txt"), "").unwrap();
+        fs::write(cpp.join("CMakeLists.txt"), "").unwrap();
+        fs::write(cpp.join("CMakePresets.json"), "{}").unwrap();
+        for sub in ["cmake-build-debug", "cmake-build-release", "out/build", ".cache/clangd"] {
+            fs::create_dir_all(cpp.join(sub)).unwrap();
+        }
+        fs::create_dir_all(dir.join("tool/builddir")).unwrap();
+        fs::write(dir.join("tool/meson.build"), "").unwrap();
+        // A build directory without a CMake cache, e.g. a docs site's output
+        fs::create_dir_all(dir.join("docs/build")).unwrap();
+        fs::write(dir.join("docs/CMakeLists.txt"), "").unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let mut config = test_config(&dir);
+        // .cache is entered for clangd's index even when hidden folders are skipped
+        config.skip_hidden = true;
+        assert_eq!(
+            found(&config, &patterns),
+            vec![
+                (cpp.join(".cache/clangd"), "clangd".to_string()),
+                (cpp.join("build"), "cmake".to_string()),
+                (cpp.join("cmake-build-debug"), "cmake-clion".to_string()),
+                (cpp.join("cmake-build-release"), "cmake-clion".to_string()),
+                (cpp.join("out"), "cmake-presets".to_string()),
+                (dir.join("tool/builddir"), "meson".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_gitignore_entry() {
+        let simple = [("build/", "build"), ("/out", "out"), ("**/tmp", "tmp"), ("dist-*", "dist-*")];
+        for (line, entry) in simple {
+            assert_eq!(gitignore_entry(line), Some(entry), "{line}");
+        }
+        for line in ["# build", "!keep/", "src/gen/", "*", "/**/"] {
+            assert_eq!(gitignore_entry(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_scan_uses_gitignore_in_repositories() {
+        let dir = test_dir("tmignore_test_gitignore");
+        let gitignore = "# outputs\n/build-output/\ndist-*\nnode_modules\nsrc/gen\n!keep\n*.log\n\
+                         .*\n!.github/\n!.vscode\n";
+        for project in ["repo", "plain"] {
+            let root = dir.join(project);
+            let subs = [
+                "build-output",
+                "dist-web",
+                "node_modules",
+                "src/gen",
+                "keep",
+                "sub/build-output",
+                ".cache",
+                ".github",
+                ".vscode",
+            ];
+            for sub in subs {
+                fs::create_dir_all(root.join(sub)).unwrap();
+            }
+            fs::write(root.join(".gitignore"), gitignore).unwrap();
+            fs::write(root.join("package.json"), "{}").unwrap();
+            fs::write(root.join("debug.log"), "").unwrap();
+        }
+        fs::create_dir_all(dir.join("repo/.git")).unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let mut config = test_config(&dir);
+        let node = |project: &str| (dir.join(project).join("node_modules"), "node".to_string());
+        assert_eq!(found(&config, &patterns), vec![node("plain"), node("repo")]);
+
+        // Only directories at the top of an actual repository, and patterns win.
+        // Tracked directories a `!` line re-includes are left alone.
+        config.use_gitignore = true;
+        assert_eq!(
+            found(&config, &patterns),
+            vec![
+                node("plain"),
+                (dir.join("repo/.cache"), GITIGNORE_PATTERN.to_string()),
+                (dir.join("repo/build-output"), GITIGNORE_PATTERN.to_string()),
+                (dir.join("repo/dist-web"), GITIGNORE_PATTERN.to_string()),
+                node("repo"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_only_dirs_skips_walk() {
+        let dir = test_dir("tmignore_test_only_dirs");
+        for project in ["old", "new"] {
+            fs::create_dir_all(dir.join(project).join("node_modules/pkg")).unwrap();
+            fs::write(dir.join(project).join("package.json"), "{}").unwrap();
+        }
+        let changed = [dir.join("new/node_modules/pkg/index.js"), dir.join("new/package.json")];
+        let candidates = changed_dirs(&dir, changed.iter().map(PathBuf::as_path));
+        assert_eq!(
+            candidates,
+            vec![dir.join("new"), dir.join("new/node_modules"), dir.join("new/node_modules/pkg")]
+        );
+
+        let options = ScanOptions {
+            only_dirs: Some(candidates),
+            ..ScanOptions::default()
+        };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let result = scan_with_options(&test_config(&dir), &patterns, &options);
+        let paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        assert_eq!(paths, vec![&dir.join("new/node_modules")]);
+        assert_eq!(result.stats.dirs_visited, 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_streaming_stops_on_break() {
+        let dir = test_dir("tmignore_test_streaming");
+        for project in ["a", "b", "c"] {
+            fs::create_dir_all(dir.join(project).join("node_modules")).unwrap();
+            fs::write(dir.join(project).join("package.json"), "{}").unwrap();
+        }
+        fs::create_dir_all(dir.join("media")).unwrap();
+        let mut config = test_config(&dir);
+        config.extra_exclude_paths = vec![dir.join("media").to_string_lossy().to_string()];
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+
+        let mut streamed = Vec::new();
+        scan_streaming(&config, &patterns, &ScanOptions::default(), |m| {
+            streamed.push(m.path);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(streamed.len(), 4);
+
+        // Breaking ends the walk and skips the exclude paths
+        let mut streamed = Vec::new();
+        scan_streaming(&config, &patterns, &ScanOptions::default(), |m| {
+            streamed.push(m.path);
+            ControlFlow::Break(())
+        });
+        assert_eq!(streamed.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_finds_frontend_build_output() {
+        let dir = test_dir("tmignore_test_frontend_builds");
+        fs::create_dir_all(dir.join("vite-app/dist")).unwrap();
+        fs::write(dir.join("vite-app/vite.config.ts"), "").unwrap();
+        fs::write(dir.join("vite-app/package.json"), "{}").unwrap();
+        // A vite config without package.json is not a vite project
+        fs::create_dir_all(dir.join("stray/dist")).unwrap();
+        fs::write(dir.join("stray/vite.config.ts"), "").unwrap();
+        fs::create_dir_all(dir.join("ui/.storybook")).unwrap();
+        fs::create_dir_all(dir.join("ui/storybook-static")).unwrap();
+        fs::write(dir.join("ui/.storybook/main.js"), "").unwrap();
+
+        let config = test_config(&dir);
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let result = scan_optimized(&config, &patterns);
+
+        let mut found: Vec<_> = result
+            .matches
+            .iter()
+            .map(|m| (m.path.strip_prefix(&dir).unwrap().to_path_buf(), m.pattern_name.as_str()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (PathBuf::from("ui/storybook-static"), "storybook"),
+                (PathBuf::from("vite-app/dist"), "vite"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_dedups_exclude_paths() {
+        let dir = test_dir("tmignore_test_dedup_excludes");
+        fs::create_dir_all(dir.join("Movies")).unwrap();
+        let movies = dir.join("Movies").to_string_lossy().to_string();
+
+        let config = Config {
+            scan_roots: vec![],
+            // Same directory, spelled with and without a trailing slash
+            extra_exclude_paths: vec![movies.clone(), format!("{movies}/")],
+            ..test_config(&dir)
+        };
+
+        let result = scan_optimized(&config, &[]);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].path, dir.join("Movies"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_last_modified_uses_newest_child() {
+        let dir = test_dir("tmignore_test_last_modified");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+
+        let old = SystemTime::now() - std::time::Duration::from_secs(90 * 86400);
+        fs::File::open(&dir).unwrap().set_modified(old).unwrap();
+        fs::File::open(dir.join("sub")).unwrap().set_modified(old).unwrap();
+        assert_eq!(last_modified(&dir), Some(old));
+
+        fs::write(dir.join("fresh.txt"), "").unwrap();
+        fs::File::open(&dir).unwrap().set_modified(old).unwrap();
+        assert!(last_modified(&dir).unwrap() > old);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_last_modified_of_unlistable_directory() {
+        use std::os::unix::fs::PermissionsExt;
+        // Root can list any directory
+        if nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let dir = test_dir("tmignore_test_last_modified_unlistable");
+        let old = SystemTime::now() - std::time::Duration::from_secs(90 * 86400);
+        fs::File::open(&dir).unwrap().set_modified(old).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o000)).unwrap();
+        assert_eq!(last_modified(&dir), Some(old));
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_time_budget_truncates() {
+        let dir = test_dir("tmignore_test_time_budget");
+        fs::create_dir_all(dir.join("project/node_modules")).unwrap();
+        fs::write(dir.join("project/package.json"), "{}").unwrap();
+        fs::create_dir_all(dir.join("Movies")).unwrap();
+
+        let config = Config {
+            extra_exclude_paths: vec![dir.join("Movies").to_string_lossy().to_string()],
+            ..test_config(&dir)
+        };
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        let options = ScanOptions {
+            time_budget: Some(Duration::ZERO),
+            ..ScanOptions::default()
+        };
+
+        let result = scan_with_options(&config, &patterns, &options);
+        assert!(result.stats.truncated);
+        assert_eq!(result.stats.dirs_visited, 0);
+        // Exclude paths are still applied when the walk is cut short
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].pattern_name, "exclude_path");
+
+        let untimed = scan_optimized(&config, &patterns);
+        assert!(!untimed.stats.truncated);
+        assert!(untimed.matches.iter().any(|m| m.pattern_name == "node"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_cancel_stops_walk() {
+        static CANCELLED: AtomicBool = AtomicBool::new(true);
+        let dir = test_dir("tmignore_test_cancel");
+        fs::create_dir_all(dir.join("app/node_modules")).unwrap();
+        fs::write(dir.join("app/package.json"), "{}").unwrap();
+
+        let options = ScanOptions {
+            cancel: Some(&CANCELLED),
+            ..ScanOptions::default()
+        };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let result = scan_with_options(&test_config(&dir), &patterns, &options);
+        assert!(result.stats.interrupted);
+        assert!(result.matches.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_resolves_symlinked_root() {
+        let dir = test_dir("tmignore_test_symlink_root");
+        let real = dir.join("real");
+        fs::create_dir_all(real.join("app/node_modules")).unwrap();
+        fs::write(real.join("app/package.json"), "{}").unwrap();
+        fs::create_dir_all(real.join("skipped/node_modules")).unwrap();
+        fs::write(real.join("skipped/package.json"), "{}").unwrap();
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let config = test_config(&link);
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+        // The skip path is given through the symlink but must still apply
+        let options = ScanOptions {
+            extra_skip_paths: vec![link.join("skipped").to_string_lossy().to_string()],
+            ..ScanOptions::default()
+        };
 
-        let matches = scan_optimized(&config, &patterns);
-        assert!(matches.iter().any(|m| m.pattern_name == "node" && m.path.ends_with("node_modules")));
+        let result = scan_with_options(&config, &patterns, &options);
+        let paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        assert_eq!(paths, vec![&real.join("app/node_modules")]);
+        assert!(
+            result
+                .warnings
+                .iter()
+                .any(|w| w.kind == ScanWarningKind::RootResolved && w.path == link)
+        );
 
         let _ = fs::remove_dir_all(&dir);
     }
+
+    #[test]
+    fn test_scan_plain_root_has_no_resolve_warning() {
+        let dir = test_dir("tmignore_test_plain_root");
+        let config = test_config(&dir);
+        let result = scan_optimized(&config, &[]);
+        assert!(result.warnings.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_count_by_kind() {
+        let warning = |kind| ScanWarning {
+            path: PathBuf::from("/x"),
+            kind,
+            message: String::new(),
+        };
+        let warnings = vec![
+            warning(ScanWarningKind::PermissionDenied),
+            warning(ScanWarningKind::Io),
+            warning(ScanWarningKind::PermissionDenied),
+        ];
+
+        let counts = count_by_kind(&warnings);
+        assert_eq!(counts.get("permission_denied"), Some(&2));
+        assert_eq!(counts.get("io"), Some(&1));
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn test_collapse_nested_roots() {
+        let roots = vec![
+            PathBuf::from("/Users/me/Code/app"),
+            PathBuf::from("/Users/me"),
+            PathBuf::from("/Volumes/Code"),
+            PathBuf::from("/Users/me-backup"),
+        ];
+        let (kept, dropped) = collapse_nested_roots(roots, &[]);
+        assert_eq!(
+            kept,
+            vec![
+                PathBuf::from("/Users/me"),
+                PathBuf::from("/Users/me-backup"),
+                PathBuf::from("/Volumes/Code"),
+            ]
+        );
+        assert_eq!(
+            dropped,
+            vec![(PathBuf::from("/Users/me/Code/app"), PathBuf::from("/Users/me"))]
+        );
+    }
+
+    #[test]
+    fn test_collapse_identical_roots() {
+        let roots = vec![PathBuf::from("/Volumes/Code"), PathBuf::from("/Volumes/Code/")];
+        let (kept, dropped) = collapse_nested_roots(roots, &[]);
+        assert_eq!(kept, vec![PathBuf::from("/Volumes/Code")]);
+        assert_eq!(dropped.len(), 1);
+    }
+
+    #[test]
+    fn test_scan_duplicate_roots_with_trailing_slashes() {
+        let dir = test_dir("tmignore_test_duplicate_roots");
+        fs::create_dir_all(dir.join("app/node_modules")).unwrap();
+        fs::write(dir.join("app/package.json"), "{}").unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        let config = Config {
+            scan_roots: vec![root.clone(), format!("{}/", root), format!("{}//", root)],
+            ..test_config(&dir)
+        };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let result = scan_optimized(&config, &patterns);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.warnings.len(), 2, "{:?}", result.warnings);
+        assert!(result
+            .warnings
+            .iter()
+            .all(|w| w.kind == ScanWarningKind::DuplicateRoot && w.path == dir));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_collapse_roots_nested_after_tilde_expansion() {
+        let home = std::env::var("HOME").unwrap();
+        let roots = vec![
+            expand_tilde(&format!("{home}/Code")).unwrap(),
+            expand_tilde("~").unwrap(),
+        ];
+        let (kept, dropped) = collapse_nested_roots(roots, &[]);
+        assert_eq!(kept, vec![PathBuf::from(&home)]);
+        assert_eq!(dropped, vec![(PathBuf::from(&home).join("Code"), PathBuf::from(&home))]);
+    }
+
+    #[test]
+    fn test_scan_nested_roots_match_once() {
+        let dir = test_dir("tmignore_test_nested_roots");
+        fs::create_dir_all(dir.join("Code/app/node_modules")).unwrap();
+        fs::write(dir.join("Code/app/package.json"), "{}").unwrap();
+
+        let config = Config {
+            scan_roots: vec![
+                dir.join("Code").to_string_lossy().to_string(),
+                dir.to_string_lossy().to_string(),
+                dir.to_string_lossy().to_string(),
+            ],
+            ..test_config(&dir)
+        };
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+
+        let result = scan_optimized(&config, &patterns);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.stats.nested_roots.len(), 2);
+        // Only the exact duplicate is worth a warning
+        assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+        assert_eq!(result.warnings[0].kind, ScanWarningKind::DuplicateRoot);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_follow_symlinks_handles_cycles_and_diamonds() {
+        let dir = test_dir("tmignore_test_follow_symlinks");
+        let root = dir.join("Code");
+        let external = dir.join("External");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(external.join("app/node_modules")).unwrap();
+        fs::write(external.join("app/package.json"), "{}").unwrap();
+        // Two links to the same tree (diamond) and a link back to the root (cycle)
+        std::os::unix::fs::symlink(&external, root.join("ext-a")).unwrap();
+        std::os::unix::fs::symlink(&external, root.join("ext-b")).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("loop")).unwrap();
+
+        let patterns = crate::patterns::resolve_patterns(&[], &[], &[], &[]);
+
+        let not_following = scan_optimized(&test_config(&root), &patterns);
+        assert!(not_following.matches.is_empty());
+
+        let config = Config {
+            follow_symlinks: true,
+            ..test_config(&root)
+        };
+        let result = scan_optimized(&config, &patterns);
+        let paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        assert_eq!(paths, vec![&external.join("app/node_modules")]);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_reports_file_roots() {
+        let dir = test_dir("tmignore_test_file_root");
+        let file = dir.join("notes.txt");
+        let link = dir.join("notes-link");
+        fs::write(&file, "").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        for root in [&file, &link] {
+            let config = test_config(root);
+            let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+            let result = scan_optimized(&config, &patterns);
+            assert!(result.matches.is_empty());
+            assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+            assert_eq!(result.warnings[0].kind, ScanWarningKind::RootNotDirectory);
+            assert_eq!(&result.warnings[0].path, root);
+            assert!(result.warnings[0].to_string().starts_with("scan root is not a directory"));
+        }
+        let via_link = scan_optimized(&test_config(&link), &[]);
+        assert!(via_link.warnings[0].to_string().contains("resolves to"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_warns_when_root_inside_skip_path() {
+        let dir = test_dir("tmignore_test_root_in_skip");
+        let root = dir.join("Code");
+        fs::create_dir_all(root.join("app/node_modules")).unwrap();
+        fs::write(root.join("app/package.json"), "{}").unwrap();
+
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        for skipped in [&dir, &root] {
+            let options = ScanOptions {
+                extra_skip_paths: vec![skipped.to_string_lossy().to_string()],
+                ..ScanOptions::default()
+            };
+            let result = scan_with_options(&test_config(&root), &patterns, &options);
+            // Still scanned, since the root was asked for explicitly
+            assert_eq!(result.matches.len(), 1);
+            assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+            assert_eq!(result.warnings[0].kind, ScanWarningKind::RootInSkipPath);
+            assert_eq!(result.warnings[0].path, root);
+            assert_eq!(result.warnings[0].message, skipped.display().to_string());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_walks_nested_root_inside_skip_path() {
+        let dir = test_dir("tmignore_test_nested_root_in_skip");
+        let root = dir.join("Library/Caches/mydata");
+        fs::create_dir_all(root.join("app/node_modules")).unwrap();
+        fs::write(root.join("app/package.json"), "{}").unwrap();
+
+        let config = Config {
+            scan_roots: vec![
+                dir.to_string_lossy().to_string(),
+                root.to_string_lossy().to_string(),
+            ],
+            ..test_config(&dir)
+        };
+        let options = ScanOptions {
+            extra_skip_paths: vec![dir.join("Library").to_string_lossy().to_string()],
+            ..ScanOptions::default()
+        };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let result = scan_with_options(&config, &patterns, &options);
+        // Walked on its own, since walking the outer root stops at the skip path
+        let paths: Vec<&PathBuf> = result.matches.iter().map(|m| &m.path).collect();
+        assert_eq!(paths, vec![&root.join("app/node_modules")]);
+        assert!(result.stats.nested_roots.is_empty());
+        assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+        assert_eq!(result.warnings[0].kind, ScanWarningKind::RootInSkipPath);
+        assert_eq!(result.warnings[0].path, root);
+
+        let matcher = DirectoryMatcher::new(&config, &patterns, &options, []);
+        assert!(matcher.walks(&root.join("app")));
+        assert!(!matcher.walks(&dir.join("Library/other")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_warns_when_root_inside_exclude_path() {
+        let dir = test_dir("tmignore_test_root_in_exclude");
+        let root = dir.join("Code");
+        fs::create_dir_all(root.join("app/node_modules")).unwrap();
+        fs::write(root.join("app/package.json"), "{}").unwrap();
+
+        let config = Config {
+            extra_exclude_paths: vec![dir.to_string_lossy().to_string()],
+            ..test_config(&root)
+        };
+        let patterns = vec![Pattern::new("node", "node_modules", "package.json")];
+        let result = scan_optimized(&config, &patterns);
+        assert!(result.matches.iter().any(|m| m.path == root.join("app/node_modules")));
+        // Reported once, as the more specific exclude-path warning
+        assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+        assert_eq!(result.warnings[0].kind, ScanWarningKind::RootInExcludePath);
+        assert!(result.warnings[0].to_string().contains("already excluded"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_reports_missing_root() {
+        let missing = std::env::temp_dir().join("tmignore_test_missing_root");
+        let _ = fs::remove_dir_all(&missing);
+
+        let config = test_config(&missing);
+
+        let result = scan_optimized(&config, &[]);
+        assert!(result.matches.is_empty());
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].kind, ScanWarningKind::RootMissing);
+        assert_eq!(result.warnings[0].path, missing);
+    }
 }
@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use crate::logging::info;
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -102,12 +103,12 @@ pub fn install(force: bool) -> Result<()> {
         anyhow::bail!("launchctl bootstrap failed: {}", stderr.trim());
     }
 
-    println!("LaunchAgent installed and loaded.");
-    println!("  Label: {LABEL}");
-    println!("  Plist: {}", plist.display());
-    println!("  Logs:  {}", log_dir().display());
-    println!();
-    println!("The service will run `tmignore run` every 24 hours.");
+    info!("LaunchAgent installed and loaded.");
+    info!("  Label: {LABEL}");
+    info!("  Plist: {}", plist.display());
+    info!("  Logs:  {}", log_dir().display());
+    info!("");
+    info!("The service will run `tmignore run` every 24 hours.");
     Ok(())
 }
 
@@ -121,9 +122,9 @@ pub fn uninstall() -> Result<()> {
     if plist.exists() {
         std::fs::remove_file(&plist)
             .with_context(|| format!("Failed to remove {}", plist.display()))?;
-        println!("LaunchAgent uninstalled.");
+        info!("LaunchAgent uninstalled.");
     } else {
-        println!("LaunchAgent was not installed.");
+        info!("LaunchAgent was not installed.");
     }
 
     Ok(())
@@ -0,0 +1,393 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+const UNITS: &[(&str, u64)] = &[
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+];
+
+/// Parse a human size like "200MB", "1.5G", "512k" or "1024" (bytes).
+/// Units are binary (1 KB = 1024 bytes), matching `du -h`.
+pub fn parse_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size: {:?}", input))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => anyhow::bail!("Invalid size unit in {:?} (use B, KB, MB, GB or TB)", input),
+    };
+
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// Format a byte count for display, e.g. "48MB" or "1.2GB".
+pub fn format_size(bytes: u64) -> String {
+    for (unit, scale) in UNITS {
+        if bytes >= *scale {
+            let value = bytes as f64 / *scale as f64;
+            return if value < 10.0 {
+                format!("{:.1}{}", value, unit)
+            } else {
+                format!("{:.0}{}", value, unit)
+            };
+        }
+    }
+    format!("{}B", bytes)
+}
+
+/// Format a count with thousands separators, e.g. "42,103".
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Disk usage of a directory tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    pub bytes: u64,
+    /// Entries that aren't directories (files, symlinks, ...).
+    pub files: u64,
+}
+
+/// On-disk size of a directory tree in bytes, counted from allocated blocks, and
+/// the number of files in it. Stops early once `limit` bytes is reached, so
+/// threshold checks on huge trees stay cheap. Unreadable entries are skipped.
+pub fn dir_usage(path: &Path, limit: Option<u64>) -> Usage {
+    let mut usage = Usage::default();
+    for entry in WalkDir::new(path).follow_links(false).into_iter().flatten() {
+        if !entry.file_type().is_dir() {
+            usage.files += 1;
+        }
+        if let Ok(meta) = entry.metadata() {
+            usage.bytes += meta.blocks() * 512;
+        }
+        if limit.is_some_and(|l| usage.bytes >= l) {
+            break;
+        }
+    }
+    usage
+}
+
+/// Like `dir_usage`, for just the size in bytes.
+pub fn dir_size_bytes(path: &Path, limit: Option<u64>) -> u64 {
+    dir_usage(path, limit).bytes
+}
+
+/// A directory size remembered between runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedSize {
+    /// The directory's modification time when it was sized.
+    pub modified: SystemTime,
+    pub bytes: u64,
+    /// File count; missing from caches saved before counts were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
+}
+
+/// Directory sizes reused from the previous run while a directory's mtime is
+/// unchanged. Safe to share between worker threads.
+#[derive(Debug, Default)]
+pub struct SizeCache {
+    previous: BTreeMap<PathBuf, CachedSize>,
+    current: Mutex<BTreeMap<PathBuf, CachedSize>>,
+}
+
+impl SizeCache {
+    pub fn new(previous: BTreeMap<PathBuf, CachedSize>) -> Self {
+        Self {
+            previous,
+            current: Mutex::default(),
+        }
+    }
+
+    /// Size of `path` in bytes, from the cache if the directory hasn't been
+    /// modified since it was last sized.
+    pub fn size(&self, path: &Path) -> u64 {
+        self.lookup(path, false).bytes
+    }
+
+    /// Size and file count of `path`, from the cache like `size`.
+    pub fn usage(&self, path: &Path) -> Usage {
+        let cached = self.lookup(path, true);
+        Usage {
+            bytes: cached.bytes,
+            files: cached.files.unwrap_or_default(),
+        }
+    }
+
+    fn lookup(&self, path: &Path, need_files: bool) -> CachedSize {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let lookup = |cache: &BTreeMap<PathBuf, CachedSize>| {
+            cache
+                .get(path)
+                .filter(|c| Some(c.modified) == modified)
+                .filter(|c| !need_files || c.files.is_some())
+                .copied()
+        };
+
+        let current = lookup(&self.current.lock().expect("size cache lock poisoned"));
+        if let Some(cached) = current {
+            return cached;
+        }
+        let entry = lookup(&self.previous).unwrap_or_else(|| {
+            let usage = dir_usage(path, None);
+            CachedSize {
+                modified: modified.unwrap_or(SystemTime::UNIX_EPOCH),
+                bytes: usage.bytes,
+                files: Some(usage.files),
+            }
+        });
+
+        if modified.is_some() {
+            self.current
+                .lock()
+                .expect("size cache lock poisoned")
+                .insert(path.to_path_buf(), entry);
+        }
+        entry
+    }
+
+    /// Sizes looked up during this run, to save for the next one. Paths that
+    /// weren't looked up are dropped so the cache doesn't grow forever.
+    pub fn into_entries(self) -> BTreeMap<PathBuf, CachedSize> {
+        self.current.into_inner().expect("size cache lock poisoned")
+    }
+}
+
+/// A byte count that reads from config as either an integer or a string like "200MB".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ByteSize(pub u64);
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        // Write the largest unit that represents the value exactly, so it round-trips.
+        for (unit, scale) in UNITS {
+            if self.0 >= *scale && self.0.is_multiple_of(*scale) {
+                return serializer.serialize_str(&format!("{}{}", self.0 / scale, unit));
+            }
+        }
+        serializer.serialize_u64(self.0)
+    }
+}
+
+/// A byte count as written in a file: a number, or a size string like "200MB".
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSize {
+    Bytes(u64),
+    Text(String),
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match RawSize::deserialize(deserializer)? {
+            RawSize::Bytes(n) => Ok(ByteSize(n)),
+            RawSize::Text(s) => parse_size(&s).map(ByteSize).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Deserialize a byte count written either as a number or, by older versions
+/// of tmignore, as a display size such as "1.2GB". A display size that doesn't
+/// parse reads as 0, like the state file migration does, rather than losing
+/// the whole record.
+pub fn bytes_or_display<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<u64, D::Error> {
+    Ok(match RawSize::deserialize(deserializer)? {
+        RawSize::Bytes(n) => n,
+        RawSize::Text(s) => parse_size(&s).unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("10KB").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("200MB").unwrap(), 200 * 1024 * 1024);
+        assert_eq!(parse_size("5G").unwrap(), 5 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5gb").unwrap(), 3 * 512 * 1024 * 1024);
+        assert_eq!(parse_size(" 2 TB ").unwrap(), 2 << 40);
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("10XB").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(48 * 1024 * 1024), "48MB");
+        assert_eq!(format_size(1288490189), "1.2GB");
+    }
+
+    #[test]
+    fn test_byte_size_serde_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            size: ByteSize,
+        }
+
+        let parsed: Wrapper = toml::from_str(r#"size = "200MB""#).unwrap();
+        assert_eq!(parsed.size, ByteSize(200 * 1024 * 1024));
+        assert_eq!(toml::to_string(&parsed).unwrap().trim(), r#"size = "200MB""#);
+
+        let parsed: Wrapper = toml::from_str("size = 4096").unwrap();
+        assert_eq!(parsed.size, ByteSize(4096));
+
+        assert!(toml::from_str::<Wrapper>(r#"size = "big""#).is_err());
+    }
+
+    #[test]
+    fn test_size_cache_reuses_unmodified_dirs() {
+        let dir = std::env::temp_dir().join("tmignore_test_size_cache");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let modified = fs::metadata(&dir).unwrap().modified().unwrap();
+
+        // A matching mtime returns the cached value without walking the directory
+        let cached = CachedSize {
+            modified,
+            bytes: 42,
+            files: None,
+        };
+        let previous = BTreeMap::from([(dir.clone(), cached)]);
+        let cache = SizeCache::new(previous);
+        assert_eq!(cache.size(&dir), 42);
+
+        // A stale mtime is recomputed
+        let stale = CachedSize {
+            modified: SystemTime::UNIX_EPOCH,
+            bytes: 42,
+            files: None,
+        };
+        let cache = SizeCache::new(BTreeMap::from([(dir.clone(), stale)]));
+        let fresh = cache.size(&dir);
+        assert_ne!(fresh, 42);
+        let saved = cache.into_entries();
+        let expected = CachedSize {
+            modified,
+            bytes: fresh,
+            files: Some(0),
+        };
+        assert_eq!(saved.get(&dir), Some(&expected));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(42_103), "42,103");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_dir_usage_counts_files() {
+        let dir = std::env::temp_dir().join("tmignore_test_dir_usage");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), b"a").unwrap();
+        fs::write(dir.join("sub/b"), b"b").unwrap();
+        fs::write(dir.join("sub/c"), b"c").unwrap();
+
+        assert_eq!(dir_usage(&dir, None).files, 3);
+
+        // Cached sizes from before file counts were recorded are re-walked
+        let modified = fs::metadata(&dir).unwrap().modified().unwrap();
+        let cached = CachedSize {
+            modified,
+            bytes: 42,
+            files: None,
+        };
+        let cache = SizeCache::new(BTreeMap::from([(dir.clone(), cached)]));
+        assert_eq!(cache.size(&dir), 42);
+        assert_eq!(cache.usage(&dir).files, 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dir_usage_sums_allocated_blocks() {
+        let dir = std::env::temp_dir().join("tmignore_test_dir_usage_tree");
+        let _ = fs::remove_dir_all(&dir);
+        let dirs = [dir.clone(), dir.join("a"), dir.join("a/b"), dir.join("c")];
+        fs::create_dir_all(&dirs[2]).unwrap();
+        fs::create_dir_all(&dirs[3]).unwrap();
+        // Whole 4KB blocks of data take up exactly their length on disk
+        let files = [("one", 1), ("a/two", 2), ("a/b/three", 3), ("c/four", 16)];
+        for (name, blocks) in files {
+            fs::write(dir.join(name), vec![7u8; blocks * 4096]).unwrap();
+        }
+        // A sparse file counts what it occupies, not its length
+        let sparse = fs::File::create(dir.join("c/sparse")).unwrap();
+        sparse.set_len(1 << 30).unwrap();
+        // Links aren't followed, so the file they point to isn't counted again
+        std::os::unix::fs::symlink(dir.join("c/four"), dir.join("a/link")).unwrap();
+
+        let on_disk = |path: &Path| fs::symlink_metadata(path).unwrap().blocks() * 512;
+        let overhead: u64 = dirs.iter().map(|d| on_disk(d)).sum::<u64>()
+            + on_disk(&dir.join("c/sparse"))
+            + on_disk(&dir.join("a/link"));
+        assert!(on_disk(&dir.join("c/sparse")) < 1 << 20);
+
+        let usage = dir_usage(&dir, None);
+        assert_eq!(usage.bytes, 22 * 4096 + overhead);
+        assert_eq!(usage.files, 6);
+        let a_overhead = on_disk(&dirs[1]) + on_disk(&dirs[2]) + on_disk(&dir.join("a/link"));
+        assert_eq!(dir_size_bytes(&dir.join("a"), None), 5 * 4096 + a_overhead);
+        assert_eq!(dir_usage(&dir.join("missing"), None), Usage::default());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dir_size_bytes_limit() {
+        let dir = std::env::temp_dir().join("tmignore_test_size_limit");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..4 {
+            fs::write(dir.join(format!("f{i}")), vec![1u8; 64 * 1024]).unwrap();
+        }
+
+        let full = dir_size_bytes(&dir, None);
+        assert!(full >= 4 * 64 * 1024);
+
+        let capped = dir_size_bytes(&dir, Some(64 * 1024));
+        assert!(capped >= 64 * 1024);
+        assert!(capped < full);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A point-in-time copy of the backup exclusions and the config file, saved by
+/// `tmignore snapshot save` and replayed by `snapshot restore`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub created_at: String,
+    /// Contents of the config file when the snapshot was taken, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<String>,
+    /// Absolute paths that were excluded from backups.
+    pub paths: Vec<String>,
+}
+
+fn snapshot_dir() -> PathBuf {
+    crate::state::state_dir().join("snapshots")
+}
+
+fn snapshot_path(name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    Ok(snapshot_dir().join(format!("{name}.json")))
+}
+
+/// Snapshot names become file names, so keep them to a safe set of characters.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if !valid {
+        anyhow::bail!(
+            "Invalid snapshot name {:?}: use letters, digits, '-', '_' and '.'",
+            name
+        );
+    }
+    Ok(())
+}
+
+pub fn exists(name: &str) -> Result<bool> {
+    Ok(snapshot_path(name)?.exists())
+}
+
+/// Write the snapshot, replacing any with the same name. Returns its file.
+pub fn save(snapshot: &Snapshot) -> Result<PathBuf> {
+    let path = snapshot_path(&snapshot.name)?;
+    std::fs::create_dir_all(snapshot_dir()).context("Failed to create snapshot directory")?;
+    let contents = serde_json::to_string_pretty(snapshot).context("Failed to serialize snapshot")?;
+    // Write then rename, so an interrupted save never leaves a truncated file
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, contents)
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+pub fn load(name: &str) -> Result<Snapshot> {
+    let path = snapshot_path(name)?;
+    if !path.exists() {
+        anyhow::bail!("No snapshot named {:?}; see `tmignore snapshot list`", name);
+    }
+    read(&path)
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    let path = snapshot_path(name)?;
+    if !path.exists() {
+        anyhow::bail!("No snapshot named {:?}; see `tmignore snapshot list`", name);
+    }
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))
+}
+
+/// All saved snapshots, oldest first.
+pub fn list() -> Result<Vec<Snapshot>> {
+    read_all(&snapshot_dir())
+}
+
+fn read(path: &Path) -> Result<Snapshot> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn read_all(dir: &Path) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            snapshots.push(read(&path)?);
+        }
+    }
+    // Timestamps are RFC 3339 in UTC, so they sort as strings
+    snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.name.cmp(&b.name)));
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        for name in ["before-upgrade", "2024.06.01", "pre_sonoma"] {
+            assert!(validate_name(name).is_ok(), "{name}");
+        }
+        for name in ["", ".hidden", "../state", "a/b", "with space"] {
+            assert!(validate_name(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_read_all_sorts_by_creation() {
+        let dir = std::env::temp_dir().join("tmignore_test_snapshots");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, created_at) in [("newer", "2024-06-02T08:00:00Z"), ("older", "2024-06-01T08:00:00Z")] {
+            let snapshot = Snapshot {
+                name: name.to_string(),
+                created_at: created_at.to_string(),
+                config: None,
+                paths: vec!["/Users/me/app/node_modules".to_string()],
+            };
+            let contents = serde_json::to_string(&snapshot).unwrap();
+            std::fs::write(dir.join(format!("{name}.json")), contents).unwrap();
+        }
+        // Leftovers from an interrupted save are ignored
+        std::fs::write(dir.join("partial.json.tmp"), "{").unwrap();
+
+        let names: Vec<String> = read_all(&dir).unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["older", "newer"]);
+        assert!(read_all(&dir.join("missing")).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,23 +1,157 @@
 use anyhow::{Context, Result};
+use crate::size::CachedSize;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ExcludedEntry {
     pub path: String,
     pub pattern: String,
-    pub size: String,
+    /// Other patterns that matched the same directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub also_matched: Vec<String>,
+    /// On-disk size in bytes. Older state and history files hold a display size
+    /// such as "1.2GB" instead, which is read back as bytes (0 if it doesn't parse).
+    #[serde(deserialize_with = "crate::size::bytes_or_display")]
+    pub size: u64,
+    /// Number of files in the excluded directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WarningEntry {
+    pub path: String,
+    pub kind: String,
+    pub message: String,
+}
+
+/// A pattern match remembered for `run --incremental`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CachedMatch {
+    pub path: PathBuf,
+    pub pattern: String,
+    /// The run left this path excluded (newly or already).
+    pub excluded: bool,
+}
+
+/// Pattern matches from the last complete scan, reused by `run --incremental`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanCache {
+    /// `scanner::fingerprint` of the scan; a different one means the cache is stale.
+    pub fingerprint: String,
+    /// When the scan started, in seconds since the Unix epoch.
+    pub scanned_at: u64,
+    pub matches: Vec<CachedMatch>,
+}
+
+/// How many scan warnings are kept in the state file; the full count is always recorded.
+pub const MAX_SAVED_WARNINGS: usize = 20;
+
+/// Version of the state file format written by this build. Bump it, and add a
+/// `migrate_vN_to_vN+1` step to `migrate`, whenever the format changes.
+pub const STATE_VERSION: u32 = 5;
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RunState {
+    /// Format version; see `STATE_VERSION`.
+    #[serde(default = "current_state_version")]
+    pub state_version: u32,
+
     pub last_run: String,
     pub excluded_count: usize,
     pub already_excluded_count: usize,
+
+    /// Combined size of the already excluded matches, recorded by `run --re-check`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub already_excluded_bytes: Option<u64>,
+
+    pub entries: Vec<ExcludedEntry>,
+
+    /// Combined size of `entries` in bytes.
+    #[serde(default)]
+    pub total_size_bytes: u64,
+
+    /// Number of matches per pattern name, including active patterns that
+    /// matched nothing and `exclude_path` for configured exclude paths.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pattern_counts: BTreeMap<String, u64>,
+
+    #[serde(default)]
+    pub warning_count: usize,
+
+    /// Warning count per kind (e.g. "permission_denied", "io").
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub warning_kinds: BTreeMap<String, usize>,
+
+    /// First few scan warnings, capped at `MAX_SAVED_WARNINGS`.
+    #[serde(default)]
+    pub warnings: Vec<WarningEntry>,
+
+    /// The scan hit its time budget, so some directories were not visited.
+    #[serde(default)]
+    pub partial: bool,
+
+    /// Timestamp of the most recent run whose scan finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_complete_run: Option<String>,
+
+    /// Config profile active for the run, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Matches from this run's scan, if it was complete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_cache: Option<ScanCache>,
+
+    /// Sizes of this run's matches, reused next run for unchanged directories.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub size_cache: BTreeMap<PathBuf, CachedSize>,
+}
+
+/// Fold the entries recorded by earlier runs into `state`, for `accumulate_state`.
+/// This run's entry wins for a path both have. The counts and total size then
+/// cover every entry, and `already_excluded_count` becomes the number of this
+/// run's `matched` paths that earlier runs had already recorded.
+pub fn accumulate<'a>(
+    state: &mut RunState,
+    previous: &[ExcludedEntry],
+    matched: impl IntoIterator<Item = &'a str>,
+) {
+    let recorded: HashSet<&str> = previous.iter().map(|e| e.path.as_str()).collect();
+    state.already_excluded_count = matched.into_iter().filter(|p| recorded.contains(p)).count();
+
+    let fresh: HashSet<&str> = state.entries.iter().map(|e| e.path.as_str()).collect();
+    let mut merged: Vec<ExcludedEntry> = previous
+        .iter()
+        .filter(|e| !fresh.contains(e.path.as_str()))
+        .cloned()
+        .collect();
+    merged.append(&mut state.entries);
+
+    state.excluded_count = merged.len();
+    state.total_size_bytes = merged.iter().map(|e| e.size).sum();
+    state.entries = merged;
+}
+
+/// One line of the run history: what a run newly excluded. Appended after every
+/// run that excluded anything, so `list --since` can tell when a path was first
+/// excluded.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryRecord {
+    pub run_at: String,
     pub entries: Vec<ExcludedEntry>,
 }
 
-fn state_dir() -> PathBuf {
+fn current_state_version() -> u32 {
+    STATE_VERSION
+}
+
+pub(crate) fn state_dir() -> PathBuf {
     let home = std::env::var("HOME").expect("HOME not set");
     PathBuf::from(home).join(".local/state/tmignore")
 }
@@ -26,13 +160,104 @@ fn state_path() -> PathBuf {
     state_dir().join("state.json")
 }
 
+fn history_path() -> PathBuf {
+    state_dir().join("history.jsonl")
+}
+
+/// Files tmignore writes to its state directory.
+fn state_files() -> Vec<PathBuf> {
+    vec![state_path(), history_path()]
+}
+
+/// Delete all state files, leaving backup exclusions untouched.
+/// Returns the files that were removed.
+pub fn clear_state() -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for path in state_files() {
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// Delete the last run's state file (`reset`), keeping the run history.
+/// Returns whether there was one.
+pub fn remove_state() -> Result<bool> {
+    let path = state_path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    Ok(true)
+}
+
 pub fn save_state(state: &RunState) -> Result<()> {
     std::fs::create_dir_all(state_dir()).context("Failed to create state directory")?;
     let contents = serde_json::to_string_pretty(state).context("Failed to serialize state")?;
-    std::fs::write(state_path(), contents).context("Failed to write state file")?;
+    // Write then rename, so a run killed mid-save never leaves a truncated file
+    let tmp = state_path().with_extension("json.tmp");
+    std::fs::write(&tmp, contents).context("Failed to write state file")?;
+    std::fs::rename(&tmp, state_path()).context("Failed to write state file")?;
     Ok(())
 }
 
+/// Add a run's record to the end of the history. Records without entries are
+/// skipped, since they can't be the first sighting of any path.
+pub fn append_history(record: &HistoryRecord) -> Result<()> {
+    if record.entries.is_empty() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(state_dir()).context("Failed to create state directory")?;
+    let line = serde_json::to_string(record).context("Failed to serialize run history")?;
+    let path = history_path();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", line).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Every recorded run, oldest first.
+pub fn load_history() -> Result<Vec<HistoryRecord>> {
+    read_history(&history_path())
+}
+
+fn read_history(path: &Path) -> Result<Vec<HistoryRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    // A run killed mid-write can leave a partial last line; skip it
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Entries whose path was first excluded at or after `since` (a UTC timestamp
+/// as `logging::timestamp` writes them, or a prefix such as a date), with the
+/// time of that run. Paths excluded before `since` and again later are left out.
+pub fn first_excluded_since(
+    history: &[HistoryRecord],
+    since: &str,
+) -> Vec<(String, ExcludedEntry)> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for record in history {
+        for entry in &record.entries {
+            if seen.insert(entry.path.as_str()) && record.run_at.as_str() >= since {
+                found.push((record.run_at.clone(), entry.clone()));
+            }
+        }
+    }
+    found
+}
+
 pub fn load_state() -> Result<Option<RunState>> {
     let path = state_path();
     if !path.exists() {
@@ -42,8 +267,310 @@ pub fn load_state() -> Result<Option<RunState>> {
     let contents = std::fs::read_to_string(&path)
         .with_context(|| format!("Failed to read {}", path.display()))?;
 
-    let state: RunState = serde_json::from_str(&contents)
+    let raw: Value = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let migrated = migrate(raw).with_context(|| format!("Failed to load {}", path.display()))?;
+    let state: RunState = serde_json::from_value(migrated)
         .with_context(|| format!("Failed to parse {}", path.display()))?;
 
     Ok(Some(state))
 }
+
+/// Bring a state file written by any earlier version up to `STATE_VERSION`, one
+/// step at a time. Files from a newer tmignore are rejected rather than guessed at.
+fn migrate(mut raw: Value) -> Result<Value> {
+    // Files from before versioning have no field and are version 1
+    let version = raw.get("state_version").and_then(Value::as_u64).unwrap_or(1);
+    let version = u32::try_from(version).unwrap_or(u32::MAX);
+    if version > STATE_VERSION {
+        anyhow::bail!(
+            "state file version {} is newer than this tmignore supports ({}); \
+             upgrade tmignore or run `tmignore clean`",
+            version,
+            STATE_VERSION
+        );
+    }
+
+    if version < 2 {
+        raw = migrate_v1_to_v2(raw);
+    }
+    if version < 3 {
+        raw = migrate_v2_to_v3(raw);
+    }
+    if version < 4 {
+        raw = migrate_v3_to_v4(raw);
+    }
+    if version < 5 {
+        raw = migrate_v4_to_v5(raw);
+    }
+    Ok(raw)
+}
+
+/// v2 records the total size of the excluded entries. Fill it in from the
+/// entries' display sizes.
+fn migrate_v1_to_v2(mut raw: Value) -> Value {
+    let total: u64 = raw
+        .get("entries")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.get("size").and_then(Value::as_str))
+        .filter_map(|size| crate::size::parse_size(size).ok())
+        .sum();
+    if let Some(fields) = raw.as_object_mut() {
+        fields.insert("total_size_bytes".to_string(), total.into());
+        fields.insert("state_version".to_string(), 2.into());
+    }
+    raw
+}
+
+/// v3 adds the optional `already_excluded_bytes`, which v2 files don't have.
+fn migrate_v2_to_v3(mut raw: Value) -> Value {
+    if let Some(fields) = raw.as_object_mut() {
+        fields.insert("state_version".to_string(), 3.into());
+    }
+    raw
+}
+
+/// v4 adds the optional `pattern_counts`; older runs didn't record them.
+fn migrate_v3_to_v4(mut raw: Value) -> Value {
+    if let Some(fields) = raw.as_object_mut() {
+        fields.insert("state_version".to_string(), 4.into());
+    }
+    raw
+}
+
+/// v5 stores each entry's size in bytes rather than as a display size. Sizes
+/// that don't parse are dropped to 0 rather than failing the whole file.
+fn migrate_v4_to_v5(mut raw: Value) -> Value {
+    let entries = raw.get_mut("entries").and_then(Value::as_array_mut);
+    for entry in entries.into_iter().flatten() {
+        if let Some(size) = entry.get("size").and_then(Value::as_str) {
+            let bytes = crate::size::parse_size(size).unwrap_or(0);
+            entry["size"] = bytes.into();
+        }
+    }
+    if let Some(fields) = raw.as_object_mut() {
+        fields.insert("state_version".to_string(), 5.into());
+    }
+    raw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn history_entry(path: &str, size: u64) -> ExcludedEntry {
+        ExcludedEntry {
+            path: path.to_string(),
+            pattern: "node".to_string(),
+            also_matched: Vec::new(),
+            size,
+            files: None,
+        }
+    }
+
+    #[test]
+    fn test_first_excluded_since() {
+        let history = vec![
+            HistoryRecord {
+                run_at: "2024-05-27T09:00:00Z".to_string(),
+                entries: vec![history_entry("~/old/node_modules", 1 << 30)],
+            },
+            HistoryRecord {
+                run_at: "2024-06-03T09:00:00Z".to_string(),
+                entries: vec![
+                    history_entry("~/old/node_modules", 1100 << 20),
+                    history_entry("~/new/node_modules", 200 << 20),
+                ],
+            },
+        ];
+        let found = first_excluded_since(&history, "2024-06-01");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "2024-06-03T09:00:00Z");
+        assert_eq!(found[0].1.path, "~/new/node_modules");
+        assert_eq!(first_excluded_since(&history, "2024-01-01").len(), 2);
+        assert!(first_excluded_since(&history, "2024-06-04").is_empty());
+    }
+
+    #[test]
+    fn test_read_history_skips_partial_lines() {
+        let path = std::env::temp_dir().join("tmignore_test_history.jsonl");
+        let record = HistoryRecord {
+            run_at: "2024-06-03T09:00:00Z".to_string(),
+            entries: vec![history_entry("~/app/node_modules", 200 << 20)],
+        };
+        let line = serde_json::to_string(&record).unwrap();
+        // Lines from before sizes were stored in bytes hold display sizes
+        let old = json!({
+            "run_at": "2024-06-01T09:00:00Z",
+            "entries": [
+                { "path": "~/web/node_modules", "pattern": "node", "size": "1.5GB" },
+                { "path": "~/api/vendor", "pattern": "composer", "size": "?" }
+            ]
+        });
+        std::fs::write(&path, format!("{old}\n{line}\n{{\"run_at\": \"2024-06-0")).unwrap();
+        let history = read_history(&path).unwrap();
+        assert_eq!(history.len(), 2);
+        // A display size that doesn't parse counts as 0, as in the state migration
+        let sizes: Vec<u64> = history[0].entries.iter().map(|e| e.size).collect();
+        assert_eq!(sizes, vec![3 << 29, 0]);
+        assert_eq!(history[1].entries[0].path, "~/app/node_modules");
+        assert_eq!(history[1].entries[0].size, 200 << 20);
+        let _ = std::fs::remove_file(&path);
+        assert!(read_history(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_v1_to_v2() {
+        let v1 = json!({
+            "last_run": "2024-03-01T12:00:00Z",
+            "excluded_count": 2,
+            "already_excluded_count": 5,
+            "entries": [
+                { "path": "~/a/node_modules", "pattern": "node", "size": "1.5G" },
+                { "path": "~/b/target", "pattern": "cargo", "size": "512M" }
+            ]
+        });
+
+        let v2 = migrate_v1_to_v2(v1.clone());
+        assert_eq!(v2["state_version"], 2);
+
+        let state: RunState = serde_json::from_value(migrate(v1).unwrap()).unwrap();
+        assert_eq!(state.state_version, STATE_VERSION);
+        assert_eq!(state.total_size_bytes, (3 << 29) + (512 << 20));
+        assert_eq!(state.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_migrate_v2_to_v3() {
+        let v2 = json!({
+            "state_version": 2,
+            "last_run": "2024-03-01T12:00:00Z",
+            "excluded_count": 0,
+            "already_excluded_count": 0,
+            "entries": [],
+            "total_size_bytes": 7
+        });
+        assert_eq!(migrate_v2_to_v3(v2.clone())["state_version"], 3);
+        let state: RunState = serde_json::from_value(migrate(v2).unwrap()).unwrap();
+        assert_eq!(state.state_version, STATE_VERSION);
+        assert_eq!(state.total_size_bytes, 7);
+        assert_eq!(state.already_excluded_bytes, None);
+    }
+
+    #[test]
+    fn test_migrate_v3_to_v4() {
+        let v3 = json!({
+            "state_version": 3,
+            "last_run": "2024-03-01T12:00:00Z",
+            "excluded_count": 0,
+            "already_excluded_count": 0,
+            "entries": []
+        });
+        assert_eq!(migrate_v3_to_v4(v3.clone())["state_version"], 4);
+        let state: RunState = serde_json::from_value(migrate(v3).unwrap()).unwrap();
+        assert_eq!(state.state_version, STATE_VERSION);
+        assert!(state.pattern_counts.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_v4_to_v5() {
+        let v4 = json!({
+            "state_version": 4,
+            "last_run": "2024-03-01T12:00:00Z",
+            "excluded_count": 3,
+            "already_excluded_count": 0,
+            "entries": [
+                { "path": "~/a/node_modules", "pattern": "node", "size": "1.5GB" },
+                { "path": "~/b/target", "pattern": "cargo", "size": "512B", "files": 4 },
+                { "path": "~/c/vendor", "pattern": "composer", "size": "?" }
+            ],
+            "total_size_bytes": 1610613248
+        });
+        let v5 = migrate_v4_to_v5(v4.clone());
+        assert_eq!(v5["state_version"], 5);
+        assert_eq!(v5["entries"][0]["size"], 3u64 << 29);
+        let state: RunState = serde_json::from_value(migrate(v4).unwrap()).unwrap();
+        assert_eq!(state.state_version, STATE_VERSION);
+        let sizes: Vec<u64> = state.entries.iter().map(|e| e.size).collect();
+        assert_eq!(sizes, vec![3 << 29, 512, 0]);
+        assert_eq!(state.entries[1].files, Some(4));
+    }
+
+    #[test]
+    fn test_accumulate_merges_entries_by_path() {
+        let entry = |path: &str, size: u64| ExcludedEntry {
+            path: path.to_string(),
+            pattern: "node".to_string(),
+            also_matched: Vec::new(),
+            size,
+            files: None,
+        };
+        let previous = vec![entry("~/a/node_modules", 1 << 20), entry("~/b/node_modules", 2 << 20)];
+        let mut state: RunState = serde_json::from_value(json!({
+            "last_run": "2024-03-02T12:00:00Z",
+            "excluded_count": 2,
+            "already_excluded_count": 7,
+            "entries": [
+                { "path": "~/b/node_modules", "pattern": "node", "size": 3 << 20 },
+                { "path": "~/c/node_modules", "pattern": "node", "size": 4 << 20 }
+            ]
+        }))
+        .unwrap();
+
+        accumulate(&mut state, &previous, ["~/a/node_modules", "~/b/node_modules", "~/c/node_modules"]);
+        let paths: Vec<(&str, u64)> = state
+            .entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.size))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("~/a/node_modules", 1 << 20),
+                ("~/b/node_modules", 3 << 20),
+                ("~/c/node_modules", 4 << 20),
+            ]
+        );
+        assert_eq!(state.excluded_count, 3);
+        assert_eq!(state.already_excluded_count, 2);
+        assert_eq!(state.total_size_bytes, 8 << 20);
+    }
+
+    #[test]
+    fn test_migrate_current_version_is_unchanged() {
+        let current = json!({
+            "state_version": STATE_VERSION,
+            "last_run": "2024-03-01T12:00:00Z",
+            "excluded_count": 0,
+            "already_excluded_count": 0,
+            "already_excluded_bytes": 1024,
+            "entries": [],
+            "pattern_counts": { "node": 3, "cargo": 0 },
+            "total_size_bytes": 7
+        });
+        assert_eq!(migrate(current.clone()).unwrap(), current);
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_version() {
+        let future = json!({ "state_version": STATE_VERSION + 1 });
+        let err = migrate(future).unwrap_err();
+        assert!(err.to_string().contains("newer"), "{err}");
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let raw = json!({
+            "state_version": 2,
+            "last_run": "2024-03-01T12:00:00Z",
+            "excluded_count": 0,
+            "already_excluded_count": 0,
+            "entries": [],
+            "surprise": true
+        });
+        assert!(serde_json::from_value::<RunState>(raw).is_err());
+    }
+}
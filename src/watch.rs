@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use ::notify::event::{EventKind, ModifyKind};
+use ::notify::{RecursiveMode, Watcher};
+use nix::sys::resource::{Resource, getrlimit, setrlimit};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How often the watch loop wakes up to check `cancel` when nothing happens.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Open files left for everything but the watches (the log file, `.gitignore`
+/// reads, `tmutil`).
+const SPARE_DESCRIPTORS: u64 = 64;
+
+/// Make sure watching `count` directories won't run out of file descriptors,
+/// before any is watched. kqueue (macOS) holds one per watched directory;
+/// inotify (Linux) doesn't.
+pub fn check_capacity(count: usize) -> Result<()> {
+    if cfg!(target_os = "macos") {
+        ensure_descriptors(count as u64 + SPARE_DESCRIPTORS)?;
+    }
+    Ok(())
+}
+
+/// Raise the soft open file limit to `needed` if it is lower, failing when
+/// the hard limit (or the system) doesn't allow that many.
+fn ensure_descriptors(needed: u64) -> Result<()> {
+    let (soft, hard) =
+        getrlimit(Resource::RLIMIT_NOFILE).context("Failed to read the open file limit")?;
+    if soft >= needed {
+        return Ok(());
+    }
+    // macOS also caps it below the hard limit (kern.maxfilesperproc), which
+    // shows up as setrlimit failing
+    if needed <= hard && setrlimit(Resource::RLIMIT_NOFILE, needed, hard).is_ok() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "Watching needs about {} open files, but only {} are allowed; narrow the scan with \
+         --root, skip_paths or skip_hidden",
+        needed,
+        soft
+    )
+}
+
+/// Watch each of `dirs`, but not what lies below them, and hand every directory
+/// created in one to `on_created`, until `cancel` is set. A new directory is
+/// watched in turn when `on_created` returns true. kqueue (macOS) holds a file
+/// descriptor per watched directory, so callers pass only the directories
+/// worth watching, not a whole tree, and call `check_capacity` first.
+pub fn watch_directories(
+    dirs: &[PathBuf],
+    cancel: &AtomicBool,
+    mut on_created: impl FnMut(&Path) -> bool,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        ::notify::recommended_watcher(tx).context("Failed to start the file system watcher")?;
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive).with_context(|| {
+            format!(
+                "Failed to watch {} ({} directories to watch; narrow them with --root, \
+                 skip_paths or skip_hidden)",
+                dir.display(),
+                dirs.len()
+            )
+        })?;
+    }
+    let mut watched: HashSet<PathBuf> = dirs.iter().cloned().collect();
+    // Every directory handed to `on_created` (or watched from the start), so
+    // each is only reported once
+    let mut known = watched.clone();
+
+    while !cancel.load(Ordering::Relaxed) {
+        let event = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => event,
+            // A lost event only means a directory is picked up by the next run
+            Ok(Err(_)) | Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        // kqueue doesn't reliably say what was created in a directory watched
+        // on its own, so list the directory that changed instead
+        let mut pending: Vec<PathBuf> = match event.kind {
+            EventKind::Create(_) => {
+                event.paths.iter().filter_map(|p| p.parent()).map(Path::to_path_buf).collect()
+            }
+            EventKind::Modify(ModifyKind::Data(_)) => event.paths,
+            _ => continue,
+        };
+        pending.retain(|dir| watched.contains(dir));
+        // A new directory may already hold others (e.g. a tree moved or
+        // unpacked into place), so list the ones that are watched too
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if entry.file_type().is_ok_and(|t| t.is_dir())
+                    && known.insert(path.clone())
+                    && on_created(&path)
+                    && watcher.watch(&path, RecursiveMode::NonRecursive).is_ok()
+                {
+                    watched.insert(path.clone());
+                    pending.push(path);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::thread;
+
+    #[test]
+    fn test_watch_reports_and_follows_new_directories() {
+        let dir = std::env::temp_dir().join("tmignore_test_watch");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let mut created = Vec::new();
+        thread::scope(|s| {
+            s.spawn(|| {
+                for sub in ["new", "new/inner", "skipped", "skipped/inner", "last"] {
+                    thread::sleep(Duration::from_millis(200));
+                    fs::create_dir(dir.join(sub)).unwrap();
+                }
+                // Don't hang if an event never arrives
+                for _ in 0..50 {
+                    if cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+                cancel.store(true, Ordering::Relaxed);
+            });
+            watch_directories(std::slice::from_ref(&dir), &cancel, |path| {
+                created.push(path.to_path_buf());
+                if path.ends_with("last") {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                !path.ends_with("skipped")
+            })
+            .unwrap();
+        });
+        // Directories in a new one are watched too, unless it was turned down
+        assert_eq!(created, ["new", "new/inner", "skipped", "last"].map(|sub| dir.join(sub)));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ensure_descriptors() {
+        let (soft, _) = getrlimit(Resource::RLIMIT_NOFILE).unwrap();
+        assert!(ensure_descriptors(soft).is_ok());
+        let err = ensure_descriptors(u64::MAX).unwrap_err();
+        assert!(err.to_string().contains("open files"), "{err}");
+    }
+}